@@ -0,0 +1,47 @@
+use std::{env, fs, path::Path};
+
+/// Crates that decode and encode the formats, whose versions are printed by `imy doctor`.
+const CODECS: [&str; 10] = [
+    "image",
+    "png",
+    "zune-jpeg",
+    "jpeg-decoder",
+    "gif",
+    "image-webp",
+    "tiff",
+    "exr",
+    "qoi",
+    "ravif",
+];
+
+/// Passes the versions of the codecs in Cargo.lock to the build as IMY_CODECS, e.g.
+/// "image 0.25.5;png 0.17.14". Builds without a lock file have no versions.
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let lock_file = Path::new(&manifest_dir).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_file.display());
+    let lock = fs::read_to_string(&lock_file).unwrap_or_default();
+    let mut versions = Vec::new();
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        let Some(name) = line
+            .strip_prefix("name = \"")
+            .and_then(|name| name.strip_suffix('"'))
+        else {
+            continue;
+        };
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix("version = \""))
+            .and_then(|version| version.strip_suffix('"'));
+        if let (true, Some(version)) = (CODECS.contains(&name), version) {
+            versions.push((name, version));
+        }
+    }
+    versions.sort_by_key(|(name, _)| CODECS.iter().position(|codec| codec == name));
+    let versions = versions
+        .iter()
+        .map(|(name, version)| format!("{name} {version}"))
+        .collect::<Vec<_>>();
+    println!("cargo:rustc-env=IMY_CODECS={}", versions.join(";"));
+}
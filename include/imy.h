@@ -0,0 +1,41 @@
+/* C interface of the imy shared library, which is built with the `ffi` feature. */
+
+#ifndef IMY_H
+#define IMY_H
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Options of imy_convert(). A null pointer takes the defaults. */
+typedef struct ImyConvertOptions {
+    /* Quality of lossy formats (jpeg, avif) from 0 to 100, or -1 for the default */
+    int quality;
+    /* Directory to write the outputs to, or null to write them next to the inputs */
+    const char *out_dir;
+    /* Whether existing outputs are replaced instead of failing */
+    bool overwrite;
+} ImyConvertOptions;
+
+/* Converts an image, or all images in a directory, to the format, e.g. "webp". Returns 0 on
+ * success and the exit code of the command line tool otherwise. */
+int imy_convert(const char *path, const char *format, const ImyConvertOptions *options);
+
+/* Returns the information of `imy info --output json` about an image or a directory, or null on
+ * failure. The string has to be freed with imy_string_free(). */
+char *imy_info_json(const char *path);
+
+/* Frees a string that was returned by imy. */
+void imy_string_free(char *string);
+
+/* Returns the message of the last error on this thread, or null if there was none. The string is
+ * valid until the next call of imy on this thread. */
+const char *imy_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,65 @@
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+/// Direction in which the images are placed next to each other.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Side by side from left to right
+    #[default]
+    Horizontal,
+    /// Stacked from top to bottom
+    Vertical,
+}
+
+/// Alignment of images that are smaller than the largest image across the direction.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    /// Top or left
+    Start,
+    #[default]
+    Center,
+    /// Bottom or right
+    End,
+}
+
+/// Places the images next to each other with `gap` pixels in between. Space that is not covered
+/// by an image is filled with the background.
+pub fn append(
+    images: &[DynamicImage],
+    direction: Direction,
+    align: Align,
+    gap: u32,
+    background: Rgba<u8>,
+) -> RgbaImage {
+    let gaps = gap * images.len().saturating_sub(1) as u32;
+    let along = |img: &DynamicImage| match direction {
+        Direction::Horizontal => img.width(),
+        Direction::Vertical => img.height(),
+    };
+    let across = |img: &DynamicImage| match direction {
+        Direction::Horizontal => img.height(),
+        Direction::Vertical => img.width(),
+    };
+    let length = images.iter().map(along).sum::<u32>() + gaps;
+    let breadth = images.iter().map(across).max().unwrap_or(0);
+    let (width, height) = match direction {
+        Direction::Horizontal => (length, breadth),
+        Direction::Vertical => (breadth, length),
+    };
+
+    let mut canvas = RgbaImage::from_pixel(width, height, background);
+    let mut position = 0;
+    for img in images {
+        let offset = match align {
+            Align::Start => 0,
+            Align::Center => (breadth - across(img)) / 2,
+            Align::End => breadth - across(img),
+        };
+        let (x, y) = match direction {
+            Direction::Horizontal => (position, offset),
+            Direction::Vertical => (offset, position),
+        };
+        imageops::overlay(&mut canvas, &img.to_rgba8(), x as i64, y as i64);
+        position += along(img) + gap;
+    }
+    canvas
+}
@@ -0,0 +1,154 @@
+//! Async API for embedding imy in applications that run on tokio, e.g. GUIs and servers.
+//!
+//! The conversions run on the blocking thread pool of tokio, so they don't block the async
+//! threads. A batch reports its progress as a stream of [`Progress`] events and stops starting
+//! new conversions when its [`CancellationToken`] is cancelled.
+//!
+//! ```no_run
+//! # async fn example() -> miette::Result<()> {
+//! use imy::asynchronous::{self, CancellationToken, Progress};
+//!
+//! let options = imy::ConvertOptions {
+//!     target_format: "webp".to_owned(),
+//!     ..Default::default()
+//! };
+//! let cancel = CancellationToken::new();
+//! let mut batch = asynchronous::convert_batch(vec!["a.png".into()], options, cancel.clone());
+//! while let Some(event) = batch.progress.recv().await {
+//!     if let Progress::Failed { path, message } = event {
+//!         eprintln!("{}: {message}", path.display());
+//!     }
+//! }
+//! let summary = batch.finish().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::{JoinHandle, JoinSet},
+};
+pub use tokio_util::sync::CancellationToken;
+
+use crate::{error_message, ConvertOptions};
+
+/// Event of a batch, which is sent when a conversion starts and when it ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    Started {
+        path: PathBuf,
+    },
+    Converted {
+        path: PathBuf,
+    },
+    Failed {
+        path: PathBuf,
+        message: String,
+    },
+    /// The conversion wasn't started because the batch was cancelled
+    Cancelled {
+        path: PathBuf,
+    },
+}
+
+/// Outcome of a batch, with the files in the order in which they finished.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub converted: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+    pub cancelled: Vec<PathBuf>,
+}
+
+/// Batch of conversions that runs in the background.
+pub struct Batch {
+    /// Events of the conversions. The stream ends when the batch is finished
+    pub progress: mpsc::UnboundedReceiver<Progress>,
+    handle: JoinHandle<Summary>,
+}
+
+impl Batch {
+    /// Waits until all conversions are finished or cancelled.
+    pub async fn finish(self) -> Summary {
+        self.handle.await.expect("batch panicked")
+    }
+}
+
+/// Converts an image, or all images in a directory, like [`crate::convert()`] without blocking the
+/// async threads. Existing outputs are only replaced with `force`.
+pub async fn convert(path: PathBuf, options: ConvertOptions) -> miette::Result<()> {
+    tokio::task::spawn_blocking(move || convert_blocking(&path, &options))
+        .await
+        .expect("conversion panicked")
+}
+
+/// Converts the files concurrently, at most `options.jobs` at a time, which defaults to the number
+/// of CPUs. Conversions that are running when the token is cancelled are finished, the others are
+/// reported as cancelled. Must be called within a tokio runtime.
+pub fn convert_batch(
+    paths: Vec<PathBuf>,
+    options: ConvertOptions,
+    cancel: CancellationToken,
+) -> Batch {
+    let (sender, progress) = mpsc::unbounded_channel();
+    let jobs = options
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |jobs| jobs.get()));
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let options = Arc::new(options);
+    let handle = tokio::spawn(async move {
+        let mut summary = Summary::default();
+        let mut tasks = JoinSet::new();
+        for path in paths {
+            let permit = tokio::select! {
+                permit = semaphore.clone().acquire_owned() => permit.ok(),
+                () = cancel.cancelled() => None,
+            };
+            let Some(permit) = permit.filter(|_| !cancel.is_cancelled()) else {
+                let _ = sender.send(Progress::Cancelled { path: path.clone() });
+                summary.cancelled.push(path);
+                continue;
+            };
+            let _ = sender.send(Progress::Started { path: path.clone() });
+            let options = options.clone();
+            let sender = sender.clone();
+            tasks.spawn_blocking(move || {
+                let _permit = permit;
+                let result =
+                    convert_blocking(&path, &options).map_err(|report| error_message(&report));
+                let event = match &result {
+                    Ok(()) => Progress::Converted { path: path.clone() },
+                    Err(message) => Progress::Failed {
+                        path: path.clone(),
+                        message: message.clone(),
+                    },
+                };
+                let _ = sender.send(event);
+                (path, result)
+            });
+        }
+        // The sender is dropped with the last conversion, which ends the stream of events
+        drop(sender);
+        while let Some(joined) = tasks.join_next().await {
+            match joined.expect("conversion panicked") {
+                (path, Ok(())) => summary.converted.push(path),
+                (path, Err(message)) => summary.failed.push((path, message)),
+            }
+        }
+        summary
+    });
+    Batch { progress, handle }
+}
+
+/// Converts without printing, the events take the place of the output.
+fn convert_blocking(path: &Path, options: &ConvertOptions) -> miette::Result<()> {
+    let mut sink = io::sink();
+    let mut context = crate::Context::new(&mut sink);
+    crate::convert(&mut context, path, options)
+}
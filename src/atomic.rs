@@ -0,0 +1,54 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::error::Error;
+
+/// Writes a file through a temporary file in the same directory, which replaces the file once
+/// `write` succeeded. An interrupted or failed write never leaves a truncated file at `path`.
+pub fn write(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> miette::Result<()>,
+) -> miette::Result<()> {
+    let temp_path = temp_path(path);
+    let write_err = |source| Error::WriteFile {
+        path: path.to_owned(),
+        source,
+    };
+    let file = File::create(&temp_path).map_err(write_err)?;
+    let mut writer = BufWriter::new(file);
+    let result = write(&mut writer).and_then(|()| {
+        let file = writer
+            .into_inner()
+            .map_err(|error| write_err(error.into_error()))?;
+        file.sync_all().map_err(write_err)?;
+        drop(file);
+        fs::rename(&temp_path, path).map_err(write_err)?;
+        Ok(())
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Writes the data to a file like [`write`].
+pub fn write_data(path: &Path, data: &[u8]) -> miette::Result<()> {
+    write(path, |writer| {
+        writer.write_all(data).map_err(|source| {
+            Error::WriteFile {
+                path: path.to_owned(),
+                source,
+            }
+            .into()
+        })
+    })
+}
+
+/// Hidden file next to the path, which is skipped when the directory is searched for images.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.tmp", std::process::id()))
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{atomic, error::Error};
+
+/// Name of the cache in the converted directory, which is hidden like the temporary files.
+pub const FILE_NAME: &str = ".imy-cache";
+
+/// Sources of earlier conversions in a directory, keyed by their path relative to it, which lets
+/// incremental conversions skip the files whose outputs are up to date.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    entries: BTreeMap<String, Entry>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+/// State of a source when it was converted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    modified: u64,
+    /// CRC-32 of the contents, which detects unchanged files with a new modification time
+    hash: u32,
+    /// Fingerprint of the options of the conversion
+    options: u32,
+    /// Path of the output relative to the directory
+    output: String,
+}
+
+impl Cache {
+    /// Loads the cache of the directory. A missing or unreadable cache is empty, so that all
+    /// files are converted again.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(FILE_NAME);
+        let cache = fs::read(&path)
+            .ok()
+            .and_then(|data| match serde_json::from_slice::<Self>(&data) {
+                Ok(cache) => Some(cache),
+                Err(error) => {
+                    tracing::warn!("Ignoring the invalid cache {}: {error}", path.display());
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            dir: dir.to_owned(),
+            ..cache
+        }
+    }
+
+    /// Writes the cache into its directory.
+    pub fn save(&self) -> miette::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(Error::Json)?;
+        atomic::write_data(&self.dir.join(FILE_NAME), (json + "\n").as_bytes())
+    }
+
+    /// Whether the output was written by an earlier conversion of the source.
+    pub fn is_output_of(&self, source: &Path, output: &Path) -> bool {
+        self.entries
+            .get(&self.key(source))
+            .is_some_and(|entry| entry.output == self.key(output))
+    }
+
+    /// Whether the file is the output of an earlier conversion, which is not converted itself.
+    pub fn is_output(&self, path: &Path) -> bool {
+        let key = self.key(path);
+        self.entries.values().any(|entry| entry.output == key)
+    }
+
+    /// Whether the output exists and was converted from the same contents with the same options.
+    pub fn is_fresh(&self, source: &Path, output: &Path, options: u32) -> bool {
+        let Some(entry) = self.entries.get(&self.key(source)) else {
+            return false;
+        };
+        if entry.output != self.key(output) || entry.options != options || !output.exists() {
+            return false;
+        }
+        let Some((size, modified)) = file_state(source) else {
+            return false;
+        };
+        if (size, modified) == (entry.size, entry.modified) {
+            return true;
+        }
+        // Checkouts and copies change the modification time without changing the contents
+        size == entry.size
+            && fs::read(source).is_ok_and(|data| crc32fast::hash(&data) == entry.hash)
+    }
+
+    /// Records the conversion of the source into the output.
+    pub fn insert(&mut self, path: &Path, output: &Path, options: u32) -> miette::Result<()> {
+        let data = fs::read(path).map_err(|source| Error::ReadFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        let (size, modified) = file_state(path).unwrap_or((data.len() as u64, 0));
+        let entry = Entry {
+            size,
+            modified,
+            hash: crc32fast::hash(&data),
+            options,
+            output: self.key(output),
+        };
+        self.entries.insert(self.key(path), entry);
+        Ok(())
+    }
+
+    /// Path relative to the directory, so that the cache doesn't depend on the working directory.
+    fn key(&self, path: &Path) -> String {
+        path.strip_prefix(&self.dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Size and modification time of a file.
+fn file_state(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos() as u64))
+}
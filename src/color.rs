@@ -0,0 +1,337 @@
+use image::{ColorType, DynamicImage, ImageFormat, Rgba};
+
+/// Transfer function with which the values of an image are encoded.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Values are proportional to the light intensity
+    Linear,
+    /// Values are encoded with the sRGB transfer function
+    Srgb,
+}
+
+/// Pixel layout of the output.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputColorType {
+    /// 8-bit grayscale
+    L8,
+    /// 8-bit grayscale with alpha
+    La8,
+    /// 8-bit RGB
+    Rgb8,
+    /// 8-bit RGB with alpha
+    Rgba8,
+    /// 16-bit grayscale
+    L16,
+    /// 16-bit grayscale with alpha
+    La16,
+    /// 16-bit RGB
+    Rgb16,
+    /// 16-bit RGB with alpha
+    Rgba16,
+}
+
+impl From<OutputColorType> for ColorType {
+    fn from(color_type: OutputColorType) -> Self {
+        match color_type {
+            OutputColorType::L8 => ColorType::L8,
+            OutputColorType::La8 => ColorType::La8,
+            OutputColorType::Rgb8 => ColorType::Rgb8,
+            OutputColorType::Rgba8 => ColorType::Rgba8,
+            OutputColorType::L16 => ColorType::L16,
+            OutputColorType::La16 => ColorType::La16,
+            OutputColorType::Rgb16 => ColorType::Rgb16,
+            OutputColorType::Rgba16 => ColorType::Rgba16,
+        }
+    }
+}
+
+/// Converts the image to the given pixel layout.
+pub fn to_color_type(img: DynamicImage, color_type: OutputColorType) -> DynamicImage {
+    match color_type {
+        OutputColorType::L8 => DynamicImage::ImageLuma8(img.to_luma8()),
+        OutputColorType::La8 => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        OutputColorType::Rgb8 => DynamicImage::ImageRgb8(img.to_rgb8()),
+        OutputColorType::Rgba8 => DynamicImage::ImageRgba8(img.to_rgba8()),
+        OutputColorType::L16 => DynamicImage::ImageLuma16(img.to_luma16()),
+        OutputColorType::La16 => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+        OutputColorType::Rgb16 => DynamicImage::ImageRgb16(img.to_rgb16()),
+        OutputColorType::Rgba16 => DynamicImage::ImageRgba16(img.to_rgba16()),
+    }
+}
+
+/// Number of bits used for a single channel of a pixel.
+pub fn bits_per_channel(color: ColorType) -> u16 {
+    color.bits_per_pixel() / color.channel_count() as u16
+}
+
+fn supports_16_bit(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Tiff | ImageFormat::Pnm | ImageFormat::Farbfeld
+    )
+}
+
+/// Bits per channel that are stored in the target format. The depth of the source is kept if the
+/// target supports it, unless `bit_depth` requests a specific depth.
+pub fn target_bits_per_channel(
+    source_color: ColorType,
+    target_format: ImageFormat,
+    bit_depth: Option<u8>,
+) -> u16 {
+    match target_format {
+        ImageFormat::OpenExr | ImageFormat::Hdr => return 32,
+        ImageFormat::Farbfeld => return 16,
+        _ => {}
+    }
+
+    let bits = bit_depth
+        .map(u16::from)
+        .unwrap_or_else(|| bits_per_channel(source_color));
+    if bits > 8 {
+        if supports_16_bit(target_format) {
+            return 16;
+        }
+        if bit_depth.is_some() {
+            tracing::warn!(
+                "Target format {:?} does not support 16-bit, falling back to 8-bit",
+                target_format
+            );
+        }
+    }
+    8
+}
+
+/// Converts the image to the bit depth that is stored in the target format, see
+/// `target_bits_per_channel`.
+pub fn adapt_bit_depth(
+    img: DynamicImage,
+    source_color: ColorType,
+    target_format: ImageFormat,
+    bit_depth: Option<u8>,
+) -> DynamicImage {
+    match target_format {
+        ImageFormat::OpenExr | ImageFormat::Hdr => return to_32_bit_float(img, target_format),
+        ImageFormat::Farbfeld => return DynamicImage::ImageRgba16(img.to_rgba16()),
+        _ => {}
+    }
+    match target_bits_per_channel(source_color, target_format, bit_depth) {
+        16 => to_16_bit(img, source_color),
+        _ => to_8_bit(img, source_color),
+    }
+}
+
+/// Reduces the image to 8 bits per channel with Floyd-Steinberg error diffusion, which avoids
+/// banding in gradients compared to rounding every pixel on its own.
+pub fn dither_to_8_bit(img: DynamicImage, layout: ColorType) -> DynamicImage {
+    let mut buffer = img.into_rgba32f();
+    let (width, height) = (buffer.width() as usize, buffer.height() as usize);
+    let channel_count = if layout.has_alpha() { 4 } else { 3 };
+    let values: &mut [f32] = &mut buffer;
+    for y in 0..height {
+        for x in 0..width {
+            for channel in 0..channel_count {
+                let index = (y * width + x) * 4 + channel;
+                let value = values[index].clamp(0.0, 1.0);
+                let quantized = (value * 255.0).round() / 255.0;
+                values[index] = quantized;
+                let error = value - quantized;
+                let mut diffuse = |dx: isize, dy: usize, factor: f32| {
+                    let (x, y) = (x as isize + dx, y + dy);
+                    if x >= 0 && (x as usize) < width && y < height {
+                        values[(y * width + x as usize) * 4 + channel] += error * factor;
+                    }
+                };
+                diffuse(1, 0, 7.0 / 16.0);
+                diffuse(-1, 1, 3.0 / 16.0);
+                diffuse(0, 1, 5.0 / 16.0);
+                diffuse(1, 1, 1.0 / 16.0);
+            }
+        }
+    }
+    to_8_bit(DynamicImage::ImageRgba32F(buffer), layout)
+}
+
+/// Converts the image to 8 bits per channel with the channel layout of `layout`.
+pub fn to_8_bit(img: DynamicImage, layout: ColorType) -> DynamicImage {
+    if img.color() == with_bits_per_channel(layout, 8) {
+        return img;
+    }
+    match (layout.has_color(), layout.has_alpha()) {
+        (false, false) => DynamicImage::ImageLuma8(img.to_luma8()),
+        (false, true) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        (true, false) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        (true, true) => DynamicImage::ImageRgba8(img.to_rgba8()),
+    }
+}
+
+/// Converts the image to 16 bits per channel with the channel layout of `layout`.
+pub fn to_16_bit(img: DynamicImage, layout: ColorType) -> DynamicImage {
+    if img.color() == with_bits_per_channel(layout, 16) {
+        return img;
+    }
+    match (layout.has_color(), layout.has_alpha()) {
+        (false, false) => DynamicImage::ImageLuma16(img.to_luma16()),
+        (false, true) => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+        (true, false) => DynamicImage::ImageRgb16(img.to_rgb16()),
+        (true, true) => DynamicImage::ImageRgba16(img.to_rgba16()),
+    }
+}
+
+/// Converts an image, usually with 32-bit floats, to the bit depth and channel layout of `layout`.
+pub fn to_layout(img: DynamicImage, layout: ColorType) -> DynamicImage {
+    match layout {
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(img.into_rgb32f()),
+        ColorType::Rgba32F => DynamicImage::ImageRgba32F(img.into_rgba32f()),
+        _ if bits_per_channel(layout) > 8 => to_16_bit(img, layout),
+        _ => to_8_bit(img, layout),
+    }
+}
+
+/// Layout with color channels and the bit depth and alpha channel of `layout`.
+pub fn with_color(layout: ColorType) -> ColorType {
+    match layout {
+        ColorType::L8 => ColorType::Rgb8,
+        ColorType::La8 => ColorType::Rgba8,
+        ColorType::L16 => ColorType::Rgb16,
+        ColorType::La16 => ColorType::Rgba16,
+        layout => layout,
+    }
+}
+
+/// Layout without color channels and with the bit depth and alpha channel of `layout`. Floating
+/// point images keep their color channels, since there is no grayscale layout for them.
+pub fn without_color(layout: ColorType) -> ColorType {
+    match layout {
+        ColorType::Rgb8 => ColorType::L8,
+        ColorType::Rgba8 => ColorType::La8,
+        ColorType::Rgb16 => ColorType::L16,
+        ColorType::Rgba16 => ColorType::La16,
+        layout => layout,
+    }
+}
+
+fn with_bits_per_channel(layout: ColorType, bits: u16) -> ColorType {
+    match (layout.has_color(), layout.has_alpha(), bits) {
+        (false, false, 8) => ColorType::L8,
+        (false, true, 8) => ColorType::La8,
+        (true, false, 8) => ColorType::Rgb8,
+        (true, true, 8) => ColorType::Rgba8,
+        (false, false, _) => ColorType::L16,
+        (false, true, _) => ColorType::La16,
+        (true, false, _) => ColorType::Rgb16,
+        (true, true, _) => ColorType::Rgba16,
+    }
+}
+
+fn to_32_bit_float(img: DynamicImage, target_format: ImageFormat) -> DynamicImage {
+    if img.color().has_alpha() && target_format == ImageFormat::OpenExr {
+        DynamicImage::ImageRgba32F(img.into_rgba32f())
+    } else {
+        DynamicImage::ImageRgb32F(img.into_rgb32f())
+    }
+}
+
+/// Converts the color channels of the image from one transfer function to another. Alpha is left
+/// untouched. The result is a floating-point image to avoid losing precision before the final
+/// conversion to the bit depth of the target.
+pub fn convert_colorspace(img: DynamicImage, from: ColorSpace, to: ColorSpace) -> DynamicImage {
+    let transfer: fn(f32) -> f32 = match (from, to) {
+        (ColorSpace::Srgb, ColorSpace::Linear) => srgb_to_linear,
+        (ColorSpace::Linear, ColorSpace::Srgb) => linear_to_srgb,
+        _ => return img,
+    };
+    let has_alpha = img.color().has_alpha();
+    let mut buffer = img.into_rgba32f();
+    for pixel in buffer.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = transfer(channel.max(0.0));
+        }
+    }
+    let img = DynamicImage::ImageRgba32F(buffer);
+    if has_alpha {
+        img
+    } else {
+        DynamicImage::ImageRgb32F(img.into_rgb32f())
+    }
+}
+
+/// Composites the image over an opaque background color. The result is a floating-point image
+/// without alpha. Also returns whether any pixel was transparent.
+pub fn flatten(img: DynamicImage, background: Rgba<u8>) -> (DynamicImage, bool) {
+    let background = background.0.map(|channel| channel as f32 / 255.0);
+    let mut buffer = img.into_rgba32f();
+    let mut transparent = false;
+    for pixel in buffer.pixels_mut() {
+        let alpha = pixel[3].clamp(0.0, 1.0);
+        transparent |= alpha < 1.0;
+        for channel in 0..3 {
+            pixel[channel] = pixel[channel] * alpha + background[channel] * (1.0 - alpha);
+        }
+    }
+    let img = DynamicImage::ImageRgb32F(DynamicImage::ImageRgba32F(buffer).into_rgb32f());
+    (img, transparent)
+}
+
+/// Channel layout of an image after it was composited over the background, which turns grayscale
+/// images into color images unless the background is gray.
+pub fn flattened_layout(layout: ColorType, background: Rgba<u8>) -> ColorType {
+    let [red, green, blue, _] = background.0;
+    let gray = !layout.has_color() && red == green && green == blue;
+    match (gray, bits_per_channel(layout)) {
+        (true, 8) => ColorType::L8,
+        (true, 16) => ColorType::L16,
+        (false, 8) => ColorType::Rgb8,
+        (false, 16) => ColorType::Rgb16,
+        _ => ColorType::Rgb32F,
+    }
+}
+
+/// Encodes a linear value with the sRGB transfer function.
+pub fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.003_130_8 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes a value that is encoded with the sRGB transfer function.
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.040_45 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Parses a color given as hex code ("#rgb", "#rrggbb" or "#rrggbbaa") or as one of the names
+/// "black", "white", "gray" and "transparent".
+pub fn parse_color(value: &str) -> Result<Rgba<u8>, String> {
+    match value.trim().to_lowercase().as_str() {
+        "black" => return Ok(Rgba([0, 0, 0, 255])),
+        "white" => return Ok(Rgba([255, 255, 255, 255])),
+        "gray" | "grey" => return Ok(Rgba([128, 128, 128, 255])),
+        "transparent" => return Ok(Rgba([0, 0, 0, 0])),
+        _ => {}
+    }
+    let hex = value.trim().trim_start_matches('#');
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("invalid color: {value}"))?;
+    let channels = match digits.len() {
+        3 | 4 => digits.iter().map(|digit| digit * 17).collect::<Vec<_>>(),
+        6 | 8 => digits
+            .chunks(2)
+            .map(|pair| pair[0] * 16 + pair[1])
+            .collect::<Vec<_>>(),
+        _ => return Err(format!("invalid color: {value}")),
+    };
+    Ok(Rgba([
+        channels[0],
+        channels[1],
+        channels[2],
+        channels.get(3).copied().unwrap_or(255),
+    ]))
+}
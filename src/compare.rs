@@ -0,0 +1,123 @@
+use image::{DynamicImage, GrayImage, Rgba, RgbaImage};
+use serde::Serialize;
+
+/// Pixel difference between two images of the same size. Values are in the range of `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Difference {
+    /// Number of pixels in which at least one channel differs
+    pub differing_pixels: u64,
+    /// Largest difference of a single channel
+    pub max_difference: f32,
+    /// Peak signal-to-noise ratio in dB over all channels, infinite for identical images
+    pub psnr: f64,
+    /// Structural similarity of the luma, 1 for identical images
+    pub ssim: f64,
+}
+
+impl Difference {
+    pub fn is_identical(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+/// Compares two images channel by channel, independent of their color type. Returns `None` if the
+/// dimensions differ.
+pub fn difference(a: &DynamicImage, b: &DynamicImage) -> Option<Difference> {
+    if (a.width(), a.height()) != (b.width(), b.height()) {
+        return None;
+    }
+    let (rgba_a, rgba_b) = (a.to_rgba32f(), b.to_rgba32f());
+    let mut difference = Difference {
+        differing_pixels: 0,
+        max_difference: 0.0,
+        psnr: f64::INFINITY,
+        ssim: 1.0,
+    };
+    let mut squared_error = 0.0;
+    for (pixel_a, pixel_b) in rgba_a.pixels().zip(rgba_b.pixels()) {
+        let mut max = 0.0f32;
+        for (value_a, value_b) in pixel_a.0.iter().zip(pixel_b.0) {
+            max = max.max((value_a - value_b).abs());
+            squared_error += ((value_a - value_b) as f64).powi(2);
+        }
+        if max > 0.0 {
+            difference.differing_pixels += 1;
+            difference.max_difference = difference.max_difference.max(max);
+        }
+    }
+    if difference.differing_pixels > 0 {
+        let mse = squared_error / (rgba_a.len() as f64);
+        difference.psnr = -10.0 * mse.log10();
+        difference.ssim = ssim(a, b);
+    }
+    Some(difference)
+}
+
+/// Edge length of the windows over which the structural similarity is computed.
+const SSIM_WINDOW: u32 = 8;
+
+/// Mean structural similarity of the luma of two images of the same size, computed over windows
+/// of 8x8 pixels that overlap by half.
+pub fn ssim(a: &DynamicImage, b: &DynamicImage) -> f64 {
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+    let (a, b) = (a.to_luma8(), b.to_luma8());
+    let window = SSIM_WINDOW.min(a.width()).min(a.height());
+    if window == 0 {
+        return 1.0;
+    }
+    let step = (window / 2).max(1);
+    let (mut sum, mut count) = (0.0, 0);
+    for y in (0..=a.height() - window).step_by(step as usize) {
+        for x in (0..=a.width() - window).step_by(step as usize) {
+            let (mean_a, mean_b) = (mean(&a, x, y, window), mean(&b, x, y, window));
+            let (mut variance_a, mut variance_b, mut covariance) = (0.0, 0.0, 0.0);
+            for (dx, dy) in (0..window).flat_map(|dy| (0..window).map(move |dx| (dx, dy))) {
+                let value_a = a.get_pixel(x + dx, y + dy)[0] as f64 / 255.0 - mean_a;
+                let value_b = b.get_pixel(x + dx, y + dy)[0] as f64 / 255.0 - mean_b;
+                variance_a += value_a * value_a;
+                variance_b += value_b * value_b;
+                covariance += value_a * value_b;
+            }
+            let n = (window * window) as f64;
+            let (variance_a, variance_b, covariance) =
+                (variance_a / n, variance_b / n, covariance / n);
+            sum += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+            count += 1;
+        }
+    }
+    sum / count as f64
+}
+
+fn mean(img: &GrayImage, x: u32, y: u32, window: u32) -> f64 {
+    let sum = (0..window)
+        .flat_map(|dy| (0..window).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| img.get_pixel(x + dx, y + dy)[0] as f64)
+        .sum::<f64>();
+    sum / 255.0 / (window * window) as f64
+}
+
+/// Visualizes the differences of two images of the same size: differing pixels are red, with an
+/// intensity depending on the difference, on top of a faded grayscale copy of the first image.
+pub fn diff_image(a: &DynamicImage, b: &DynamicImage) -> RgbaImage {
+    let (a32, b32) = (a.to_rgba32f(), b.to_rgba32f());
+    let background = a.to_luma8();
+    RgbaImage::from_fn(a.width(), a.height(), |x, y| {
+        let max = a32
+            .get_pixel(x, y)
+            .0
+            .iter()
+            .zip(b32.get_pixel(x, y).0)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        if max > 0.0 {
+            // Small differences are still clearly visible
+            let intensity = 0.5 + 0.5 * max.min(1.0);
+            Rgba([(255.0 * intensity) as u8, 0, 0, 255])
+        } else {
+            let gray = 191 + background.get_pixel(x, y)[0] / 4;
+            Rgba([gray, gray, gray, 255])
+        }
+    })
+}
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// Name of the project configuration, which is searched for from the working directory upwards.
+pub const PROJECT_FILE: &str = "imy.toml";
+
+/// Defaults for the command line options. Options given on the command line take precedence.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Quality of lossy formats (jpeg, avif) from 0 to 100
+    pub quality: Option<u8>,
+    /// Directory to write the outputs to. Relative to the configuration file
+    pub out_dir: Option<PathBuf>,
+    /// Number of files that are converted in parallel
+    pub jobs: Option<usize>,
+    /// Respect .gitignore, .ignore and similar files
+    pub ignore: Option<bool>,
+    /// Include hidden files and directories
+    pub hidden: Option<bool>,
+    /// File name of the converted images with placeholders, e.g. "{stem}_{width}x{height}.{ext}"
+    pub output_template: Option<String>,
+}
+
+impl Config {
+    /// Reads the user configuration in `~/.config/imy/config.toml` and the nearest `imy.toml` from
+    /// `dir` upwards. The settings of the project take precedence over those of the user.
+    pub fn discover(dir: &Path) -> miette::Result<Self> {
+        let user = std::env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".config/imy/config.toml"))
+            .filter(|path| path.is_file());
+        let project = dir
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_FILE))
+            .find(|path| path.is_file());
+        let mut config = Self::default();
+        for path in [project, user].into_iter().flatten() {
+            config = config.or(Self::read(&path)?);
+        }
+        Ok(config)
+    }
+
+    /// Reads a configuration file and resolves its paths relative to its directory.
+    pub fn read(path: &Path) -> miette::Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|source| Error::ReadFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        let mut config = toml::from_str::<Self>(&text).map_err(|source| Error::ConfigFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        if config.quality.is_some_and(|quality| quality > 100) {
+            return Err(Error::InvalidConfig {
+                path: path.to_owned(),
+                help: "The quality must be between 0 and 100".to_owned(),
+            }
+            .into());
+        }
+        if config.jobs == Some(0) {
+            return Err(Error::InvalidConfig {
+                path: path.to_owned(),
+                help: "The number of jobs must be at least 1".to_owned(),
+            }
+            .into());
+        }
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.out_dir = config.out_dir.map(|dir| base.join(dir));
+        Ok(config)
+    }
+
+    /// Fills the settings that are not set with those of `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            quality: self.quality.or(other.quality),
+            out_dir: self.out_dir.or(other.out_dir),
+            jobs: self.jobs.or(other.jobs),
+            ignore: self.ignore.or(other.ignore),
+            hidden: self.hidden.or(other.hidden),
+            output_template: self.output_template.or(other.output_template),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::hash::ImageHash;
+
+/// Groups the hashes whose distance is at most `threshold`, also through other hashes of the
+/// group. Returns the indices of the hashes of each group, including groups of a single hash.
+pub fn group(hashes: &[ImageHash], threshold: u32) -> Vec<Vec<usize>> {
+    let mut parents = (0..hashes.len()).collect::<Vec<_>>();
+    for i in 0..hashes.len() {
+        for j in i + 1..hashes.len() {
+            if hashes[i]
+                .distance(&hashes[j])
+                .is_some_and(|d| d <= threshold)
+            {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups = Vec::<Vec<usize>>::new();
+    let mut group_of_root = vec![None::<usize>; hashes.len()];
+    for i in 0..hashes.len() {
+        let root = root(&mut parents, i);
+        match group_of_root[root] {
+            Some(group) => groups[group].push(i),
+            None => {
+                group_of_root[root] = Some(groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+    groups
+}
+
+/// Finds the root of the union-find tree of the element and flattens the path to it.
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
@@ -0,0 +1,108 @@
+use std::{
+    env, fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::error::Error;
+
+/// Size of the largest image that is downloaded.
+const MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Time after which a download is aborted.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether the path is an http:// or https:// URL.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Temporary directory for the images at URLs, which is deleted when it's dropped.
+pub struct Downloads {
+    dir: PathBuf,
+    count: AtomicUsize,
+}
+
+impl Downloads {
+    pub fn new() -> Self {
+        Self {
+            dir: env::temp_dir().join(format!("imy-{}", process::id())),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Downloads the image at the URL into a file named after the last segment of the URL, which
+    /// keeps the extension for the commands that need one.
+    pub fn download(&self, url: &str) -> miette::Result<PathBuf> {
+        tracing::debug!("Downloading: {url}");
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let response = agent.get(url).call().map_err(|source| Error::Download {
+            url: url.to_owned(),
+            source: Box::new(source),
+        })?;
+        let too_large = || Error::DownloadTooLarge {
+            url: url.to_owned(),
+            max_size: MAX_SIZE,
+        };
+        let length = response
+            .header("Content-Length")
+            .and_then(|length| length.parse::<u64>().ok());
+        if length.is_some_and(|length| length > MAX_SIZE) {
+            return Err(too_large().into());
+        }
+        // The length is missing for chunked responses, so the size is also checked while reading
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .take(MAX_SIZE + 1)
+            .read_to_end(&mut data)
+            .map_err(|source| Error::DownloadRead {
+                url: url.to_owned(),
+                source,
+            })?;
+        if data.len() as u64 > MAX_SIZE {
+            return Err(too_large().into());
+        }
+
+        // Each download gets its own directory, so that URLs with the same file name don't clash
+        let index = self.count.fetch_add(1, Ordering::Relaxed);
+        let dir = self.dir.join(index.to_string());
+        fs::create_dir_all(&dir).map_err(|source| Error::CreateDir {
+            path: dir.clone(),
+            source,
+        })?;
+        let path = dir.join(file_name(url));
+        fs::write(&path, data).map_err(|source| Error::WriteFile {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(path)
+    }
+}
+
+impl Drop for Downloads {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Last segment of the path of the URL, e.g. "photo.jpg" for
+/// "https://example.com/photos/photo.jpg?size=large", or "download" if the path is empty.
+fn file_name(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name = url
+        .split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+        .unwrap_or_default()
+        .replace(['\\', ':', '*', '"', '<', '>', '|'], "_");
+    if name.is_empty() || name == "." || name == ".." {
+        "download".to_owned()
+    } else {
+        name
+    }
+}
@@ -0,0 +1,525 @@
+use std::{io, path::PathBuf};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum Error {
+    #[error("A path is required")]
+    #[diagnostic(
+        code(imy::path_required),
+        help("Pass the path of a file or directory, e.g. \".\" for the current directory")
+    )]
+    PathRequired,
+
+    #[error("Failed to find the path: {}", path.display())]
+    #[diagnostic(
+        code(imy::path_not_found),
+        help("Check that the path exists and is spelled correctly")
+    )]
+    PathNotFound { path: PathBuf },
+
+    #[error("Failed to access the path: {}", path.display())]
+    #[diagnostic(
+        code(imy::path_access),
+        help("The path is neither a file nor a directory, or its permissions prevent access")
+    )]
+    PathAccess { path: PathBuf },
+
+    #[error("Failed to open file: {}", path.display())]
+    #[diagnostic(code(imy::open))]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to determine the format of the file: {}", path.display())]
+    #[diagnostic(
+        code(imy::undetermined_format),
+        help(
+            "Neither the contents nor the extension of the file match a supported format. Pass \
+             --from-format to convert to choose the decoder"
+        )
+    )]
+    UndeterminedFormat { path: PathBuf },
+
+    #[error("Failed to decode file: {}", path.display())]
+    #[diagnostic(code(imy::decode))]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    #[error("Failed to save file with format {format}: {}", path.display())]
+    #[diagnostic(code(imy::save))]
+    Save {
+        path: PathBuf,
+        format: String,
+        #[source]
+        source: image::ImageError,
+    },
+
+    #[error("Failed to render the preview: {}", path.display())]
+    #[diagnostic(code(imy::preview))]
+    Preview {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    #[error("Failed to delete file: {}", path.display())]
+    #[diagnostic(code(imy::delete))]
+    Delete {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to move file to {}: {}", destination.display(), path.display())]
+    #[diagnostic(code(imy::move_file))]
+    MoveFile {
+        path: PathBuf,
+        destination: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to move file to the trash: {}", path.display())]
+    #[diagnostic(code(imy::trash))]
+    Trash {
+        path: PathBuf,
+        #[source]
+        source: trash::Error,
+    },
+
+    #[error("Unknown format: {format}")]
+    #[diagnostic(code(imy::unknown_format))]
+    UnknownFormat {
+        format: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Unsupported format: {format}")]
+    #[diagnostic(code(imy::unsupported_format))]
+    UnsupportedFormat {
+        format: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Format does not support transparency: {format}")]
+    #[diagnostic(
+        code(imy::no_alpha_support),
+        help("Use a format with transparency, e.g. png or webp")
+    )]
+    NoAlphaSupport { format: String },
+
+    #[error("Format mismatch: {}", path.display())]
+    #[diagnostic(code(imy::format_mismatch))]
+    FormatMismatch { path: PathBuf },
+
+    #[error("Unsupported bit depth: {bit_depth}")]
+    #[diagnostic(code(imy::unsupported_bit_depth), help("Valid bit depths are: 8, 16"))]
+    UnsupportedBitDepth { bit_depth: u8 },
+
+    #[error("Expected {expected} exposure times but found {found}")]
+    #[diagnostic(
+        code(imy::exposure_time_count),
+        help("Pass one exposure time per input image or none to estimate them")
+    )]
+    ExposureTimeCount { expected: usize, found: usize },
+
+    #[error("Dimensions do not match the first image: {}", path.display())]
+    #[diagnostic(code(imy::dimension_mismatch))]
+    DimensionMismatch { path: PathBuf },
+
+    #[error("Failed to read OpenEXR file: {}", path.display())]
+    #[diagnostic(code(imy::openexr))]
+    OpenExr {
+        path: PathBuf,
+        #[source]
+        source: exr::error::Error,
+    },
+
+    #[error("Failed to read or write GeoTIFF tags: {}", path.display())]
+    #[diagnostic(code(imy::geotiff))]
+    GeoTiff {
+        path: PathBuf,
+        #[source]
+        source: tiff::TiffError,
+    },
+
+    #[error("Found {count} difference(s)")]
+    #[diagnostic(code(imy::differences))]
+    Differences { count: usize },
+
+    #[error("Failed to import metadata into the file: {}", path.display())]
+    #[diagnostic(
+        code(imy::metadata_import),
+        help("Metadata can only be imported into well-formed png and jpeg files")
+    )]
+    MetadataImport { path: PathBuf },
+
+    #[error("Failed to read or write the sidecar: {}", path.display())]
+    #[diagnostic(code(imy::sidecar))]
+    Sidecar {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Failed to parse the icon spec: {}", path.display())]
+    #[diagnostic(
+        code(imy::icon_spec),
+        help(r#"The spec is a JSON array of icons, e.g. [{{"path": "icon-64.png", "size": 64}}]"#)
+    )]
+    IconSpec {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Invalid pipeline stage: {stage}")]
+    #[diagnostic(code(imy::pipeline_stage))]
+    InvalidStage {
+        stage: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Failed to parse the job file: {}", path.display())]
+    #[diagnostic(code(imy::job_file))]
+    JobFile {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to parse the configuration: {}", path.display())]
+    #[diagnostic(code(imy::config_file))]
+    ConfigFile {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid configuration: {}", path.display())]
+    #[diagnostic(code(imy::invalid_config))]
+    InvalidConfig {
+        path: PathBuf,
+        #[help]
+        help: String,
+    },
+
+    #[error("Invalid job: {name}")]
+    #[diagnostic(code(imy::invalid_job))]
+    InvalidJob {
+        name: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("The interrupted conversion used other options: {}", path.display())]
+    #[diagnostic(
+        code(imy::journal_options),
+        help("Pass the options of the interrupted conversion, or convert without --resume to start over")
+    )]
+    JournalOptions { path: PathBuf },
+
+    #[error("Unknown OpenEXR layer: {layer}")]
+    #[diagnostic(code(imy::unknown_exr_layer))]
+    UnknownExrLayer {
+        layer: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Unknown OpenEXR channel: {channel}")]
+    #[diagnostic(code(imy::unknown_exr_channel))]
+    UnknownExrChannel {
+        channel: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Failed to map the OpenEXR channels to a color image")]
+    #[diagnostic(code(imy::ambiguous_exr_channels))]
+    AmbiguousExrChannels {
+        #[help]
+        help: String,
+    },
+
+    #[error("Data of {needed} bytes does not fit into the image, which holds {available} bytes")]
+    #[diagnostic(
+        code(imy::stego_capacity),
+        help("Use a larger image or compress the data")
+    )]
+    StegoCapacity { needed: usize, available: usize },
+
+    #[error("Failed to find hidden data in the image")]
+    #[diagnostic(
+        code(imy::stego_no_data),
+        help("Check the passphrase and that the image was not re-encoded with a lossy format")
+    )]
+    StegoNoData,
+
+    #[error("Format has no quality setting: {format}")]
+    #[diagnostic(
+        code(imy::no_quality_setting),
+        help("Use jpeg or avif as target format to limit the size of the output")
+    )]
+    NoQualitySetting { format: String },
+
+    #[error("Icon sizes are only supported by ico as target format: {format}")]
+    #[diagnostic(
+        code(imy::ico_sizes),
+        help("Use ico as target format or leave out --ico-sizes")
+    )]
+    IcoSizes { format: String },
+
+    #[error("Output does not fit into {max_size} at the lowest quality: {}", path.display())]
+    #[diagnostic(
+        code(imy::size_budget),
+        help("Reduce the dimensions of the image, e.g. with --resize or --scale")
+    )]
+    SizeBudget { path: PathBuf, max_size: String },
+
+    #[error("Format does not preserve the pixels exactly: {format}")]
+    #[diagnostic(
+        code(imy::lossy_format),
+        help("Use a lossless format, e.g. png, tiff, qoi or webp")
+    )]
+    LossyFormat { format: String },
+
+    #[error("Pixels of the output differ from the source: {}", path.display())]
+    #[diagnostic(
+        code(imy::pixel_mismatch),
+        help("The conversion changed the bit depth, the channels or the color space of the image")
+    )]
+    PixelMismatch { path: PathBuf },
+
+    #[error("Failed to verify the output {}: {reason}", path.display())]
+    #[diagnostic(code(imy::verification))]
+    Verification { path: PathBuf, reason: String },
+
+    #[error("Failed to convert {count} file(s)")]
+    #[diagnostic(code(imy::failed_files))]
+    FailedFiles {
+        count: usize,
+        #[help]
+        help: String,
+    },
+
+    #[error("Failed to read file: {}", path.display())]
+    #[diagnostic(code(imy::read_file))]
+    ReadFile {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to write file: {}", path.display())]
+    #[diagnostic(code(imy::write_file))]
+    WriteFile {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to create directory: {}", path.display())]
+    #[diagnostic(code(imy::create_dir))]
+    CreateDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[cfg(feature = "ffmpeg")]
+    #[error("Failed to find ffmpeg")]
+    #[diagnostic(
+        code(imy::ffmpeg_not_found),
+        help("Install ffmpeg and make sure that it is on the PATH")
+    )]
+    FfmpegNotFound,
+
+    #[cfg(feature = "ffmpeg")]
+    #[error("Failed to run ffmpeg")]
+    #[diagnostic(code(imy::ffmpeg_spawn))]
+    FfmpegSpawn(#[source] io::Error),
+
+    #[cfg(feature = "ffmpeg")]
+    #[error("ffmpeg failed with {status}")]
+    #[diagnostic(code(imy::ffmpeg))]
+    Ffmpeg {
+        status: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Unknown placeholder: {{{placeholder}}}")]
+    #[diagnostic(code(imy::unknown_placeholder))]
+    UnknownPlaceholder {
+        placeholder: String,
+        #[help]
+        help: String,
+    },
+
+    #[error("Failed to create the thread pool")]
+    #[diagnostic(code(imy::thread_pool))]
+    ThreadPool(#[source] rayon::ThreadPoolBuildError),
+
+    #[error("Unknown log level: {level}")]
+    #[diagnostic(
+        code(imy::unknown_log_level),
+        help("Valid log levels are: trace, debug, info, warn, error")
+    )]
+    UnknownLogLevel { level: String },
+
+    #[error("Failed setting the tracing subscriber")]
+    #[diagnostic(code(imy::tracing))]
+    TracingSubscriber,
+
+    #[error("Failed to write to stdout")]
+    #[diagnostic(code(imy::stdout))]
+    Stdout(#[source] io::Error),
+
+    #[error("Failed to serialize the output as JSON")]
+    #[diagnostic(code(imy::json))]
+    Json(#[source] serde_json::Error),
+
+    #[error("Failed to read from stdin")]
+    #[diagnostic(code(imy::stdin))]
+    Stdin(#[source] io::Error),
+
+    #[error("Output already exists: {} ({count} existing file(s))", path.display())]
+    #[diagnostic(
+        code(imy::output_exists),
+        help("Pass --force to overwrite existing outputs or --skip-existing to skip them")
+    )]
+    OutputExists { path: PathBuf, count: usize },
+
+    #[error("Several files would be converted to: {}", path.display())]
+    #[diagnostic(
+        code(imy::output_collision),
+        help(
+            "Rename {} or {}, or pass --on-collision suffix to number the outputs",
+            first.display(),
+            second.display()
+        )
+    )]
+    OutputCollision {
+        path: PathBuf,
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    #[error("Failed to determine the format of stdin")]
+    #[diagnostic(
+        code(imy::stdin_format),
+        help("Pass the format of the image with --from-format, e.g. --from-format png")
+    )]
+    StdinFormat,
+
+    #[cfg(feature = "http")]
+    #[error("Failed to download: {url}")]
+    #[diagnostic(code(imy::download))]
+    Download {
+        url: String,
+        #[source]
+        source: Box<ureq::Error>,
+    },
+
+    #[cfg(feature = "http")]
+    #[error("Failed to read the download: {url}")]
+    #[diagnostic(code(imy::download_read))]
+    DownloadRead {
+        url: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[cfg(feature = "http")]
+    #[error("The download is larger than {} MiB: {url}", max_size / 1024 / 1024)]
+    #[diagnostic(
+        code(imy::download_too_large),
+        help("Download the image with another tool and pass its path")
+    )]
+    DownloadTooLarge { url: String, max_size: u64 },
+
+    #[cfg(feature = "watch")]
+    #[error("Failed to watch the directory: {}", path.display())]
+    #[diagnostic(code(imy::watch))]
+    Watch {
+        path: PathBuf,
+        #[source]
+        source: notify::Error,
+    },
+
+    #[cfg(feature = "watch")]
+    #[error("Watching requires a single directory")]
+    #[diagnostic(
+        code(imy::watch_paths),
+        help("Pass one directory, e.g. imy inbox watch convert -t webp")
+    )]
+    WatchPaths,
+
+    #[cfg(feature = "ffi")]
+    #[error("Invalid argument `{name}`: {reason}")]
+    #[diagnostic(code(imy::ffi_argument))]
+    FfiArgument { name: &'static str, reason: String },
+
+    #[error("Aborted")]
+    #[diagnostic(code(imy::aborted), help("Pass --yes to skip the confirmation"))]
+    Aborted,
+}
+
+/// Exit code of the command line tool, which is stable for scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// The command succeeded
+    Success = 0,
+    /// Any error without a more specific code
+    Failure = 1,
+    /// An image didn't match the format of `is` or `compare` found differences
+    Mismatch = 2,
+    /// Some files of a batch failed while the others were converted
+    PartialFailure = 3,
+    /// A path passed to the command doesn't exist
+    PathNotFound = 4,
+    /// The user declined a confirmation or an output already exists
+    Aborted = 5,
+    /// The command line arguments are invalid
+    Usage = 6,
+}
+
+impl ExitCode {
+    /// Exit code for the error of a command.
+    pub fn of(report: &miette::Report) -> Self {
+        match report.downcast_ref::<Error>() {
+            Some(Error::FormatMismatch { .. } | Error::Differences { .. }) => Self::Mismatch,
+            Some(Error::FailedFiles { .. }) => Self::PartialFailure,
+            Some(Error::PathNotFound { .. }) => Self::PathNotFound,
+            Some(Error::Aborted | Error::OutputExists { .. }) => Self::Aborted,
+            _ => Self::Failure,
+        }
+    }
+
+    /// Exit code for the error of parsing the command line arguments. Help and version are
+    /// reported as errors by clap, but aren't failures.
+    pub fn of_usage(error: &clap::Error) -> Self {
+        if error.use_stderr() {
+            Self::Usage
+        } else {
+            Self::Success
+        }
+    }
+}
+
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(code: ExitCode) -> Self {
+        Self::from(code as u8)
+    }
+}
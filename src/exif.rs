@@ -0,0 +1,326 @@
+use serde::Serialize;
+
+/// Commonly used EXIF fields of a photo.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Exif {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lens: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub software: Option<String>,
+    /// Orientation from 1 to 8, where 1 is upright
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<u16>,
+    /// Time when the photo was taken, e.g. "2024:06:01 14:30:00"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_time_original: Option<String>,
+    /// Time when the file was last changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_time: Option<String>,
+    /// Exposure time in seconds, e.g. "1/125"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exposure_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub f_number: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iso: Option<u32>,
+    /// Focal length in millimeters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focal_length: Option<f64>,
+    /// Latitude in degrees, negative in the southern hemisphere
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, negative west of Greenwich
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// Altitude in meters, negative below sea level
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+}
+
+impl Exif {
+    /// Names and formatted values of the fields that are present, in the order of the struct.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let strings = [
+            ("make", &self.make),
+            ("model", &self.model),
+            ("lens", &self.lens),
+            ("software", &self.software),
+        ];
+        let mut fields = strings
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value.clone()?)))
+            .collect::<Vec<_>>();
+        let mut push = |name, value: Option<String>| fields.extend(value.map(|v| (name, v)));
+        push("orientation", self.orientation.map(|o| o.to_string()));
+        push("date_time_original", self.date_time_original.clone());
+        push("date_time", self.date_time.clone());
+        push("exposure_time", self.exposure_time.clone());
+        push("f_number", self.f_number.map(|f| f.to_string()));
+        push("iso", self.iso.map(|iso| iso.to_string()));
+        push("focal_length", self.focal_length.map(|f| f.to_string()));
+        push("latitude", self.latitude.map(|l| format!("{l:.6}")));
+        push("longitude", self.longitude.map(|l| format!("{l:.6}")));
+        push("altitude", self.altitude.map(|a| format!("{a:.1}")));
+        fields
+    }
+}
+
+const MAKE: u16 = 0x010f;
+const MODEL: u16 = 0x0110;
+const ORIENTATION: u16 = 0x0112;
+const SOFTWARE: u16 = 0x0131;
+const DATE_TIME: u16 = 0x0132;
+const EXIF_IFD: u16 = 0x8769;
+const GPS_IFD: u16 = 0x8825;
+const EXPOSURE_TIME: u16 = 0x829a;
+const F_NUMBER: u16 = 0x829d;
+const ISO: u16 = 0x8827;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const FOCAL_LENGTH: u16 = 0x920a;
+const LENS_MODEL: u16 = 0xa434;
+const GPS_LATITUDE_REF: u16 = 0x0001;
+const GPS_LATITUDE: u16 = 0x0002;
+const GPS_LONGITUDE_REF: u16 = 0x0003;
+const GPS_LONGITUDE: u16 = 0x0004;
+const GPS_ALTITUDE_REF: u16 = 0x0005;
+const GPS_ALTITUDE: u16 = 0x0006;
+
+/// Parses raw EXIF data starting with the TIFF header, which is also the start of a TIFF file.
+/// Returns `None` if the data is not a valid TIFF structure.
+pub fn parse(data: &[u8]) -> Option<Exif> {
+    let tiff = Tiff::new(data)?;
+    let ifd0 = tiff.ifd(tiff.u32(4)? as usize)?;
+    let exif_ifd = ifd_at(&tiff, &ifd0, EXIF_IFD).unwrap_or_default();
+    let gps_ifd = ifd_at(&tiff, &ifd0, GPS_IFD).unwrap_or_default();
+    let find = |ifd: &[Entry], tag| ifd.iter().find(|entry| entry.tag == tag).copied();
+    let ascii = |ifd: &[Entry], tag| tiff.ascii(find(ifd, tag)?);
+    let number = |ifd: &[Entry], tag| tiff.number(find(ifd, tag)?, 0);
+    let rational = |ifd: &[Entry], tag, index| tiff.rational(find(ifd, tag)?, index);
+    let real = |ifd: &[Entry], tag| {
+        let (numerator, denominator) = rational(ifd, tag, 0)?;
+        (denominator != 0.0).then(|| numerator / denominator)
+    };
+    let coordinate = |tag, reference_tag, negative| {
+        let degrees = (0..3)
+            .map(|index| {
+                let (numerator, denominator) = rational(&gps_ifd, tag, index)?;
+                (denominator != 0.0).then(|| numerator / denominator)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let value = degrees[0] + degrees[1] / 60.0 + degrees[2] / 3600.0;
+        let reference = ascii(&gps_ifd, reference_tag).unwrap_or_default();
+        Some(if reference == negative { -value } else { value })
+    };
+
+    Some(Exif {
+        make: ascii(&ifd0, MAKE),
+        model: ascii(&ifd0, MODEL),
+        lens: ascii(&exif_ifd, LENS_MODEL),
+        software: ascii(&ifd0, SOFTWARE),
+        orientation: number(&ifd0, ORIENTATION).map(|o| o as u16),
+        date_time_original: ascii(&exif_ifd, DATE_TIME_ORIGINAL),
+        date_time: ascii(&ifd0, DATE_TIME),
+        exposure_time: rational(&exif_ifd, EXPOSURE_TIME, 0)
+            .filter(|(_, denominator)| *denominator != 0.0)
+            .map(|(numerator, denominator)| {
+                if numerator >= denominator || numerator == 0.0 {
+                    format!("{}", numerator / denominator)
+                } else {
+                    format!("1/{}", (denominator / numerator).round())
+                }
+            }),
+        f_number: real(&exif_ifd, F_NUMBER),
+        iso: number(&exif_ifd, ISO),
+        focal_length: real(&exif_ifd, FOCAL_LENGTH),
+        latitude: coordinate(GPS_LATITUDE, GPS_LATITUDE_REF, "S"),
+        longitude: coordinate(GPS_LONGITUDE, GPS_LONGITUDE_REF, "W"),
+        altitude: real(&gps_ifd, GPS_ALTITUDE).map(|altitude| {
+            // A reference of 1 means below sea level
+            match find(&gps_ifd, GPS_ALTITUDE_REF).and_then(|e| tiff.number(e, 0)) {
+                Some(1) => -altitude,
+                _ => altitude,
+            }
+        }),
+    })
+}
+
+/// Removes the GPS location from raw EXIF data by emptying the GPS IFD and zeroing its values,
+/// which keeps the offsets of all other data intact. Returns whether there was a location.
+pub fn strip_gps(data: &mut [u8]) -> bool {
+    let Some(tiff) = Tiff::new(data) else {
+        return false;
+    };
+    let Some(ifd0) = tiff.u32(4).and_then(|offset| tiff.ifd(offset as usize)) else {
+        return false;
+    };
+    let Some(offset) = ifd0
+        .iter()
+        .find(|entry| entry.tag == GPS_IFD)
+        .and_then(|entry| tiff.number(*entry, 0))
+    else {
+        return false;
+    };
+    let offset = offset as usize;
+    let Some(gps_ifd) = tiff.ifd(offset) else {
+        return false;
+    };
+    let mut ranges = gps_ifd
+        .iter()
+        .map(|entry| entry.offset..entry.offset + entry.size)
+        .collect::<Vec<_>>();
+    // An IFD without entries is followed by a zero offset of the next IFD
+    ranges.push(offset..offset + 6 + gps_ifd.len() * 12);
+    for range in ranges {
+        if let Some(bytes) = data.get_mut(range) {
+            bytes.fill(0);
+        }
+    }
+    !gps_ifd.is_empty()
+}
+
+/// Replaces the orientation in raw EXIF data, e.g. with 1 after the pixels were rotated upright.
+/// Data without an orientation is left unchanged.
+pub fn set_orientation(data: &mut [u8], orientation: u16) {
+    let Some(tiff) = Tiff::new(data) else {
+        return;
+    };
+    let Some(entry) = tiff
+        .u32(4)
+        .and_then(|offset| tiff.ifd(offset as usize))
+        .and_then(|ifd| ifd.into_iter().find(|entry| entry.tag == ORIENTATION))
+        .filter(|entry| entry.kind == 3)
+    else {
+        return;
+    };
+    let bytes = if tiff.big_endian {
+        orientation.to_be_bytes()
+    } else {
+        orientation.to_le_bytes()
+    };
+    data[entry.offset..entry.offset + 2].copy_from_slice(&bytes);
+}
+
+/// Reads the IFD that the entry with the tag points to.
+fn ifd_at(tiff: &Tiff, ifd: &[Entry], tag: u16) -> Option<Vec<Entry>> {
+    let entry = ifd.iter().find(|entry| entry.tag == tag)?;
+    tiff.ifd(tiff.number(*entry, 0)? as usize)
+}
+
+/// Entry of an image file directory.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: usize,
+    /// Offset of the value, which is stored in the entry itself if it fits into four bytes
+    offset: usize,
+    /// Size of the value in bytes
+    size: usize,
+}
+
+/// TIFF structure in either byte order.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Tiff<'a>> {
+        let big_endian = match data.get(..2)? {
+            b"II" => false,
+            b"MM" => true,
+            _ => return None,
+        };
+        let tiff = Tiff { data, big_endian };
+        (tiff.u16(2)? == 42).then_some(tiff)
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn ifd(&self, offset: usize) -> Option<Vec<Entry>> {
+        let count = self.u16(offset)? as usize;
+        (0..count)
+            .map(|index| {
+                let start = offset + 2 + index * 12;
+                let kind = self.u16(start + 2)?;
+                let count = self.u32(start + 4)? as usize;
+                let size = match kind {
+                    1 | 2 | 6 | 7 => 1,
+                    3 | 8 => 2,
+                    4 | 9 | 11 => 4,
+                    _ => 8,
+                } * count;
+                let offset = if size <= 4 {
+                    start + 8
+                } else {
+                    self.u32(start + 8)? as usize
+                };
+                self.data.get(offset..offset.checked_add(size)?)?;
+                Some(Entry {
+                    tag: self.u16(start)?,
+                    kind,
+                    count,
+                    offset,
+                    size,
+                })
+            })
+            .collect()
+    }
+
+    fn ascii(&self, entry: Entry) -> Option<String> {
+        if entry.kind != 2 {
+            return None;
+        }
+        let bytes = &self.data[entry.offset..entry.offset + entry.count];
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_owned())
+    }
+
+    /// Value of an entry of an unsigned integer type.
+    fn number(&self, entry: Entry, index: usize) -> Option<u32> {
+        if index >= entry.count {
+            return None;
+        }
+        match entry.kind {
+            1 | 7 => Some(self.data[entry.offset + index] as u32),
+            3 => self.u16(entry.offset + index * 2).map(u32::from),
+            4 => self.u32(entry.offset + index * 4),
+            _ => None,
+        }
+    }
+
+    /// Numerator and denominator of an entry of a rational type.
+    fn rational(&self, entry: Entry, index: usize) -> Option<(f64, f64)> {
+        if index >= entry.count {
+            return None;
+        }
+        let offset = entry.offset + index * 8;
+        let (numerator, denominator) = (self.u32(offset)?, self.u32(offset + 4)?);
+        match entry.kind {
+            5 => Some((numerator as f64, denominator as f64)),
+            10 => Some((numerator as i32 as f64, denominator as i32 as f64)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! C interface for applications that link imy as a shared library instead of spawning the binary.
+//! The declarations are in `include/imy.h`.
+//!
+//! Functions that fail return a nonzero [`ExitCode`] or a null pointer, and the message of the
+//! error can be retrieved with [`imy_last_error()`] on the same thread.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    io,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+use crate::{error::Error, error_message, Context, ConvertOptions, ExitCode, OutputFormat};
+
+/// Options of [`imy_convert()`]. A null pointer takes the defaults.
+#[repr(C)]
+pub struct ImyConvertOptions {
+    /// Quality of lossy formats (jpeg, avif) from 0 to 100, or -1 for the default
+    pub quality: c_int,
+    /// Directory to write the outputs to, or null to write them next to the inputs
+    pub out_dir: *const c_char,
+    /// Whether existing outputs are replaced instead of failing
+    pub overwrite: bool,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Converts an image, or all images in a directory, to the format, e.g. "webp". Returns 0 on
+/// success and the exit code of the command line tool otherwise.
+///
+/// # Safety
+///
+/// `path` and `format` must be valid NUL-terminated strings. `options` must be null or point to
+/// valid options.
+#[no_mangle]
+pub unsafe extern "C" fn imy_convert(
+    path: *const c_char,
+    format: *const c_char,
+    options: *const ImyConvertOptions,
+) -> c_int {
+    let result = catch(|| {
+        let path = path_arg("path", path)?;
+        let mut convert_options = ConvertOptions {
+            target_format: str_arg("format", format)?.to_owned(),
+            ..Default::default()
+        };
+        if let Some(options) = options.as_ref() {
+            convert_options.quality = u8::try_from(options.quality).ok();
+            if !options.out_dir.is_null() {
+                convert_options.out_dir = Some(path_arg("out_dir", options.out_dir)?);
+            }
+            convert_options.force = options.overwrite;
+        }
+        let mut sink = io::sink();
+        crate::convert(&mut Context::new(&mut sink), &path, &convert_options)
+    });
+    match result {
+        Ok(()) => ExitCode::Success as c_int,
+        Err(code) => code as c_int,
+    }
+}
+
+/// Returns the information of `imy info --output json` about an image or a directory, or null on
+/// failure. The string has to be freed with [`imy_string_free()`].
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn imy_info_json(path: *const c_char) -> *mut c_char {
+    let result = catch(|| {
+        let path = path_arg("path", path)?;
+        let mut json = Vec::new();
+        crate::info(
+            &mut Context::new(&mut json),
+            &path,
+            None,
+            OutputFormat::Json,
+            false,
+        )?;
+        // The JSON escapes control characters, so it doesn't contain NUL characters
+        Ok(CString::new(json.trim_ascii_end()).expect("JSON without NUL"))
+    });
+    result.map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Frees a string that was returned by imy.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by imy that wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn imy_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Returns the message of the last error on this thread, or null if there was none. The string is
+/// valid until the next call of imy on this thread.
+#[no_mangle]
+pub extern "C" fn imy_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|error| error.as_ref().map_or(ptr::null(), |error| error.as_ptr()))
+}
+
+/// Runs the function, recording its error as the last error. Panics mustn't unwind into C.
+fn catch<T>(function: impl FnOnce() -> miette::Result<T>) -> Result<T, ExitCode> {
+    let result = panic::catch_unwind(AssertUnwindSafe(function));
+    let (message, code) = match result {
+        Ok(Ok(value)) => {
+            LAST_ERROR.set(None);
+            return Ok(value);
+        }
+        Ok(Err(report)) => (error_message(&report), ExitCode::of(&report)),
+        Err(_) => ("imy panicked".to_owned(), ExitCode::Failure),
+    };
+    let message = CString::new(message.replace('\0', " ")).expect("message without NUL");
+    LAST_ERROR.set(Some(message));
+    Err(code)
+}
+
+/// Borrows a string argument, which has to be UTF-8.
+unsafe fn str_arg<'a>(name: &'static str, string: *const c_char) -> Result<&'a str, Error> {
+    if string.is_null() {
+        return Err(null_arg(name));
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map_err(|error| Error::FfiArgument {
+            name,
+            reason: error.to_string(),
+        })
+}
+
+/// Takes a path argument, which is taken as raw bytes on unix like the paths of the command line.
+unsafe fn path_arg(name: &'static str, string: *const c_char) -> Result<PathBuf, Error> {
+    if string.is_null() {
+        return Err(null_arg(name));
+    }
+    #[cfg(unix)]
+    {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        Ok(PathBuf::from(OsStr::from_bytes(
+            CStr::from_ptr(string).to_bytes(),
+        )))
+    }
+    #[cfg(not(unix))]
+    str_arg(name, string).map(PathBuf::from)
+}
+
+fn null_arg(name: &'static str) -> Error {
+    Error::FfiArgument {
+        name,
+        reason: "null pointer".to_owned(),
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::error::Error;
+
+/// Parses a duration given in seconds with an optional unit, e.g. "1s", "500ms", "2m" or "0.5".
+pub fn parse_duration(value: &str) -> Result<f64, String> {
+    let value = value.trim();
+    let (number, factor) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = value.strip_suffix('m') {
+        (number, 60.0)
+    } else if let Some(number) = value.strip_suffix('h') {
+        (number, 3600.0)
+    } else {
+        (value, 1.0)
+    };
+    let seconds = number
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid duration: {value}"))?
+        * factor;
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return Err(format!("duration must be positive: {value}"));
+    }
+    Ok(seconds)
+}
+
+/// Extracts a still every `every` seconds from the video into `output_dir` as PNG files named
+/// after the video, e.g. "clip_00001.png". Returns the paths of the stills in order.
+pub fn extract_frames(path: &Path, every: f64, output_dir: &Path) -> miette::Result<Vec<PathBuf>> {
+    fs::create_dir_all(output_dir).map_err(|source| Error::CreateDir {
+        path: output_dir.to_owned(),
+        source,
+    })?;
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or("frame".to_owned());
+    let pattern = output_dir.join(format!("{stem}_%05d.png"));
+
+    run(Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .args(["-loglevel", "error"])
+        .arg("-y")
+        .arg("-i")
+        .arg(path)
+        .args(["-vf", &format!("fps=1/{every}")])
+        .arg(&pattern))?;
+
+    let prefix = format!("{stem}_");
+    let mut frames = fs::read_dir(output_dir)
+        .map_err(|source| Error::ReadFile {
+            path: output_dir.to_owned(),
+            source,
+        })?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|frame| {
+            frame
+                .extension()
+                .is_some_and(|extension| extension == "png")
+                && frame
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .collect::<Vec<_>>();
+    frames.sort();
+    Ok(frames)
+}
+
+/// Encodes the stills in `frames_dir`, named "00000.png", "00001.png" and so on, into a video
+/// with the given frame rate. The codec is chosen by ffmpeg from the extension of `output`.
+pub fn encode_video(frames_dir: &Path, fps: f64, output: &Path) -> miette::Result<()> {
+    run(Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .args(["-loglevel", "error"])
+        .arg("-y")
+        .args(["-framerate", &fps.to_string()])
+        .arg("-i")
+        .arg(frames_dir.join("%05d.png"))
+        // Most players only support 4:2:0 chroma subsampling
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(output))
+}
+
+/// First line of `ffmpeg -version`, e.g. "ffmpeg version 6.1.1", or `None` if ffmpeg isn't on the
+/// PATH.
+pub fn version() -> Option<String> {
+    let output = Command::new("ffmpeg").arg("-version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().next().map(|line| line.trim().to_owned())
+}
+
+/// Runs ffmpeg and turns a failure into a diagnostic that contains its error output.
+fn run(command: &mut Command) -> miette::Result<()> {
+    tracing::debug!("Running: {:?}", command);
+    let output = command.output().map_err(|source| match source.kind() {
+        io::ErrorKind::NotFound => Error::FfmpegNotFound,
+        _ => Error::FfmpegSpawn(source),
+    })?;
+    if !output.status.success() {
+        return Err(Error::Ffmpeg {
+            status: output.status.to_string(),
+            help: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
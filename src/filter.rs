@@ -0,0 +1,159 @@
+use image::{imageops, DynamicImage};
+
+use crate::color;
+
+/// Color filter that maps each pixel on its own.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Luma of the colors with the weights of Rec. 709
+    Grayscale,
+    /// Negative of the colors, keeping the alpha channel
+    Invert,
+    /// Brownish tone of old photographs
+    Sepia,
+}
+
+/// Applies the filter, keeping the bit depth and the alpha channel of the image. Grayscale turns
+/// color images into grayscale images, and sepia turns grayscale images into color images.
+pub fn apply(img: DynamicImage, filter: Filter) -> DynamicImage {
+    let layout = match filter {
+        Filter::Grayscale => color::without_color(img.color()),
+        Filter::Invert => img.color(),
+        Filter::Sepia => color::with_color(img.color()),
+    };
+    let mut buffer = img.into_rgba32f();
+    for pixel in buffer.pixels_mut() {
+        let [r, g, b, _] = pixel.0;
+        let rgb = match filter {
+            Filter::Grayscale => {
+                let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                [luma; 3]
+            }
+            Filter::Invert => [r, g, b].map(|value| 1.0 - value.clamp(0.0, 1.0)),
+            Filter::Sepia => [
+                0.393 * r + 0.769 * g + 0.189 * b,
+                0.349 * r + 0.686 * g + 0.168 * b,
+                0.272 * r + 0.534 * g + 0.131 * b,
+            ],
+        };
+        pixel.0[..3].copy_from_slice(&rgb);
+    }
+    color::to_layout(DynamicImage::ImageRgba32F(buffer), layout)
+}
+
+/// Tonal adjustment of the colors, applied in the order brightness, contrast and gamma.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustment {
+    /// Offset added to the colors as a fraction of the range, from -1 to 1
+    pub brightness: f32,
+    /// Change of the distance of the colors to the middle gray as a fraction, from -1 to 1
+    pub contrast: f32,
+    /// Exponent of `1 / gamma` applied to the colors, which brightens them for values above 1
+    pub gamma: f32,
+}
+
+impl Default for Adjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+/// Parses a change in percent from -100 to 100, e.g. "+20" or "-15", into a fraction.
+pub fn parse_change(value: &str) -> Result<f32, String> {
+    let change = value
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f32>()
+        .map_err(|e| e.to_string())?;
+    if !(-100.0..=100.0).contains(&change) {
+        return Err(format!("change must be between -100 and 100: {value}"));
+    }
+    Ok(change / 100.0)
+}
+
+/// Parses a positive gamma, e.g. "2.2".
+pub fn parse_gamma(value: &str) -> Result<f32, String> {
+    let gamma = value.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    if !gamma.is_finite() || gamma <= 0.0 {
+        return Err(format!("gamma must be positive: {value}"));
+    }
+    Ok(gamma)
+}
+
+/// Adjusts the colors, keeping the bit depth and the channel layout of the image.
+pub fn adjust(img: DynamicImage, adjustment: &Adjustment) -> DynamicImage {
+    let layout = img.color();
+    let mut buffer = img.into_rgba32f();
+    for pixel in buffer.pixels_mut() {
+        for value in &mut pixel.0[..3] {
+            let brightened = *value + adjustment.brightness;
+            let contrasted = (brightened - 0.5) * (1.0 + adjustment.contrast) + 0.5;
+            *value = contrasted.max(0.0).powf(1.0 / adjustment.gamma);
+        }
+    }
+    color::to_layout(DynamicImage::ImageRgba32F(buffer), layout)
+}
+
+/// Unsharp mask, which increases the contrast at edges by adding the difference to a blurred copy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sharpen {
+    /// Standard deviation of the blur in pixels, which is about the width of the edges
+    pub sigma: f32,
+    /// Smallest difference to the blurred copy in levels of 8 bits (0 to 255) that is sharpened,
+    /// which keeps noise in smooth areas from being amplified
+    pub threshold: f32,
+}
+
+/// Parses a positive standard deviation of a blur in pixels, e.g. "1.5".
+pub fn parse_sigma(value: &str) -> Result<f32, String> {
+    let sigma = value.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    if !sigma.is_finite() || sigma <= 0.0 {
+        return Err(format!("sigma must be positive: {value}"));
+    }
+    Ok(sigma)
+}
+
+/// Parses an unsharp mask given as sigma and optional threshold, e.g. "1.0" or "1.0,4".
+pub fn parse_sharpen(value: &str) -> Result<Sharpen, String> {
+    let (sigma, threshold) = match value.split_once(',') {
+        Some((sigma, threshold)) => {
+            let threshold = threshold.trim().parse::<f32>().map_err(|e| e.to_string())?;
+            if !(0.0..=255.0).contains(&threshold) {
+                return Err(format!("threshold must be between 0 and 255: {value}"));
+            }
+            (sigma, threshold)
+        }
+        None => (value, 0.0),
+    };
+    Ok(Sharpen {
+        sigma: parse_sigma(sigma)?,
+        threshold,
+    })
+}
+
+/// Blurs the image with a Gaussian kernel, keeping its bit depth and channel layout.
+pub fn blur(img: &DynamicImage, sigma: f32) -> DynamicImage {
+    img.blur(sigma)
+}
+
+/// Sharpens the colors with an unsharp mask, keeping the bit depth, the channel layout and the
+/// alpha channel of the image.
+pub fn sharpen(img: DynamicImage, sharpen: &Sharpen) -> DynamicImage {
+    let layout = img.color();
+    let mut buffer = img.into_rgba32f();
+    let blurred = imageops::blur(&buffer, sharpen.sigma);
+    let threshold = sharpen.threshold / 255.0;
+    for (pixel, blurred) in buffer.pixels_mut().zip(blurred.pixels()) {
+        for channel in 0..3 {
+            let difference = pixel[channel] - blurred[channel];
+            if difference.abs() > threshold {
+                pixel[channel] += difference;
+            }
+        }
+    }
+    color::to_layout(DynamicImage::ImageRgba32F(buffer), layout)
+}
@@ -0,0 +1,135 @@
+use image::{Rgba, RgbaImage};
+
+/// Width of a glyph in pixels without spacing.
+const GLYPH_WIDTH: u32 = 5;
+/// Height of a glyph in pixels without spacing.
+const GLYPH_HEIGHT: u32 = 7;
+/// Horizontal distance between the origins of two glyphs.
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+/// Vertical distance between the origins of two lines.
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/// Built-in 5x7 bitmap font for the printable ASCII characters, starting at ' '. Every glyph is
+/// stored as five columns with the least significant bit at the top.
+#[rustfmt::skip]
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00], [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00], [0x08, 0x2A, 0x1C, 0x2A, 0x08], [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02], [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4B, 0x31], [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39], [0x3C, 0x4A, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1E], [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x00, 0x56, 0x36, 0x00, 0x00], [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06], [0x32, 0x49, 0x79, 0x41, 0x3E],
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], [0x7F, 0x49, 0x49, 0x49, 0x41], [0x7F, 0x09, 0x09, 0x01, 0x01],
+    [0x3E, 0x41, 0x41, 0x51, 0x32], [0x7F, 0x08, 0x08, 0x08, 0x7F], [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41], [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06], [0x3E, 0x41, 0x51, 0x21, 0x5E], [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31], [0x01, 0x01, 0x7F, 0x01, 0x01], [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x7F, 0x20, 0x18, 0x20, 0x7F], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03], [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x00, 0x7F, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x41, 0x41, 0x7F, 0x00, 0x00], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7F, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20], [0x38, 0x44, 0x44, 0x48, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x08, 0x7E, 0x09, 0x01, 0x02], [0x08, 0x14, 0x54, 0x54, 0x3C],
+    [0x7F, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7D, 0x40, 0x00], [0x20, 0x40, 0x44, 0x3D, 0x00],
+    [0x00, 0x7F, 0x10, 0x28, 0x44], [0x00, 0x41, 0x7F, 0x40, 0x00], [0x7C, 0x04, 0x18, 0x04, 0x78],
+    [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0x7C, 0x14, 0x14, 0x14, 0x08],
+    [0x08, 0x14, 0x14, 0x18, 0x7C], [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3F, 0x44, 0x40, 0x20], [0x3C, 0x40, 0x40, 0x20, 0x7C], [0x1C, 0x20, 0x40, 0x20, 0x1C],
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], [0x44, 0x28, 0x10, 0x28, 0x44], [0x0C, 0x50, 0x50, 0x50, 0x3C],
+    [0x44, 0x64, 0x54, 0x4C, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x7F, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00], [0x02, 0x01, 0x02, 0x04, 0x02],
+];
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    let index = (c as u32).wrapping_sub(' ' as u32) as usize;
+    // Characters outside of printable ASCII are drawn as '?'
+    GLYPHS.get(index).unwrap_or(&GLYPHS[(b'?' - b' ') as usize])
+}
+
+/// Size of the text in pixels when drawn with the given scale.
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let lines = text.lines().count().max(1) as u32;
+    let columns = text
+        .lines()
+        .map(|line| line.chars().count() as u32)
+        .max()
+        .unwrap_or(0);
+    let width = (columns * ADVANCE).saturating_sub(1);
+    let height = lines * LINE_HEIGHT - (LINE_HEIGHT - GLYPH_HEIGHT);
+    (width * scale, height * scale)
+}
+
+/// Draws the text with its top left corner at `(x, y)`. Pixels outside of the image are skipped.
+pub fn draw_text(img: &mut RgbaImage, text: &str, (x, y): (i64, i64), scale: u32, color: Rgba<u8>) {
+    let scale = scale as i64;
+    for (row, line) in text.lines().enumerate() {
+        let line_y = y + row as i64 * LINE_HEIGHT as i64 * scale;
+        for (column, c) in line.chars().enumerate() {
+            let glyph_x = x + column as i64 * ADVANCE as i64 * scale;
+            for (gx, bits) in glyph(c).iter().enumerate() {
+                for gy in 0..GLYPH_HEIGHT as i64 {
+                    if bits >> gy & 1 == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            let px = glyph_x + gx as i64 * scale + sx;
+                            let py = line_y + gy * scale + sy;
+                            if px >= 0
+                                && py >= 0
+                                && px < img.width() as i64
+                                && py < img.height() as i64
+                            {
+                                blend(img.get_pixel_mut(px as u32, py as u32), color);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Renders the text onto a transparent image that fits it. The outline is one pixel of the font
+/// wide and is drawn around the letters.
+pub fn render_label(
+    text: &str,
+    scale: u32,
+    color: Rgba<u8>,
+    outline: Option<Rgba<u8>>,
+) -> RgbaImage {
+    let (width, height) = text_size(text, scale);
+    let padding = if outline.is_some() { scale } else { 0 };
+    let mut img = RgbaImage::new(width + 2 * padding, height + 2 * padding);
+    let origin = padding as i64;
+    if let Some(outline) = outline {
+        for dy in [-1, 0, 1] {
+            for dx in [-1, 0, 1] {
+                if (dx, dy) == (0, 0) {
+                    continue;
+                }
+                let offset = (origin + dx * origin, origin + dy * origin);
+                draw_text(&mut img, text, offset, scale, outline);
+            }
+        }
+    }
+    draw_text(&mut img, text, (origin, origin), scale, color);
+    img
+}
+
+/// Composites `color` over `pixel` using the alpha of `color`.
+pub fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>) {
+    let alpha = color[3] as f32 / 255.0;
+    for channel in 0..3 {
+        pixel[channel] =
+            (color[channel] as f32 * alpha + pixel[channel] as f32 * (1.0 - alpha)).round() as u8;
+    }
+    pixel[3] = (color[3] as f32 + pixel[3] as f32 * (1.0 - alpha)).round() as u8;
+}
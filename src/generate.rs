@@ -0,0 +1,100 @@
+use image::{Rgba, RgbaImage};
+
+use crate::font;
+
+/// Pattern of a generated image.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Pattern {
+    /// Squares alternating between the background and the color
+    Checkerboard,
+    /// Horizontal gradient from the background to the color
+    Gradient,
+    /// Uniformly distributed gray noise
+    Noise,
+    /// Filled with the background
+    #[default]
+    Solid,
+}
+
+/// Edge length of the squares of the checkerboard pattern.
+const CHECKERBOARD_CELL_SIZE: u32 = 32;
+
+/// Parses a size given as "<width>x<height>", e.g. "1024x768".
+pub fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected <width>x<height>: {value}"))?;
+    let width = width.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    let height = height.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    if width == 0 || height == 0 {
+        return Err(format!("size must not be empty: {value}"));
+    }
+    Ok((width, height))
+}
+
+/// Colors and seed of a generated image.
+pub struct Style {
+    pub color: Rgba<u8>,
+    pub background: Rgba<u8>,
+    /// Makes the noise pattern reproducible
+    pub seed: u64,
+}
+
+/// Generates an image with the given pattern.
+pub fn generate(pattern: Pattern, (width, height): (u32, u32), style: &Style) -> RgbaImage {
+    let Style {
+        color,
+        background,
+        seed,
+    } = *style;
+    match pattern {
+        Pattern::Checkerboard => RgbaImage::from_fn(width, height, |x, y| {
+            if (x / CHECKERBOARD_CELL_SIZE + y / CHECKERBOARD_CELL_SIZE).is_multiple_of(2) {
+                color
+            } else {
+                background
+            }
+        }),
+        Pattern::Gradient => RgbaImage::from_fn(width, height, |x, _| {
+            let t = if width > 1 {
+                x as f32 / (width - 1) as f32
+            } else {
+                1.0
+            };
+            let mut pixel = background;
+            for channel in 0..4 {
+                let (from, to) = (background[channel] as f32, color[channel] as f32);
+                pixel[channel] = (from + (to - from) * t).round() as u8;
+            }
+            pixel
+        }),
+        Pattern::Noise => {
+            let mut state = seed.max(1);
+            RgbaImage::from_fn(width, height, |_, _| {
+                // xorshift64, good enough for test patterns and free of dependencies
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let value = (state >> 56) as u8;
+                Rgba([value, value, value, 255])
+            })
+        }
+        Pattern::Solid => RgbaImage::from_pixel(width, height, background),
+    }
+}
+
+/// Draws the text centered onto the image, scaled to take up about half of its width, like the
+/// placeholder images of mockups.
+pub fn draw_placeholder_text(img: &mut RgbaImage, text: &str, color: Rgba<u8>) {
+    let (text_width, text_height) = font::text_size(text, 1);
+    if text_width == 0 {
+        return;
+    }
+    let scale = (img.width() / 2 / text_width)
+        .min(img.height() / 2 / text_height)
+        .max(1);
+    let (text_width, text_height) = font::text_size(text, scale);
+    let x = (img.width() as i64 - text_width as i64) / 2;
+    let y = (img.height() as i64 - text_height as i64) / 2;
+    font::draw_text(img, text, (x, y), scale, color);
+}
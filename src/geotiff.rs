@@ -0,0 +1,198 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use image::DynamicImage;
+use tiff::{
+    decoder::Decoder,
+    encoder::{colortype, TiffEncoder},
+    tags::Tag,
+    TiffResult,
+};
+
+use crate::{atomic, error::Error};
+
+/// Geo-referencing tags of a GeoTIFF, which place the image on the earth.
+#[derive(Debug, Default, PartialEq)]
+pub struct GeoTags {
+    pub pixel_scale: Option<Vec<f64>>,
+    pub tiepoint: Option<Vec<f64>>,
+    pub transformation: Option<Vec<f64>>,
+    pub key_directory: Option<Vec<u16>>,
+    pub double_params: Option<Vec<f64>>,
+    pub ascii_params: Option<String>,
+    pub nodata: Option<String>,
+}
+
+/// Reads the geo-referencing tags of a TIFF file, or `None` if it is not a GeoTIFF.
+pub fn read(path: &Path) -> miette::Result<Option<GeoTags>> {
+    let map_err = |source| Error::GeoTiff {
+        path: path.to_owned(),
+        source,
+    };
+    let file = File::open(path).map_err(|source| Error::Open {
+        path: path.to_owned(),
+        source,
+    })?;
+    let mut decoder = Decoder::new(file).map_err(map_err)?;
+    let mut f64_vec = |tag| -> TiffResult<Option<Vec<f64>>> {
+        decoder
+            .find_tag(tag)?
+            .map(|value| value.into_f64_vec())
+            .transpose()
+    };
+    let pixel_scale = f64_vec(Tag::ModelPixelScaleTag).map_err(map_err)?;
+    let tiepoint = f64_vec(Tag::ModelTiepointTag).map_err(map_err)?;
+    let transformation = f64_vec(Tag::ModelTransformationTag).map_err(map_err)?;
+    let double_params = f64_vec(Tag::GeoDoubleParamsTag).map_err(map_err)?;
+    let key_directory = decoder
+        .find_tag_unsigned_vec(Tag::GeoKeyDirectoryTag)
+        .map_err(map_err)?;
+    let mut string = |tag| -> TiffResult<Option<String>> {
+        decoder
+            .find_tag(tag)?
+            .map(|value| value.into_string())
+            .transpose()
+    };
+    let ascii_params = string(Tag::GeoAsciiParamsTag).map_err(map_err)?;
+    let nodata = string(Tag::GdalNodata).map_err(map_err)?;
+
+    let tags = GeoTags {
+        pixel_scale,
+        tiepoint,
+        transformation,
+        key_directory,
+        double_params,
+        ascii_params,
+        nodata,
+    };
+    Ok((tags != GeoTags::default()).then_some(tags))
+}
+
+/// Saves the image as TIFF with the geo-referencing tags. Grayscale with alpha is stored as RGBA,
+/// because the encoder lacks a color type for it.
+pub fn save_tiff(img: &DynamicImage, path: &Path, tags: &GeoTags) -> miette::Result<()> {
+    atomic::write(path, |writer| {
+        encode(img, writer, tags).map_err(|source| {
+            Error::GeoTiff {
+                path: path.to_owned(),
+                source,
+            }
+            .into()
+        })
+    })
+}
+
+fn encode(img: &DynamicImage, writer: &mut BufWriter<File>, tags: &GeoTags) -> TiffResult<()> {
+    let mut encoder = TiffEncoder::new(writer)?;
+    let (width, height) = (img.width(), img.height());
+    macro_rules! write_image {
+        ($color_type:ty, $data:expr) => {{
+            let mut image = encoder.new_image::<$color_type>(width, height)?;
+            write_tags(image.encoder(), tags)?;
+            image.write_data($data)
+        }};
+    }
+    match img {
+        DynamicImage::ImageLuma8(buffer) => write_image!(colortype::Gray8, buffer),
+        DynamicImage::ImageLuma16(buffer) => write_image!(colortype::Gray16, buffer),
+        DynamicImage::ImageRgb8(buffer) => write_image!(colortype::RGB8, buffer),
+        DynamicImage::ImageRgb16(buffer) => write_image!(colortype::RGB16, buffer),
+        DynamicImage::ImageRgb32F(buffer) => write_image!(colortype::RGB32Float, buffer),
+        DynamicImage::ImageRgba16(buffer) => write_image!(colortype::RGBA16, buffer),
+        DynamicImage::ImageLumaA16(_) => write_image!(colortype::RGBA16, &img.to_rgba16()),
+        DynamicImage::ImageRgba32F(buffer) => write_image!(colortype::RGBA32Float, buffer),
+        _ => write_image!(colortype::RGBA8, &img.to_rgba8()),
+    }
+}
+
+fn write_tags<W: std::io::Write + std::io::Seek, K: tiff::encoder::TiffKind>(
+    encoder: &mut tiff::encoder::DirectoryEncoder<W, K>,
+    tags: &GeoTags,
+) -> TiffResult<()> {
+    if let Some(pixel_scale) = &tags.pixel_scale {
+        encoder.write_tag(Tag::ModelPixelScaleTag, pixel_scale.as_slice())?;
+    }
+    if let Some(tiepoint) = &tags.tiepoint {
+        encoder.write_tag(Tag::ModelTiepointTag, tiepoint.as_slice())?;
+    }
+    if let Some(transformation) = &tags.transformation {
+        encoder.write_tag(Tag::ModelTransformationTag, transformation.as_slice())?;
+    }
+    if let Some(key_directory) = &tags.key_directory {
+        encoder.write_tag(Tag::GeoKeyDirectoryTag, key_directory.as_slice())?;
+    }
+    if let Some(double_params) = &tags.double_params {
+        encoder.write_tag(Tag::GeoDoubleParamsTag, double_params.as_slice())?;
+    }
+    if let Some(ascii_params) = &tags.ascii_params {
+        encoder.write_tag(Tag::GeoAsciiParamsTag, ascii_params.as_str())?;
+    }
+    if let Some(nodata) = &tags.nodata {
+        encoder.write_tag(Tag::GdalNodata, nodata.as_str())?;
+    }
+    Ok(())
+}
+
+/// Path of the world file next to an image, which is named after the first and the last letter
+/// of the extension followed by "w", e.g. "map.pgw" for "map.png".
+pub fn world_file_path(path: &Path) -> PathBuf {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut chars = extension.chars();
+    let suffix = match (chars.next(), chars.last()) {
+        (Some(first), Some(last)) => format!("{first}{last}w"),
+        _ => format!("{extension}w"),
+    };
+    path.with_extension(suffix)
+}
+
+/// Writes a world file with the affine transformation of the tags, which lets GIS tools place
+/// images in formats without geo-referencing tags. The projection is not part of a world file.
+pub fn write_world_file(path: &Path, tags: &GeoTags) -> miette::Result<bool> {
+    let Some(parameters) = world_file_parameters(tags) else {
+        return Ok(false);
+    };
+    let contents = parameters
+        .iter()
+        .map(|parameter| format!("{parameter}\n"))
+        .collect::<String>();
+    atomic::write_data(path, contents.as_bytes())?;
+    Ok(true)
+}
+
+/// Parameters of a world file: the pixel size in x, two rotation terms, the negative pixel size
+/// in y and the coordinates of the center of the upper left pixel.
+fn world_file_parameters(tags: &GeoTags) -> Option<[f64; 6]> {
+    if let Some(m) = tags.transformation.as_deref().filter(|m| m.len() >= 16) {
+        return Some([
+            m[0],
+            m[4],
+            m[1],
+            m[5],
+            m[3] + (m[0] + m[1]) / 2.0,
+            m[7] + (m[4] + m[5]) / 2.0,
+        ]);
+    }
+    let scale = tags
+        .pixel_scale
+        .as_deref()
+        .filter(|scale| scale.len() >= 2)?;
+    let tiepoint = tags
+        .tiepoint
+        .as_deref()
+        .filter(|tiepoint| tiepoint.len() >= 6)?;
+    let (i, j, x, y) = (tiepoint[0], tiepoint[1], tiepoint[3], tiepoint[4]);
+    Some([
+        scale[0],
+        0.0,
+        0.0,
+        -scale[1],
+        x - i * scale[0] + scale[0] / 2.0,
+        y + j * scale[1] - scale[1] / 2.0,
+    ])
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+use image::{imageops::FilterType, DynamicImage, GrayImage};
+
+/// Algorithm of a perceptual hash. Similar images get hashes with a small Hamming distance.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Compares the low frequencies of the discrete cosine transform with their median. Robust
+    /// against scaling, compression and small color changes
+    #[default]
+    #[value(alias = "phash")]
+    Perceptual,
+    /// Compares the brightness of horizontally adjacent pixels. Fast and robust against
+    /// brightness changes
+    #[value(alias = "dhash")]
+    Difference,
+    /// Compares the brightness of each pixel with the mean. Fastest, but sensitive to contrast
+    /// changes
+    #[value(alias = "ahash")]
+    Average,
+}
+
+/// Perceptual hash of an image with `size * size` bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageHash {
+    bits: Vec<bool>,
+}
+
+impl ImageHash {
+    /// Number of differing bits, which is `None` for hashes of different sizes.
+    pub fn distance(&self, other: &ImageHash) -> Option<u32> {
+        if self.bits.len() != other.bits.len() {
+            return None;
+        }
+        let distance = self
+            .bits
+            .iter()
+            .zip(&other.bits)
+            .filter(|(a, b)| a != b)
+            .count();
+        Some(distance as u32)
+    }
+}
+
+impl fmt::Display for ImageHash {
+    /// Formats the bits as hexadecimal digits, most significant bit first.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for nibble in self.bits.chunks(4) {
+            let value = nibble
+                .iter()
+                .chain(std::iter::repeat(&false))
+                .take(4)
+                .fold(0, |value, &bit| value << 1 | bit as u32);
+            write!(f, "{value:x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the hash of the image with `size * size` bits.
+pub fn hash(img: &DynamicImage, algorithm: Algorithm, size: u32) -> ImageHash {
+    let bits = match algorithm {
+        Algorithm::Perceptual => perceptual(img, size),
+        Algorithm::Difference => {
+            let gray = grayscale(img, size + 1, size);
+            (0..size)
+                .flat_map(|y| (0..size).map(move |x| (x, y)))
+                .map(|(x, y)| gray.get_pixel(x, y)[0] < gray.get_pixel(x + 1, y)[0])
+                .collect()
+        }
+        Algorithm::Average => {
+            let gray = grayscale(img, size, size);
+            let mean = gray.pixels().map(|p| p[0] as f64).sum::<f64>() / gray.len() as f64;
+            gray.pixels().map(|p| p[0] as f64 > mean).collect()
+        }
+    };
+    ImageHash { bits }
+}
+
+fn grayscale(img: &DynamicImage, width: u32, height: u32) -> GrayImage {
+    img.resize_exact(width, height, FilterType::Triangle)
+        .to_luma8()
+}
+
+/// Compares the `size * size` lowest frequencies of the DCT of a downscaled image with their
+/// median. The DC coefficient is left out of the median because it only holds the mean brightness.
+fn perceptual(img: &DynamicImage, size: u32) -> Vec<bool> {
+    let n = (size * 4) as usize;
+    let gray = grayscale(img, n as u32, n as u32);
+    let pixels = gray.pixels().map(|p| p[0] as f64).collect::<Vec<_>>();
+    let cosines = (0..size as usize)
+        .flat_map(|k| {
+            (0..n)
+                .map(move |i| (std::f64::consts::PI / n as f64 * (i as f64 + 0.5) * k as f64).cos())
+        })
+        .collect::<Vec<_>>();
+    let size = size as usize;
+
+    // Separable DCT-II, first along the rows and then along the columns
+    let mut rows = vec![0.0; n * size];
+    for y in 0..n {
+        for k in 0..size {
+            rows[y * size + k] = (0..n).map(|x| pixels[y * n + x] * cosines[k * n + x]).sum();
+        }
+    }
+    let mut coefficients = vec![0.0; size * size];
+    for k in 0..size {
+        for x in 0..size {
+            coefficients[k * size + x] = (0..n)
+                .map(|y| rows[y * size + x] * cosines[k * n + y])
+                .sum();
+        }
+    }
+
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted.get(sorted.len() / 2).copied().unwrap_or_default();
+    coefficients.iter().map(|&c| c > median).collect()
+}
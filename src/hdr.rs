@@ -0,0 +1,113 @@
+use image::{DynamicImage, Rgb32FImage};
+
+use crate::color;
+
+/// Parses an exposure time in seconds given either as a decimal ("0.5") or a fraction ("1/30").
+pub fn parse_exposure_time(value: &str) -> Result<f32, String> {
+    let time = match value.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator = numerator.trim().parse::<f32>().map_err(|e| e.to_string())?;
+            let denominator = denominator
+                .trim()
+                .parse::<f32>()
+                .map_err(|e| e.to_string())?;
+            numerator / denominator
+        }
+        None => value.trim().parse::<f32>().map_err(|e| e.to_string())?,
+    };
+    if time.is_finite() && time > 0.0 {
+        Ok(time)
+    } else {
+        Err(format!("exposure time must be positive: {value}"))
+    }
+}
+
+/// Merges bracketed exposures of the same scene into a linear HDR image.
+///
+/// This follows Debevec and Malik, but assumes the camera response to be the sRGB transfer
+/// function instead of recovering it. Every exposure contributes `linear / time` weighted by a hat
+/// function that favors well-exposed values. Without `exposure_times`, the relative exposures are
+/// estimated from the pixels that are well exposed in both the first and the respective image.
+pub fn merge(images: &[DynamicImage], exposure_times: Option<&[f32]>) -> DynamicImage {
+    let encoded = images.iter().map(|img| img.to_rgb32f()).collect::<Vec<_>>();
+    let linear = images
+        .iter()
+        .zip(&encoded)
+        .map(|(img, encoded)| {
+            if matches!(
+                img,
+                DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+            ) {
+                encoded.clone()
+            } else {
+                let mut linear = encoded.clone();
+                for channel in linear.iter_mut() {
+                    *channel = color::srgb_to_linear(*channel);
+                }
+                linear
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let exposure_times = match exposure_times {
+        Some(exposure_times) => exposure_times.to_vec(),
+        None => estimate_exposure_times(&encoded, &linear),
+    };
+    tracing::debug!("Exposure times: {:?}", exposure_times);
+
+    let (width, height) = encoded[0].dimensions();
+    let mut merged = Rgb32FImage::new(width, height);
+    for (index, channel) in merged.iter_mut().enumerate() {
+        let mut weighted_sum = 0.0;
+        let mut weight_sum = 0.0;
+        let mut fallback_sum = 0.0;
+        for ((encoded, linear), time) in encoded.iter().zip(&linear).zip(&exposure_times) {
+            let radiance = linear.as_raw()[index] / time;
+            let weight = hat(encoded.as_raw()[index]);
+            weighted_sum += weight * radiance;
+            weight_sum += weight;
+            fallback_sum += radiance;
+        }
+        *channel = if weight_sum > 0.0 {
+            weighted_sum / weight_sum
+        } else {
+            fallback_sum / images.len() as f32
+        };
+    }
+    DynamicImage::ImageRgb32F(merged)
+}
+
+fn hat(z: f32) -> f32 {
+    1.0 - (2.0 * z - 1.0).abs()
+}
+
+fn is_well_exposed(z: f32) -> bool {
+    (0.05..=0.95).contains(&z)
+}
+
+fn estimate_exposure_times(encoded: &[Rgb32FImage], linear: &[Rgb32FImage]) -> Vec<f32> {
+    let mut exposure_times = vec![1.0];
+    for (encoded_i, linear_i) in encoded.iter().zip(linear).skip(1) {
+        let mut sum_i = 0.0;
+        let mut sum_0 = 0.0;
+        for (((z_0, z_i), l_0), l_i) in encoded[0]
+            .iter()
+            .zip(encoded_i.iter())
+            .zip(linear[0].iter())
+            .zip(linear_i.iter())
+        {
+            if is_well_exposed(*z_0) && is_well_exposed(*z_i) {
+                sum_i += l_i;
+                sum_0 += l_0;
+            }
+        }
+        let ratio = if sum_0 > 0.0 && sum_i > 0.0 {
+            sum_i / sum_0
+        } else {
+            tracing::warn!("Failed to estimate the exposure time, assuming one stop difference");
+            2f32.powi(exposure_times.len() as i32)
+        };
+        exposure_times.push(ratio);
+    }
+    exposure_times
+}
@@ -0,0 +1,156 @@
+use image::DynamicImage;
+
+use crate::color;
+
+/// RGB color profile described by its primaries and tone curves. Such matrix profiles are used by
+/// cameras and displays, e.g. Display P3 or Adobe RGB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// Conversion from linear RGB to XYZ relative to D50, with the primaries as columns
+    matrix: [[f32; 3]; 3],
+    curves: [Curve; 3],
+}
+
+/// Tone curve of a channel, which maps encoded values to linear values.
+#[derive(Debug, Clone, PartialEq)]
+enum Curve {
+    /// Values of the curve at equidistant points
+    Table(Vec<f32>),
+    /// Parametric curve `(a * x + b) ^ g + e` above `d` and `c * x + f` below it
+    Parametric([f32; 7]),
+}
+
+impl Curve {
+    fn linearize(&self, value: f32) -> f32 {
+        match self {
+            Curve::Table(table) => {
+                let position = value.clamp(0.0, 1.0) * (table.len() - 1) as f32;
+                let index = (position as usize).min(table.len() - 2);
+                let fraction = position - index as f32;
+                table[index] * (1.0 - fraction) + table[index + 1] * fraction
+            }
+            Curve::Parametric([g, a, b, c, d, e, f]) => {
+                if value >= *d {
+                    (a * value + b).max(0.0).powf(*g) + e
+                } else {
+                    c * value + f
+                }
+            }
+        }
+    }
+}
+
+/// XYZ of the primaries of sRGB relative to D50 as rows, inverted to convert to linear sRGB.
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133856, -1.6168667, -0.4906146],
+    [-0.9787684, 1.9161415, 0.0334540],
+    [0.0719453, -0.2289914, 1.4052427],
+];
+
+/// Parses an ICC profile of an RGB matrix profile. Returns `None` for other profiles, e.g. of
+/// grayscale images or with lookup tables.
+pub fn parse(data: &[u8]) -> Option<Profile> {
+    if data.get(16..20)? != b"RGB " {
+        return None;
+    }
+    let u32_at = |offset: usize| {
+        Some(u32::from_be_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let tag = |signature: &[u8; 4]| {
+        let count = u32_at(128)? as usize;
+        (0..count).find_map(|index| {
+            let entry = 132 + index * 12;
+            if data.get(entry..entry + 4)? != signature {
+                return None;
+            }
+            let (offset, size) = (u32_at(entry + 4)? as usize, u32_at(entry + 8)? as usize);
+            data.get(offset..offset.checked_add(size)?)
+        })
+    };
+    let fixed = |data: &[u8], offset: usize| {
+        let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(i32::from_be_bytes(bytes) as f32 / 65536.0)
+    };
+    let xyz = |signature| {
+        let data = tag(signature).filter(|data| data.starts_with(b"XYZ "))?;
+        Some([fixed(data, 8)?, fixed(data, 12)?, fixed(data, 16)?])
+    };
+    let curve = |signature| parse_curve(tag(signature)?, fixed);
+
+    let primaries = [xyz(b"rXYZ")?, xyz(b"gXYZ")?, xyz(b"bXYZ")?];
+    let matrix = [0, 1, 2].map(|row| primaries.map(|primary| primary[row]));
+    let curves = [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?];
+    Some(Profile { matrix, curves })
+}
+
+fn parse_curve(data: &[u8], fixed: impl Fn(&[u8], usize) -> Option<f32>) -> Option<Curve> {
+    let u16_at = |offset: usize| {
+        Some(u16::from_be_bytes(
+            data.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    match data.get(..4)? {
+        b"curv" => {
+            let count = u32::from_be_bytes(data.get(8..12)?.try_into().ok()?) as usize;
+            match count {
+                0 => Some(Curve::Parametric([1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0])),
+                1 => {
+                    let gamma = u16_at(12)? as f32 / 256.0;
+                    Some(Curve::Parametric([gamma, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]))
+                }
+                _ => {
+                    let table = (0..count)
+                        .map(|index| Some(u16_at(12 + index * 2)? as f32 / 65535.0))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some(Curve::Table(table))
+                }
+            }
+        }
+        b"para" => {
+            let parameters = match u16_at(8)? {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return None,
+            };
+            let p = (0..parameters)
+                .map(|index| fixed(data, 12 + index * 4))
+                .collect::<Option<Vec<_>>>()?;
+            // All function types are special cases of the last one
+            let [g, a, b, c, d, e, f] = match p[..] {
+                [g] => [g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                [g, a, b] => [g, a, b, 0.0, -b / a, 0.0, 0.0],
+                [g, a, b, c] => [g, a, b, 0.0, -b / a, c, c],
+                [g, a, b, c, d] => [g, a, b, c, d, 0.0, 0.0],
+                [g, a, b, c, d, e, f] => [g, a, b, c, d, e, f],
+                _ => unreachable!(),
+            };
+            Some(Curve::Parametric([g, a, b, c, d, e, f]))
+        }
+        _ => None,
+    }
+}
+
+/// Converts the colors of the image from the profile to sRGB, keeping its bit depth and channels.
+/// Colors outside of the sRGB gamut are clipped.
+pub fn to_srgb(img: DynamicImage, profile: &Profile) -> DynamicImage {
+    let layout = img.color();
+    let mut buffer = img.into_rgba32f();
+    for pixel in buffer.pixels_mut() {
+        let linear = [0, 1, 2].map(|channel| profile.curves[channel].linearize(pixel[channel]));
+        let xyz = multiply(&profile.matrix, linear);
+        let srgb = multiply(&XYZ_TO_SRGB, xyz);
+        for (channel, value) in srgb.into_iter().enumerate() {
+            pixel[channel] = color::linear_to_srgb(value.clamp(0.0, 1.0));
+        }
+    }
+    color::to_layout(DynamicImage::ImageRgba32F(buffer), layout)
+}
+
+fn multiply(matrix: &[[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
@@ -0,0 +1,223 @@
+use image::{DynamicImage, Rgba};
+use serde::{Deserialize, Serialize};
+
+use crate::{color, mask, resize};
+
+/// Square icon of an icon set, which is written to `path` relative to the output directory. Spec
+/// files of the `icons` command are JSON arrays of icons.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Icon {
+    pub path: String,
+    pub size: u32,
+    /// Whether transparent pixels are composited over the background, because the platform shows
+    /// transparency as black, e.g. iOS
+    #[serde(default)]
+    pub opaque: bool,
+    /// Whether the icon is cut out as a circle, e.g. for the round launcher icons of Android
+    #[serde(default)]
+    pub round: bool,
+}
+
+impl Icon {
+    fn new(path: &str, size: u32, opaque: bool) -> Self {
+        Self {
+            path: path.to_owned(),
+            size,
+            opaque,
+            round: false,
+        }
+    }
+}
+
+/// Platform with a built-in set of app icons.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    /// iOS and Android
+    #[default]
+    All,
+    /// AppIcon asset catalog for Xcode
+    Ios,
+    /// Launcher icons in the mipmap directories of each density
+    Android,
+}
+
+/// Directory of the iOS asset catalog, relative to the output directory.
+pub const IOS_DIR: &str = "ios/AppIcon.appiconset";
+
+/// Icons of the iOS asset catalog as idiom, size in points and scale.
+const IOS_ICONS: [(&str, &str, u32); 18] = [
+    ("iphone", "20", 2),
+    ("iphone", "20", 3),
+    ("iphone", "29", 2),
+    ("iphone", "29", 3),
+    ("iphone", "40", 2),
+    ("iphone", "40", 3),
+    ("iphone", "60", 2),
+    ("iphone", "60", 3),
+    ("ipad", "20", 1),
+    ("ipad", "20", 2),
+    ("ipad", "29", 1),
+    ("ipad", "29", 2),
+    ("ipad", "40", 1),
+    ("ipad", "40", 2),
+    ("ipad", "76", 1),
+    ("ipad", "76", 2),
+    ("ipad", "83.5", 2),
+    ("ios-marketing", "1024", 1),
+];
+
+/// Densities of Android with the size of the launcher icons.
+const ANDROID_DENSITIES: [(&str, u32); 5] = [
+    ("mdpi", 48),
+    ("hdpi", 72),
+    ("xhdpi", 96),
+    ("xxhdpi", 144),
+    ("xxxhdpi", 192),
+];
+
+/// App icons of the platform. Icons that are shared by several idioms are only listed once.
+pub fn platform(platform: Platform) -> Vec<Icon> {
+    match platform {
+        Platform::All => [ios(), android()].concat(),
+        Platform::Ios => ios(),
+        Platform::Android => android(),
+    }
+}
+
+fn ios() -> Vec<Icon> {
+    let mut icons = Vec::<Icon>::new();
+    for (_, points, scale) in IOS_ICONS {
+        let path = format!("{IOS_DIR}/{}", ios_file_name(points, scale));
+        if icons.iter().all(|icon| icon.path != path) {
+            icons.push(Icon::new(&path, ios_pixels(points, scale), true));
+        }
+    }
+    icons
+}
+
+fn ios_file_name(points: &str, scale: u32) -> String {
+    format!("Icon-{points}@{scale}x.png")
+}
+
+fn ios_pixels(points: &str, scale: u32) -> u32 {
+    let points = points.parse::<f32>().unwrap_or_default();
+    (points * scale as f32).round() as u32
+}
+
+fn android() -> Vec<Icon> {
+    let mut icons = Vec::new();
+    for (density, size) in ANDROID_DENSITIES {
+        let dir = format!("android/res/mipmap-{density}");
+        icons.push(Icon::new(&format!("{dir}/ic_launcher.png"), size, false));
+        icons.push(Icon {
+            round: true,
+            ..Icon::new(&format!("{dir}/ic_launcher_round.png"), size, false)
+        });
+    }
+    icons.push(Icon::new("android/playstore-icon.png", 512, false));
+    icons
+}
+
+/// Contents.json of the iOS asset catalog, which maps the idioms and sizes to the files.
+pub fn ios_contents() -> serde_json::Value {
+    let images = IOS_ICONS
+        .iter()
+        .map(|&(idiom, points, scale)| {
+            serde_json::json!({
+                "idiom": idiom,
+                "size": format!("{points}x{points}"),
+                "scale": format!("{scale}x"),
+                "filename": ios_file_name(points, scale),
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({
+        "images": images,
+        "info": { "version": 1, "author": "imy" },
+    })
+}
+
+/// Sizes of the icons in favicon.ico, which are picked by browsers for tabs and bookmarks.
+pub const FAVICON_ICO_SIZES: [u32; 3] = [16, 32, 48];
+
+/// PNG icons of a favicon set, next to favicon.ico.
+pub fn favicon() -> Vec<Icon> {
+    vec![
+        Icon::new("favicon-16x16.png", 16, false),
+        Icon::new("favicon-32x32.png", 32, false),
+        Icon::new("apple-touch-icon.png", 180, true),
+        Icon::new("android-chrome-192x192.png", 192, false),
+        Icon::new("android-chrome-512x512.png", 512, false),
+    ]
+}
+
+/// Renders the icon from the image, which is scaled to fit and centered.
+pub fn render(img: &DynamicImage, icon: &Icon, background: Rgba<u8>) -> DynamicImage {
+    let resized = resize::resize(img, icon.size, icon.size, resize::Mode::Contain);
+    let resized = if icon.round {
+        DynamicImage::ImageRgba8(mask::apply(&resized, mask::Shape::Circle))
+    } else {
+        resized
+    };
+    if icon.opaque {
+        let (flattened, _) = color::flatten(resized, background);
+        DynamicImage::ImageRgb8(flattened.into_rgb8())
+    } else {
+        DynamicImage::ImageRgba8(resized.into_rgba8())
+    }
+}
+
+/// Web app manifest that refers to the Android icons of the favicon set.
+#[derive(Serialize)]
+pub struct WebManifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short_name: Option<String>,
+    icons: Vec<ManifestIcon>,
+    display: &'static str,
+}
+
+#[derive(Serialize)]
+struct ManifestIcon {
+    src: String,
+    sizes: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+impl WebManifest {
+    pub fn new(name: Option<String>, icons: &[Icon]) -> Self {
+        let icons = icons
+            .iter()
+            .filter(|icon| icon.path.starts_with("android-chrome"))
+            .map(|icon| ManifestIcon {
+                src: format!("/{}", icon.path),
+                sizes: format!("{0}x{0}", icon.size),
+                kind: "image/png",
+            })
+            .collect();
+        Self {
+            short_name: name.clone(),
+            name,
+            icons,
+            display: "standalone",
+        }
+    }
+}
+
+/// HTML tags for the head of a page that refer to the favicon set at the root of the site.
+pub fn favicon_html(manifest: bool) -> String {
+    let mut html = String::from("<link rel=\"icon\" href=\"/favicon.ico\" sizes=\"48x48\">\n");
+    for size in [32, 16] {
+        html += &format!(
+            "<link rel=\"icon\" type=\"image/png\" sizes=\"{size}x{size}\" \
+             href=\"/favicon-{size}x{size}.png\">\n"
+        );
+    }
+    html += "<link rel=\"apple-touch-icon\" sizes=\"180x180\" href=\"/apple-touch-icon.png\">\n";
+    if manifest {
+        html += "<link rel=\"manifest\" href=\"/site.webmanifest\">\n";
+    }
+    html
+}
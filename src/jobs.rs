@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// Manifest of the `run` command with the jobs in the order in which they are run.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobFile {
+    #[serde(rename = "job", default)]
+    pub jobs: Vec<Job>,
+}
+
+/// Pipeline that is applied to the images of a source. Paths are relative to the job file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// Name that is printed when the job is finished
+    pub name: Option<String>,
+    /// File or directory with the images
+    pub source: PathBuf,
+    /// Globs of the files in the source directory to process, e.g. "*.jpg"
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs of the files in the source directory to skip
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Stages of the pipeline, e.g. "resize 800x"
+    #[serde(default)]
+    pub stages: Vec<String>,
+    /// Format of the outputs. Defaults to overwriting the images in their format
+    pub format: Option<String>,
+    /// Quality of lossy formats (jpeg, avif) from 0 to 100
+    pub quality: Option<u8>,
+    /// Directory to write the outputs to, mirroring the directory structure of the source
+    pub out_dir: Option<PathBuf>,
+}
+
+impl Job {
+    /// Stages of the pipeline including the conversion into the format of the job.
+    pub fn pipeline_stages(&self) -> Vec<String> {
+        let convert = self.format.as_ref().map(|format| match self.quality {
+            Some(quality) => format!("convert {format} --quality {quality}"),
+            None => format!("convert {format}"),
+        });
+        self.stages.iter().cloned().chain(convert).collect()
+    }
+}
+
+/// Reads a job file and resolves the paths of the jobs relative to its directory.
+pub fn read(path: &Path) -> miette::Result<JobFile> {
+    let text = std::fs::read_to_string(path).map_err(|source| Error::ReadFile {
+        path: path.to_owned(),
+        source,
+    })?;
+    let mut file = toml::from_str::<JobFile>(&text).map_err(|source| Error::JobFile {
+        path: path.to_owned(),
+        source,
+    })?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    for (index, job) in file.jobs.iter_mut().enumerate() {
+        let name = job.name.get_or_insert_with(|| format!("job {}", index + 1));
+        if job.stages.is_empty() && job.format.is_none() {
+            return Err(Error::InvalidJob {
+                name: name.clone(),
+                help: "Add stages or a format to the job".to_owned(),
+            }
+            .into());
+        }
+        if job.quality.is_some() && job.format.is_none() {
+            return Err(Error::InvalidJob {
+                name: name.clone(),
+                help: "The quality requires a format".to_owned(),
+            }
+            .into());
+        }
+        job.source = base.join(&job.source);
+        job.out_dir = job.out_dir.as_ref().map(|dir| base.join(dir));
+    }
+    Ok(file)
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Name of the journal in the converted directory, which is hidden like the temporary files.
+pub const FILE_NAME: &str = ".imy-journal";
+
+/// First line of the journal.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    /// Fingerprint of the options of the conversion
+    options: u32,
+}
+
+/// Line of the journal for each converted file, with paths relative to the directory.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    source: String,
+    output: String,
+}
+
+/// Files that an interrupted conversion of a directory has converted.
+#[derive(Debug)]
+pub struct Progress {
+    pub options: u32,
+    done: BTreeMap<String, String>,
+    dir: PathBuf,
+}
+
+impl Progress {
+    /// Whether the source was converted into the output, which still exists.
+    pub fn is_done(&self, source: &Path, output: &Path) -> bool {
+        self.done
+            .get(&key(&self.dir, source))
+            .is_some_and(|done| *done == key(&self.dir, output) && output.exists())
+    }
+
+    /// Whether the file is the output of a converted source, which is not converted itself.
+    pub fn is_output(&self, path: &Path) -> bool {
+        let key = key(&self.dir, path);
+        self.done.values().any(|output| *output == key)
+    }
+}
+
+/// Reads the journal of the directory, or `None` if no conversion was interrupted.
+pub fn read(dir: &Path) -> miette::Result<Option<Progress>> {
+    let path = dir.join(FILE_NAME);
+    let journal = match fs::read_to_string(&path) {
+        Ok(journal) => journal,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(Error::ReadFile { path, source }.into()),
+    };
+    let mut lines = journal.lines();
+    let Some(header) = lines
+        .next()
+        .and_then(|line| serde_json::from_str::<Header>(line).ok())
+    else {
+        tracing::warn!("Ignoring the invalid journal {}", path.display());
+        return Ok(None);
+    };
+    // The last line is incomplete if the conversion was interrupted while writing it
+    let done = lines
+        .map_while(|line| serde_json::from_str::<Entry>(line).ok())
+        .map(|entry| (entry.source, entry.output))
+        .collect();
+    Ok(Some(Progress {
+        options: header.options,
+        done,
+        dir: dir.to_owned(),
+    }))
+}
+
+/// Journal that records each converted file as soon as its output is written, so that the
+/// conversion can be resumed after an interruption.
+pub struct Journal {
+    file: File,
+    dir: PathBuf,
+}
+
+impl Journal {
+    /// Starts a new journal in the directory, replacing that of an earlier conversion.
+    pub fn create(dir: &Path, options: u32) -> miette::Result<Self> {
+        let path = dir.join(FILE_NAME);
+        let file = File::create(&path).map_err(|source| Error::WriteFile { path, source })?;
+        let mut journal = Self {
+            file,
+            dir: dir.to_owned(),
+        };
+        journal.write_line(&Header { options })?;
+        Ok(journal)
+    }
+
+    /// Continues the journal of the directory after the files that were converted before.
+    pub fn resume(dir: &Path) -> miette::Result<Self> {
+        let path = dir.join(FILE_NAME);
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(|source| Error::Open { path, source })?;
+        Ok(Self {
+            file,
+            dir: dir.to_owned(),
+        })
+    }
+
+    /// Records that the source was converted into the output.
+    pub fn record(&mut self, source: &Path, output: &Path) -> miette::Result<()> {
+        let entry = Entry {
+            source: key(&self.dir, source),
+            output: key(&self.dir, output),
+        };
+        self.write_line(&entry)
+    }
+
+    /// Deletes the journal once the conversion is complete.
+    pub fn remove(self) -> miette::Result<()> {
+        let path = self.dir.join(FILE_NAME);
+        drop(self.file);
+        fs::remove_file(&path).map_err(|source| Error::Delete { path, source }.into())
+    }
+
+    fn write_line(&mut self, line: &impl Serialize) -> miette::Result<()> {
+        let write_err = |source| Error::WriteFile {
+            path: self.dir.join(FILE_NAME),
+            source,
+        };
+        let mut line = serde_json::to_string(line).map_err(Error::Json)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes()).map_err(write_err)?;
+        self.file.sync_data().map_err(write_err)?;
+        Ok(())
+    }
+}
+
+/// Path relative to the directory, so that the journal doesn't depend on the working directory.
+fn key(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,8974 @@
+//! Converting and inspecting images, as used by the `imy` command line tool.
+//!
+//! The commands of the tool are available as functions that take a [`Context`] for the output
+//! and the confirmations, e.g. [`convert()`], [`info()`] and [`is()`]. [`run()`] executes parsed
+//! command line [`Args`].
+//!
+//! ```no_run
+//! use std::{io, path::Path};
+//!
+//! let mut stdout = io::stdout();
+//! let mut context = imy::Context::new(&mut stdout);
+//! context.assume_yes = true;
+//! let options = imy::ConvertOptions {
+//!     target_format: "webp".to_owned(),
+//!     ..Default::default()
+//! };
+//! imy::convert(&mut context, Path::new("photos"), &options)?;
+//! # Ok::<(), miette::Report>(())
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+#[cfg(feature = "watch")]
+use std::{sync::mpsc, time::Duration};
+
+use clap::{CommandFactory, Parser, Subcommand};
+use ignore::{overrides::OverrideBuilder, WalkBuilder};
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        ico::{IcoEncoder, IcoFrame},
+        jpeg::JpegEncoder,
+    },
+    metadata::Orientation,
+    ColorType, DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader,
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::Serialize;
+use tracing::Level;
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, FmtSubscriber};
+
+pub use color::{ColorSpace, OutputColorType};
+pub use config::Config;
+pub use error::{Error, ExitCode};
+pub use hash::Algorithm as HashAlgorithm;
+pub use resize::Mode as ResizeMode;
+pub use tonemap::Operator as TonemapOperator;
+
+mod append;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod atomic;
+mod cache;
+mod color;
+mod compare;
+mod config;
+mod dedupe;
+#[cfg(feature = "http")]
+mod download;
+mod error;
+mod exif;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "ffmpeg")]
+mod ffmpeg;
+mod filter;
+mod font;
+mod generate;
+mod geotiff;
+mod hash;
+mod hdr;
+mod icc;
+mod icons;
+mod jobs;
+mod journal;
+mod log_file;
+mod mask;
+mod metadata;
+mod openexr;
+mod optimize;
+mod overlay;
+mod preview;
+#[cfg(feature = "python")]
+mod python;
+mod resize;
+mod stego;
+#[cfg(feature = "stitch")]
+mod stitch;
+mod template;
+mod tonemap;
+
+#[derive(Parser, Debug, Default)]
+#[command(version, about, long_about = None, subcommand_precedence_over_arg = true)]
+pub struct Args {
+    /// Paths of the files or directories to process
+    paths: Vec<PathBuf>,
+
+    /// Also process the paths in the file, or in stdin with "-", separated by newlines or by NUL
+    /// characters like the output of `find -print0`. Listed files are converted as one batch
+    #[arg(long)]
+    files_from: Option<PathBuf>,
+
+    /// Log level for logging to the console
+    #[arg(short, long)]
+    log_level: Option<String>,
+
+    /// Format of the log messages, e.g. json for log pipelines. Json logs at the info level unless
+    /// another level is given
+    #[arg(long, value_enum, default_value_t)]
+    log_format: LogFormat,
+
+    /// File to write the log to instead of the console, e.g. "imy.log". Logs at the debug level
+    /// unless another level is given. The file is rotated once it grows beyond 10 MiB
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Log more details to the console: -v for info, -vv for debug and -vvv for trace
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "log_level")]
+    verbose: u8,
+
+    /// Print nothing but errors, e.g. when only the exit code matters in scripts. Image streams,
+    /// extracted payloads and completion scripts are still written to stdout
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Answer yes to all confirmation prompts
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Move files to the trash instead of deleting or overwriting them
+    #[arg(long, global = true)]
+    trash: bool,
+
+    /// Print which files would be read, written, skipped or overwritten without changing any
+    /// file. Supported by convert, thumbnail, srcset, favicon, icons, watermark, caption,
+    /// composite, adjust, blur, sharpen, pipeline, filter, mask, rotate, flip, optimize,
+    /// strip-metadata and dedupe
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    walk: WalkOptions,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+/// Selection of the files in directories.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct WalkOptions {
+    /// Only process files matching the glob, relative to the directory, e.g. "*.jpg"
+    #[arg(long, global = true, value_parser = parse_glob)]
+    pub include: Vec<String>,
+
+    /// Skip files matching the glob, relative to the directory, e.g. "thumbs/**"
+    #[arg(long, global = true, value_parser = parse_glob)]
+    pub exclude: Vec<String>,
+
+    /// Include files that are ignored by .gitignore, .ignore and similar files
+    #[arg(long, global = true)]
+    pub no_ignore: bool,
+
+    /// Include hidden files and directories
+    #[arg(long, global = true)]
+    pub hidden: bool,
+
+    /// Descend at most this many levels into directories. 1 only includes the files directly in
+    /// the directory
+    #[arg(long, global = true, conflicts_with = "no_recursive")]
+    pub max_depth: Option<usize>,
+
+    /// Only include the files directly in the directory, same as --max-depth 1
+    #[arg(long, global = true)]
+    pub no_recursive: bool,
+}
+
+// The commands are parsed once per run, so the size of the conversion options doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Convert images to another format. `imy photo.jpg png` is a shorthand for
+    /// `imy photo.jpg convert -t png`
+    #[command(visible_alias = "c")]
+    Convert(ConvertOptions),
+    Is {
+        /// Format to check for
+        #[arg(short, long)]
+        format: String,
+
+        /// For directories, succeed if any image matches instead of all
+        #[arg(long)]
+        any: bool,
+    },
+    #[command(visible_aliases = ["i", "ls"])]
+    Info {
+        /// Template for the output, e.g. "{width}x{height} {format} {size}"
+        #[arg(short, long)]
+        format: Option<String>,
+        /// Output as text or as JSON for scripts
+        #[arg(short, long, value_enum, default_value_t, conflicts_with = "format")]
+        output: OutputFormat,
+        /// Print the path, or the templated line if given, of each image terminated by a NUL
+        /// character instead of a newline, e.g. for `xargs -0`
+        #[arg(short = '0', long, conflicts_with = "output")]
+        print0: bool,
+    },
+    /// List all supported formats and whether they can be decoded and encoded
+    Formats,
+    /// Print the version, the enabled features, the codecs and the formats of this build, e.g. to
+    /// check whether feature-gated support is available
+    Doctor,
+    /// Print the completion script for a shell, e.g. `imy completions bash > ~/.bash_completion`
+    Completions {
+        /// Shell to complete the subcommands and options in
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Place images side by side or stack them, e.g. for before and after composites
+    Append(AppendArgs),
+    /// Compare images with the images at another path. Directories are paired by relative path
+    Compare(CompareArgs),
+    /// Compute perceptual hashes of images, e.g. to find near-duplicates
+    Hash(HashArgs),
+    /// Find groups of identical and similar images in a directory tree
+    Dedupe(DedupeArgs),
+    /// Generate small previews of images, which keep their aspect ratio
+    Thumbnail(ThumbnailArgs),
+    /// Encode images at several widths for responsive web pages and print the srcset attribute
+    Srcset(SrcsetArgs),
+    /// Generate the favicon set of a website from a logo
+    Favicon(FaviconArgs),
+    /// Generate the app icons of iOS and Android from a master image of 1024x1024 pixels
+    Icons(IconsArgs),
+    /// Composite an overlay, e.g. a logo, onto images in place
+    Watermark(WatermarkArgs),
+    /// Draw text, e.g. the file name or a copyright line, onto images in place
+    Caption(CaptionArgs),
+    /// Blend a layer onto images in place, e.g. to assemble layered assets
+    Composite(CompositeArgs),
+    /// Adjust the brightness, contrast and gamma of images in place
+    Adjust(AdjustArgs),
+    /// Blur images in place with a Gaussian kernel, e.g. for backgrounds
+    Blur {
+        /// Standard deviation of the blur in pixels
+        #[arg(short, long, value_parser = filter::parse_sigma)]
+        sigma: f32,
+    },
+    /// Sharpen images in place with an unsharp mask, e.g. after downscaling
+    Sharpen {
+        /// Standard deviation of the blur of the mask in pixels, about the width of the edges
+        #[arg(short, long, default_value_t = 1.0, value_parser = filter::parse_sigma)]
+        sigma: f32,
+
+        /// Smallest difference in levels of 8 bits (0 to 255) that is sharpened, which keeps
+        /// noise in smooth areas from being amplified
+        #[arg(short, long, default_value_t = 0.0)]
+        threshold: f32,
+    },
+    /// Apply several operations to images in order and encode them once, e.g.
+    /// `pipeline "resize 800x" "sharpen 1.0" "convert webp --quality 80"`
+    Pipeline(PipelineArgs),
+    /// Apply color filters to images in place, in the given order
+    Filter {
+        /// Filters to apply
+        #[arg(required = true, value_enum)]
+        filters: Vec<filter::Filter>,
+    },
+    /// Cut out rounded corners or a circle with an alpha mask, e.g. for avatars
+    Mask(MaskArgs),
+    /// Rotate images clockwise in place
+    Rotate(RotateArgs),
+    /// Mirror images in place
+    Flip(FlipArgs),
+    /// Losslessly shrink PNG and JPEG files by recompressing them and dropping metadata that
+    /// doesn't affect the display
+    Optimize,
+    /// Draw images in the terminal, e.g. to check them over SSH without opening a viewer
+    Show(ShowArgs),
+    /// Render images as ASCII art, or as colored blocks with --color
+    Ascii(AsciiArgs),
+    /// Print the EXIF data of photos, e.g. the camera, the exposure and the location
+    Exif {
+        /// Output as text or as JSON
+        #[arg(short, long, value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+    /// Remove EXIF, XMP and IPTC metadata from PNG and JPEG files in place, e.g. for privacy
+    /// before publishing photos
+    StripMetadata(StripMetadataArgs),
+    /// Export metadata to sidecars or import it from them
+    #[command(subcommand)]
+    Meta(MetaCommands),
+    /// Merge bracketed exposures of the same scene into a single HDR image
+    MergeHdr(MergeHdrArgs),
+    /// Generate an image with a test pattern
+    Generate(GenerateArgs),
+    /// Run the pipelines of a TOML job file, e.g. as a reproducible build step for assets
+    Run {
+        /// Job file with a [[job]] table for each source
+        job_file: PathBuf,
+    },
+    /// Extract stills from a video with ffmpeg
+    #[cfg(feature = "ffmpeg")]
+    Frames(FramesArgs),
+    /// Encode a sequence of stills, sorted by path, into a video with ffmpeg
+    #[cfg(feature = "ffmpeg")]
+    Video(VideoArgs),
+    /// Hide data in the least significant bits of an image or extract it again
+    #[command(subcommand)]
+    Stego(StegoCommands),
+    /// Stitch overlapping shots, ordered from left to right, into a panorama
+    #[cfg(feature = "stitch")]
+    Stitch(StitchArgs),
+    /// Watch a directory and convert images as they are created or modified
+    #[cfg(feature = "watch")]
+    Watch(WatchArgs),
+}
+
+/// Options of a conversion, which are also the arguments of the `convert` command.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct ConvertOptions {
+    /// Format to convert to
+    #[arg(short, long)]
+    pub target_format: String,
+
+    /// Format of the input, which selects the decoder. Determined from the contents or the
+    /// extension by default
+    #[arg(long)]
+    pub from_format: Option<String>,
+
+    /// Bits per channel of the output (8 or 16). Defaults to the depth of the source if the
+    /// target format supports it
+    #[arg(long)]
+    pub bit_depth: Option<u8>,
+
+    /// Tone-mapping operator used when converting HDR images to formats without HDR support
+    #[arg(long, value_enum, default_value_t)]
+    pub tonemap: tonemap::Operator,
+
+    /// Exposure adjustment in stops that is applied before tone mapping
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub exposure: f32,
+
+    /// Layer of an OpenEXR file to convert, e.g. "diffuse"
+    #[arg(long)]
+    pub layer: Option<String>,
+
+    /// Single channel of an OpenEXR file to convert into a grayscale image, e.g. "Z"
+    #[arg(long)]
+    pub channel: Option<String>,
+
+    /// Transfer function of the input. Defaults to linear for floating-point images and sRGB
+    /// otherwise
+    #[arg(long, value_enum)]
+    pub input_colorspace: Option<color::ColorSpace>,
+
+    /// Transfer function of the output. Defaults to linear for OpenExr and Hdr and sRGB otherwise
+    #[arg(long, value_enum)]
+    pub output_colorspace: Option<color::ColorSpace>,
+
+    /// Pixel layout of the output. Defaults to the layout of the source, adapted to the target
+    #[arg(long, value_enum, conflicts_with = "bit_depth")]
+    pub color_type: Option<color::OutputColorType>,
+
+    /// Use error diffusion when reducing 16-bit or HDR images to 8 bits per channel
+    #[arg(long)]
+    pub dither_depth: bool,
+
+    /// Quality of lossy target formats (jpeg, avif) from 0 to 100. Defaults to the encoder's
+    /// default, e.g. 75 for jpeg and 80 for avif
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub quality: Option<u8>,
+
+    /// Largest size of each output, e.g. "200KB" or "1.5MiB". The highest quality that fits is
+    /// searched for, up to --quality. Requires jpeg or avif as target format
+    #[arg(long, value_parser = parse_file_size)]
+    pub max_size: Option<u64>,
+
+    /// Sizes of the icons in an ico file, e.g. "16,32,48,256". Each size is downscaled from the
+    /// source and padded to a square. Defaults to a single icon of the size of the image
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(1..=256))]
+    pub ico_sizes: Vec<u32>,
+
+    /// Standard deviation in pixels of a Gaussian blur applied after resizing
+    #[arg(long, value_parser = filter::parse_sigma)]
+    pub blur: Option<f32>,
+
+    /// Unsharp mask applied after resizing and blurring, given as sigma in pixels and optional
+    /// threshold in levels of 8 bits, e.g. "1.0" or "0.8,4"
+    #[arg(long, value_parser = filter::parse_sharpen)]
+    pub sharpen: Option<filter::Sharpen>,
+
+    /// Color filter applied after resizing. Can be given several times to apply filters in order
+    #[arg(long, value_enum)]
+    pub filter: Vec<filter::Filter>,
+
+    /// Box to resize the images into, e.g. "800x600"
+    #[arg(short, long, value_parser = generate::parse_size)]
+    pub resize: Option<(u32, u32)>,
+
+    /// Factor by which each image is scaled, e.g. "50%" or "0.5"
+    #[arg(short, long, value_parser = resize::parse_scale, conflicts_with = "resize")]
+    pub scale: Option<f64>,
+
+    /// How the images are resized into the box
+    #[arg(short, long, value_enum, default_value_t, requires = "resize")]
+    pub mode: resize::Mode,
+
+    /// Keep the pixels as stored instead of rotating and flipping them upright according to the
+    /// EXIF orientation
+    #[arg(long)]
+    pub no_auto_orient: bool,
+
+    /// Copy the EXIF data, the color profile and the text metadata of each file to its output.
+    /// Only supported by png and jpeg as target formats
+    #[arg(long)]
+    pub keep_metadata: bool,
+
+    /// Convert the colors from the embedded color profile to sRGB instead of copying the profile
+    /// to the output, e.g. for wide-gamut photos on the web
+    #[arg(long)]
+    pub convert_to_srgb: bool,
+
+    /// Color over which transparent images are composited for target formats without
+    /// transparency, e.g. jpeg. Defaults to white
+    #[arg(long, value_parser = color::parse_color)]
+    pub background: Option<image::Rgba<u8>>,
+
+    /// Number of files that are converted in parallel. Defaults to the number of CPUs
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+
+    /// Directory to write the converted images to, mirroring the directory structure of the
+    /// input. Defaults to writing next to the source
+    #[arg(long)]
+    pub out_dir: Option<PathBuf>,
+
+    /// File name of each output with placeholders, e.g. "{stem}_{width}x{height}.{ext}". The
+    /// placeholders are {stem}, {ext} or {format}, {width} and {height} of the output, {hash} of
+    /// the source file and {date} on which a photo was taken. Defaults to "{stem}.{ext}"
+    #[arg(long)]
+    pub output_template: Option<String>,
+
+    /// What to do when several sources would be converted to the same output
+    #[arg(long, value_enum, default_value_t)]
+    pub on_collision: Collision,
+
+    /// Delete each original after its output has been written and verified. Respects --trash
+    #[arg(long, conflicts_with = "move_original_to")]
+    pub delete_original: bool,
+
+    /// Move each original into the directory after its output has been written and verified,
+    /// mirroring the directory structure of the input
+    #[arg(long)]
+    pub move_original_to: Option<PathBuf>,
+
+    /// Overwrite existing outputs without asking
+    #[arg(long, conflicts_with = "skip_existing")]
+    pub force: bool,
+
+    /// Skip files whose output already exists
+    #[arg(long)]
+    pub skip_existing: bool,
+
+    /// Skip files whose output already exists and is newer than the file
+    #[arg(long)]
+    pub newer_than_output: bool,
+
+    /// Skip files that were converted with the same options before and haven't changed since,
+    /// which is recorded in a .imy-cache file in the converted directory
+    #[arg(long)]
+    pub incremental: bool,
+
+    /// Convert all files even if their outputs are up to date, and refresh the cache
+    #[arg(long, requires = "incremental")]
+    pub rewrite_all: bool,
+
+    /// Continue an interrupted conversion of a directory with the files that it hasn't
+    /// converted yet. Each conversion of a directory records its progress in a .imy-journal file
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Decode each output and check its dimensions. Files that fail the verification are reported
+    /// at the end and their originals are kept
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Continue with the other files when a file fails to convert and print a summary of the
+    /// converted, skipped and failed files at the end
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Decode the output and fail unless its pixels are identical to the source. Requires a
+    /// lossless target format
+    #[arg(long, conflicts_with_all = ["resize", "scale", "ico_sizes", "filter", "blur", "sharpen"])]
+    pub verify_pixels: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AppendArgs {
+    /// Direction in which the images are placed
+    #[arg(short, long, value_enum, default_value_t)]
+    direction: append::Direction,
+
+    /// Alignment of images that are smaller across the direction
+    #[arg(short, long, value_enum, default_value_t)]
+    align: append::Align,
+
+    /// Space between the images in pixels
+    #[arg(short, long, default_value_t = 0)]
+    gap: u32,
+
+    /// Color of the gaps and uncovered space as hex code, e.g. "#ffffff"
+    #[arg(short, long, value_parser = color::parse_color, default_value = "transparent")]
+    background: image::Rgba<u8>,
+
+    /// Path of the combined image, e.g. "comparison.png"
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct CompareArgs {
+    /// File or directory to compare with
+    other: PathBuf,
+
+    /// Include subdirectories
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Pair files whose paths only differ in the extension, e.g. after a migration to another
+    /// format
+    #[arg(long)]
+    ignore_extension: bool,
+
+    /// Write an image highlighting the differing pixels in red, e.g. "diff.png". When comparing
+    /// directories, this is a directory that receives a PNG per different file
+    #[arg(long)]
+    diff: Option<PathBuf>,
+
+    /// Output as text or as JSON with the PSNR and SSIM of every pair of files
+    #[arg(short, long, value_enum, default_value_t)]
+    output: OutputFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct HashArgs {
+    /// Hash algorithm
+    #[arg(short, long, value_enum, default_value_t)]
+    algorithm: HashAlgorithm,
+
+    /// Edge length of the hash, which has size * size bits
+    #[arg(short, long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(2..=64))]
+    size: u32,
+
+    /// Output as text or as JSON
+    #[arg(short, long, value_enum, default_value_t)]
+    output: OutputFormat,
+
+    /// Terminate each line with a NUL character instead of a newline, e.g. for paths with
+    /// newlines
+    #[arg(short = '0', long, conflicts_with = "output")]
+    print0: bool,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct StripMetadataArgs {
+    /// Only remove the GPS location from the EXIF data
+    #[arg(long)]
+    gps_only: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DedupeArgs {
+    /// Largest distance in bits between the perceptual hashes of near-duplicates
+    #[arg(short, long, default_value_t = 4, conflicts_with = "exact")]
+    threshold: u32,
+
+    /// Only group files with identical contents, without decoding them
+    #[arg(long)]
+    exact: bool,
+
+    /// Hash algorithm for near-duplicates
+    #[arg(short, long, value_enum, default_value_t, conflicts_with = "exact")]
+    algorithm: HashAlgorithm,
+
+    /// Delete every file of a group except the one to keep
+    #[arg(long)]
+    delete_duplicates: bool,
+
+    /// File of each group to keep
+    #[arg(long, value_enum, default_value_t)]
+    keep: Keep,
+
+    /// Output as text or as JSON
+    #[arg(short, long, value_enum, default_value_t)]
+    output: OutputFormat,
+
+    /// Print only the duplicates, which are not kept, each terminated by a NUL character, e.g.
+    /// for `xargs -0`
+    #[arg(short = '0', long, conflicts_with = "output")]
+    print0: bool,
+}
+
+impl Default for DedupeArgs {
+    fn default() -> Self {
+        DedupeArgs {
+            threshold: 4,
+            exact: false,
+            algorithm: HashAlgorithm::default(),
+            delete_duplicates: false,
+            keep: Keep::default(),
+            output: OutputFormat::default(),
+            print0: false,
+        }
+    }
+}
+
+/// File of a group of duplicates that is kept.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Keep {
+    /// The image with the most pixels, then the largest file
+    #[default]
+    Largest,
+    /// The file that was modified first
+    Oldest,
+}
+
+impl Default for HashArgs {
+    fn default() -> Self {
+        HashArgs {
+            algorithm: HashAlgorithm::default(),
+            size: 8,
+            output: OutputFormat::default(),
+            print0: false,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct MaskArgs {
+    /// Radius of the rounded corners in pixels
+    #[arg(long, conflicts_with = "circle", required_unless_present = "circle")]
+    rounded: Option<u32>,
+
+    /// Cut out the largest circle that fits into the image
+    #[arg(long)]
+    circle: bool,
+
+    /// Format of the masked images, which has to support transparency
+    #[arg(short, long, default_value = "png")]
+    target_format: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ThumbnailArgs {
+    /// Longest edge of the thumbnails in pixels. Smaller images are not enlarged
+    #[arg(short, long, default_value_t = 256, value_parser = clap::value_parser!(u32).range(1..))]
+    size: u32,
+
+    /// Suffix appended to the file name of each image for its thumbnail
+    #[arg(long, default_value = "_thumb", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    suffix: String,
+
+    /// Directory to write the thumbnails to instead of next to the images, mirroring the
+    /// directory structure of the input, e.g. "thumbs". The thumbnails keep the file names
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+
+    /// Format of the thumbnails. Defaults to the format of each image, or png if it can't be
+    /// encoded
+    #[arg(short, long)]
+    target_format: Option<String>,
+
+    /// Quality of lossy formats (jpeg, avif) from 0 to 100
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    quality: Option<u8>,
+}
+
+impl Default for ThumbnailArgs {
+    fn default() -> Self {
+        Self {
+            size: 256,
+            suffix: "_thumb".to_owned(),
+            out_dir: None,
+            target_format: None,
+            quality: None,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct SrcsetArgs {
+    /// Widths of the encodes in pixels, e.g. "320,640,1280". Widths larger than the image are
+    /// skipped
+    #[arg(short, long, required = true, value_delimiter = ',')]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    widths: Vec<u32>,
+
+    /// Format of the encodes, e.g. webp. Defaults to the format of each image
+    #[arg(short, long, alias = "format")]
+    target_format: Option<String>,
+
+    /// Quality of lossy formats (jpeg, avif) from 0 to 100
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    quality: Option<u8>,
+
+    /// Directory to write the encodes to instead of next to the images, mirroring the directory
+    /// structure of the input
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+
+    /// Output the srcset attribute as text or a manifest of the encodes as JSON
+    #[arg(short, long, value_enum, default_value_t)]
+    output: OutputFormat,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct FaviconArgs {
+    /// Directory to write the icons to, usually the root of the website
+    #[arg(long, default_value = ".")]
+    out_dir: PathBuf,
+
+    /// Also write site.webmanifest, which refers to the Android icons
+    #[arg(long)]
+    manifest: bool,
+
+    /// Name of the website in the web manifest
+    #[arg(long, requires = "manifest")]
+    name: Option<String>,
+
+    /// Color over which the apple-touch-icon is composited, since iOS shows transparency as
+    /// black. Defaults to white
+    #[arg(long, value_parser = color::parse_color)]
+    background: Option<image::Rgba<u8>>,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct IconsArgs {
+    /// Directory to write the icon sets to
+    #[arg(long, default_value = ".")]
+    out_dir: PathBuf,
+
+    /// Platform of the built-in icon sets
+    #[arg(short, long, value_enum, default_value_t)]
+    platform: icons::Platform,
+
+    /// JSON file with the icons to generate instead of the built-in sets, e.g.
+    /// [{"path": "icon-64.png", "size": 64, "opaque": true, "round": false}]
+    #[arg(long, conflicts_with = "platform")]
+    spec: Option<PathBuf>,
+
+    /// Color over which opaque icons, e.g. those of iOS, are composited. Defaults to white
+    #[arg(long, value_parser = color::parse_color)]
+    background: Option<image::Rgba<u8>>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct WatermarkArgs {
+    /// Image that is composited onto the images, usually with transparency
+    #[arg(short, long)]
+    image: PathBuf,
+
+    /// Position of the overlay
+    #[arg(short, long, value_enum, default_value_t)]
+    gravity: overlay::Gravity,
+
+    /// Opacity of the overlay as fraction or percentage, e.g. "0.5" or "50%"
+    #[arg(long, default_value = "1", value_parser = overlay::parse_opacity)]
+    opacity: f32,
+
+    /// Distance of the overlay to the edges of the images in pixels
+    #[arg(short, long, default_value_t = 0)]
+    margin: u32,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CaptionArgs {
+    /// Text to draw, which can span several lines. Placeholders: {name}, {stem}, {width},
+    /// {height} and {date}, the date on which a photo was taken
+    text: String,
+
+    /// Height of the letters in pixels, rounded to a multiple of the 7 pixels of the built-in font
+    #[arg(short, long, default_value_t = 14)]
+    size: u32,
+
+    /// Color of the text
+    #[arg(short, long, default_value = "white", value_parser = color::parse_color)]
+    color: image::Rgba<u8>,
+
+    /// Color of an outline around the letters, which keeps the text readable on any background
+    #[arg(long, value_parser = color::parse_color)]
+    outline: Option<image::Rgba<u8>>,
+
+    /// Position of the text
+    #[arg(short, long, value_enum, default_value_t)]
+    gravity: overlay::Gravity,
+
+    /// Distance of the text to the edges of the images in pixels
+    #[arg(short, long, default_value_t = 8)]
+    margin: u32,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CompositeArgs {
+    /// Image that is blended onto the images
+    overlay: PathBuf,
+
+    /// How the colors of the overlay are combined with the colors below it
+    #[arg(short, long, value_enum, default_value_t)]
+    blend: overlay::BlendMode,
+
+    /// Horizontal position of the left edge of the overlay in pixels
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    x: i64,
+
+    /// Vertical position of the top edge of the overlay in pixels
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    y: i64,
+
+    /// Opacity of the overlay as fraction or percentage, e.g. "0.5" or "50%"
+    #[arg(long, default_value = "1", value_parser = overlay::parse_opacity)]
+    opacity: f32,
+}
+
+#[derive(clap::Args, Debug, Default)]
+#[group(required = true, multiple = true)]
+pub struct AdjustArgs {
+    /// Change of the brightness in percent from -100 to 100, e.g. "+10"
+    #[arg(short, long, allow_negative_numbers = true, value_parser = filter::parse_change)]
+    brightness: Option<f32>,
+
+    /// Change of the contrast in percent from -100 to 100, e.g. "-20"
+    #[arg(short, long, allow_negative_numbers = true, value_parser = filter::parse_change)]
+    contrast: Option<f32>,
+
+    /// Gamma correction, which brightens the midtones for values above 1, e.g. "1.2"
+    #[arg(short, long, value_parser = filter::parse_gamma)]
+    gamma: Option<f32>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RotateArgs {
+    /// Clockwise rotation
+    #[arg(short, long, value_enum)]
+    degrees: Rotation,
+}
+
+/// Rotation in steps of 90 degrees, which are lossless for the pixels.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    #[value(name = "90")]
+    Rotate90,
+    #[value(name = "180")]
+    Rotate180,
+    #[value(name = "270")]
+    Rotate270,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct FlipArgs {
+    /// Mirror from left to right
+    #[arg(long, required_unless_present = "vertical")]
+    horizontal: bool,
+
+    /// Mirror from top to bottom
+    #[arg(long)]
+    vertical: bool,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct PipelineArgs {
+    /// Stages, each with the arguments of the operation, e.g. "resize 800x" or "filter sepia".
+    /// Operations: resize, scale, rotate, flip, blur, sharpen, adjust and filter. A final
+    /// "convert <format>" stage selects the format, otherwise the images are overwritten
+    #[arg(required = true)]
+    stages: Vec<String>,
+
+    /// Directory to write the outputs to, mirroring the directory structure of the input
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+}
+
+/// Parser of a single stage of a pipeline.
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+struct StageParser {
+    #[command(subcommand)]
+    stage: Stage,
+}
+
+/// Operation of a pipeline with the same arguments as the corresponding command.
+#[derive(Subcommand, Debug)]
+enum Stage {
+    /// Resize into a box, in which one of the dimensions can be left out, e.g. "800x"
+    Resize {
+        #[arg(value_parser = resize::parse_box)]
+        size: (Option<u32>, Option<u32>),
+
+        /// How the images are resized into the box. Requires both dimensions, except for fit
+        #[arg(short, long, value_enum, default_value_t)]
+        mode: resize::Mode,
+    },
+    /// Scale by a factor, e.g. "50%"
+    Scale {
+        #[arg(value_parser = resize::parse_scale)]
+        factor: f64,
+    },
+    /// Rotate clockwise, e.g. "--degrees 90"
+    Rotate(RotateArgs),
+    /// Mirror with "--horizontal" and/or "--vertical"
+    Flip(FlipArgs),
+    /// Gaussian blur with the standard deviation in pixels
+    Blur {
+        #[arg(value_parser = filter::parse_sigma)]
+        sigma: f32,
+    },
+    /// Unsharp mask given as sigma and optional threshold, e.g. "1.0,4"
+    Sharpen {
+        #[arg(value_parser = filter::parse_sharpen)]
+        sharpen: filter::Sharpen,
+    },
+    /// Adjust the brightness, contrast and gamma, e.g. "--brightness +10"
+    Adjust(AdjustArgs),
+    /// Color filters, e.g. "grayscale"
+    Filter {
+        #[arg(required = true, value_enum)]
+        filters: Vec<filter::Filter>,
+    },
+    /// Encode in the format, which has to be the last stage
+    Convert {
+        target_format: String,
+
+        /// Quality of lossy formats (jpeg, avif) from 0 to 100
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(0..=100))]
+        quality: Option<u8>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MetaCommands {
+    /// Print the EXIF, ICC and text metadata as JSON or write it to sidecars
+    Export {
+        /// Write a sidecar next to each image, e.g. "photo.jpg.json" for "photo.jpg"
+        #[arg(long)]
+        sidecar: bool,
+    },
+    /// Write the metadata of the sidecars back into the images (png and jpeg)
+    Import,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct MergeHdrArgs {
+    /// Path of the merged image, e.g. "out.exr"
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Exposure times of the inputs in seconds, e.g. "1/30,1/60,1/125". Estimated from the
+    /// images if omitted
+    #[arg(long, value_delimiter = ',', value_parser = hdr::parse_exposure_time)]
+    exposure_times: Vec<f32>,
+}
+
+#[cfg(feature = "stitch")]
+#[derive(clap::Args, Debug, Default)]
+pub struct StitchArgs {
+    /// Path of the panorama, e.g. "pano.jpg"
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[cfg(feature = "watch")]
+#[derive(clap::Args, Debug)]
+pub struct WatchArgs {
+    /// Time in milliseconds without further changes to a file before it is converted, so that
+    /// files are only read once they are written completely
+    #[arg(long, default_value_t = 500)]
+    debounce: u64,
+
+    #[command(subcommand)]
+    command: WatchCommands,
+}
+
+#[cfg(feature = "watch")]
+#[derive(Subcommand, Debug)]
+pub enum WatchCommands {
+    /// Convert the images that are created or modified. Images in the target format are skipped,
+    /// since they are usually outputs
+    Convert(ConvertOptions),
+}
+
+#[cfg(feature = "ffmpeg")]
+#[derive(clap::Args, Debug)]
+pub struct FramesArgs {
+    /// Interval between the stills, e.g. "1s", "500ms" or "2m"
+    #[arg(long, value_parser = ffmpeg::parse_duration, default_value = "1s")]
+    every: f64,
+
+    /// Directory the stills are written to
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Format to convert the stills to. They are kept as png if omitted
+    #[arg(short, long)]
+    target_format: Option<String>,
+}
+
+#[cfg(feature = "ffmpeg")]
+#[derive(clap::Args, Debug)]
+pub struct VideoArgs {
+    /// Frames per second
+    #[arg(long, default_value_t = 30.0)]
+    fps: f64,
+
+    /// Size of the video, e.g. "1920x1080". Defaults to the size of the first still. Stills with
+    /// a different aspect ratio are cropped
+    #[arg(short, long, value_parser = generate::parse_size)]
+    size: Option<(u32, u32)>,
+
+    /// Path of the video, e.g. "timelapse.mp4"
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct ShowArgs {
+    /// Width of the image in columns. Defaults to the width of the terminal
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+
+    /// How the image is drawn. Defaults to the protocol that the terminal is detected to support,
+    /// falling back to half blocks
+    #[arg(short, long, value_enum)]
+    protocol: Option<preview::Protocol>,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct AsciiArgs {
+    /// Width of the art in columns. Defaults to the width of the terminal
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+
+    /// Draw colored half blocks with ANSI escape codes instead of plain characters
+    #[arg(short, long)]
+    color: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GenerateArgs {
+    /// Pattern of the image
+    #[arg(short, long, value_enum, default_value_t)]
+    pattern: generate::Pattern,
+
+    /// Size of the image, e.g. "1024x768"
+    #[arg(short, long, value_parser = generate::parse_size, default_value = "512x512")]
+    size: (u32, u32),
+
+    /// Color of the pattern and the text as hex code, e.g. "#ff8000"
+    #[arg(short, long, value_parser = color::parse_color, default_value = "#666666")]
+    color: image::Rgba<u8>,
+
+    /// Background color as hex code, e.g. "#ccc"
+    #[arg(short, long, value_parser = color::parse_color, default_value = "#cccccc")]
+    background: image::Rgba<u8>,
+
+    /// Text that is drawn centered onto the image, e.g. "800x600"
+    #[arg(long)]
+    text: Option<String>,
+
+    /// Seed of the noise pattern
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+
+    /// Path of the generated image, e.g. "test.png"
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StegoCommands {
+    /// Hide the contents of a file in the image
+    Embed(StegoEmbedArgs),
+    /// Extract hidden data from the image
+    Extract(StegoExtractArgs),
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct StegoEmbedArgs {
+    /// File whose contents are hidden in the image
+    #[arg(short, long)]
+    file: PathBuf,
+
+    /// Path of the image with the hidden data. Defaults to the input with the png extension
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Passphrase that scrambles the hidden data (obfuscates, not encryption)
+    #[arg(short, long)]
+    passphrase: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct StegoExtractArgs {
+    /// File to write the hidden data to. Defaults to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Passphrase that was used to embed the data
+    #[arg(short, long)]
+    passphrase: Option<String>,
+}
+
+/// Format of the output of the `info` and `compare` commands.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Lines of text
+    #[default]
+    Text,
+    /// JSON, e.g. for processing with jq
+    Json,
+}
+
+/// Format of the log messages.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Lines of text
+    #[default]
+    Text,
+    /// A JSON object per line with the path of the file in the span, e.g. for log pipelines
+    Json,
+}
+
+/// Handling of sources that would be converted to the same output, e.g. photo.jpg and photo.png.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collision {
+    /// Append -1, -2 and so on to the outputs of all but the first source
+    #[default]
+    Suffix,
+    /// Stop before converting any file
+    Error,
+}
+
+/// Output and settings that are shared by all commands.
+pub struct Context<'a> {
+    /// Receives the printed output. Shared across the threads of parallel conversions, which only
+    /// read the flags
+    pub stdout: &'a mut (dyn io::Write + Send + Sync),
+    /// The real stdout while `stdout` is silenced by `--quiet`, for output that is data rather
+    /// than status, like encoded images, extracted payloads and completion scripts
+    data: Option<&'a mut (dyn io::Write + Send + Sync)>,
+    /// Answer yes to all confirmations, e.g. before overwriting files
+    pub assume_yes: bool,
+    /// Move files to the trash instead of deleting or overwriting them
+    pub use_trash: bool,
+    /// Whether the user can be asked for confirmation on stdin
+    pub interactive: bool,
+    /// Answer to confirmations instead of reading it from stdin, e.g. in tests
+    answer: Option<bool>,
+    /// Print what would be changed instead of changing files
+    pub dry_run: bool,
+    /// Selection of the files in directories
+    pub walk: WalkOptions,
+    /// Defaults for the options that are not given on the command line
+    pub config: Config,
+}
+
+impl<'a> Context<'a> {
+    /// Creates a non-interactive context with all settings disabled.
+    pub fn new(stdout: &'a mut (dyn io::Write + Send + Sync)) -> Self {
+        Self {
+            stdout,
+            data: None,
+            assume_yes: false,
+            use_trash: false,
+            interactive: false,
+            answer: None,
+            dry_run: false,
+            walk: WalkOptions::default(),
+            config: Config::default(),
+        }
+    }
+
+    /// Returns the writer for data output, which `--quiet` does not silence.
+    fn data(&mut self) -> &mut (dyn io::Write + Send + Sync) {
+        match self.data.as_deref_mut() {
+            Some(data) => data,
+            None => self.stdout,
+        }
+    }
+}
+
+/// Executes the command line arguments.
+pub fn run(context: Context, args: Args) -> miette::Result<()> {
+    // Quiet runs print status into a sink instead of checking the flag at every print, while
+    // data still goes to the real stdout
+    let mut sink = io::sink();
+    let (stdout, data): (
+        &mut (dyn io::Write + Send + Sync),
+        Option<&mut (dyn io::Write + Send + Sync)>,
+    ) = if args.quiet {
+        (&mut sink, Some(context.stdout))
+    } else {
+        (context.stdout, None)
+    };
+    let mut context = Context {
+        stdout,
+        data,
+        assume_yes: context.assume_yes | args.yes,
+        use_trash: context.use_trash | args.trash,
+        interactive: context.interactive,
+        answer: context.answer,
+        dry_run: context.dry_run | args.dry_run,
+        walk: args.walk,
+        config: context.config,
+    };
+    context.walk.no_ignore |= context.config.ignore == Some(false);
+    context.walk.hidden |= context.config.hidden == Some(true);
+
+    // Watching reports the failed conversions as errors, which are logged by default
+    #[cfg(feature = "watch")]
+    let watching = matches!(args.command, Some(Commands::Watch(_)));
+    #[cfg(not(feature = "watch"))]
+    let watching = false;
+    let log_level = match (args.log_level, args.verbose) {
+        (Some(log_level), _) => Some(string_to_log_level(&log_level)?),
+        (None, 0) if args.log_file.is_some() => Some(Level::DEBUG),
+        (None, 0) if args.log_format == LogFormat::Json => Some(Level::INFO),
+        (None, 0) if watching => Some(Level::ERROR),
+        (None, 0) => None,
+        (None, 1) => Some(Level::INFO),
+        (None, 2) => Some(Level::DEBUG),
+        (None, _) => Some(Level::TRACE),
+    };
+    if let Some(log_level) = log_level {
+        let writer = match &args.log_file {
+            Some(path) => BoxMakeWriter::new(Mutex::new(log_file::open(path)?)),
+            None => BoxMakeWriter::new(io::stdout),
+        };
+        let builder = FmtSubscriber::builder()
+            .with_max_level(log_level)
+            .with_writer(writer)
+            .with_ansi(args.log_file.is_none());
+        match args.log_format {
+            LogFormat::Text => tracing::subscriber::set_global_default(builder.finish()),
+            LogFormat::Json => tracing::subscriber::set_global_default(builder.json().finish()),
+        }
+        .map_err(|_| Error::TracingSubscriber)?;
+    }
+
+    match &args.command {
+        Some(Commands::Formats) => return formats(&mut context),
+        Some(Commands::Doctor) => return doctor(&mut context),
+        Some(Commands::Completions { shell }) => {
+            clap_complete::generate(*shell, &mut Args::command(), "imy", context.data());
+            return Ok(());
+        }
+        Some(Commands::Generate(generate_args)) => return generate(&context, generate_args),
+        Some(Commands::Run { job_file }) => return run_jobs(&mut context, job_file),
+        _ => {}
+    }
+
+    // The listed paths come first, so that `imy --files-from - png` is still a conversion
+    let paths = match &args.files_from {
+        Some(list) => {
+            let listed = if list == Path::new("-") {
+                read_file_list(io::stdin()).map_err(Error::Stdin)?
+            } else {
+                let file = File::open(list).map_err(|source| Error::ReadFile {
+                    path: list.to_owned(),
+                    source,
+                })?;
+                read_file_list(file).map_err(|source| Error::ReadFile {
+                    path: list.to_owned(),
+                    source,
+                })?
+            };
+            if listed.is_empty() && args.paths.is_empty() {
+                tracing::debug!("No paths listed in {}", list.display());
+                return Ok(());
+            }
+            listed.into_iter().chain(args.paths).collect()
+        }
+        None => args.paths,
+    };
+    let (paths, command) = conversion_shorthand(paths, args.command);
+    let command = command.map(|command| with_config(&context.config, command));
+
+    // Listing the current directory is harmless, while the other commands write files or are
+    // used in scripts where a forgotten path should not go unnoticed
+    let paths = if paths.is_empty() && matches!(command, None | Some(Commands::Info { .. })) {
+        vec![PathBuf::from(".")]
+    } else {
+        paths
+    };
+    if paths.is_empty() {
+        return Err(Error::PathRequired.into());
+    }
+    if let [path] = paths.as_slice() {
+        if let Some(Commands::Convert(convert_args)) = &command {
+            if path == Path::new("-") {
+                return convert_stream(&mut context, io::stdin(), convert_args);
+            }
+        }
+    }
+    // The downloads are deleted when the command is done
+    #[cfg(feature = "http")]
+    let downloads = download::Downloads::new();
+    #[cfg(feature = "http")]
+    let (paths, command) = download_urls(&downloads, paths, command)?;
+    for path in &paths {
+        if !path.exists() {
+            return Err(Error::PathNotFound {
+                path: path.to_owned(),
+            }
+            .into());
+        }
+        tracing::debug!("Path exists: {}", path.display());
+    }
+
+    match command {
+        Some(Commands::Convert(convert_args)) => {
+            // Listed files are converted in parallel like the files in a directory
+            let (files, paths) = paths
+                .iter()
+                .partition::<Vec<_>, _>(|path| args.files_from.is_some() && path.is_file());
+            if !files.is_empty() {
+                convert_listed(&mut context, &files, &convert_args)?;
+            }
+            for path in paths {
+                convert(&mut context, path, &convert_args)?;
+            }
+        }
+        Some(Commands::Is { format, any }) => {
+            for path in &paths {
+                if let Some(path) = is(&context.walk, path, &format, any)? {
+                    return Err(Error::FormatMismatch { path }.into());
+                }
+            }
+        }
+        Some(Commands::Info {
+            format,
+            output,
+            print0,
+        }) => {
+            for path in &paths {
+                info(&mut context, path, format.as_deref(), output, print0)?;
+            }
+        }
+        Some(Commands::Append(append_args)) => append(&context, &paths, &append_args)?,
+        Some(Commands::Compare(compare_args)) => {
+            for path in &paths {
+                compare(&mut context, path, &compare_args)?;
+            }
+        }
+        Some(Commands::Hash(hash_args)) => {
+            for path in &paths {
+                hash(&mut context, path, &hash_args)?;
+            }
+        }
+        Some(Commands::Dedupe(dedupe_args)) => {
+            for path in &paths {
+                dedupe(&mut context, path, &dedupe_args)?;
+            }
+        }
+        Some(Commands::Thumbnail(thumbnail_args)) => {
+            for path in &paths {
+                thumbnail(&mut context, path, &thumbnail_args)?;
+            }
+        }
+        Some(Commands::Srcset(srcset_args)) => {
+            for path in &paths {
+                srcset(&mut context, path, &srcset_args)?;
+            }
+        }
+        Some(Commands::Favicon(favicon_args)) => {
+            for path in &paths {
+                favicon(&mut context, path, &favicon_args)?;
+            }
+        }
+        Some(Commands::Icons(icons_args)) => {
+            for path in &paths {
+                app_icons(&mut context, path, &icons_args)?;
+            }
+        }
+        Some(Commands::Watermark(watermark_args)) => {
+            let overlay = decode_image(&watermark_args.image)?;
+            for path in &paths {
+                transform(&mut context, path, |_, img| {
+                    let position = overlay::position(
+                        watermark_args.gravity,
+                        img.dimensions(),
+                        overlay.dimensions(),
+                        watermark_args.margin,
+                    );
+                    Ok(overlay::composite(
+                        img,
+                        &overlay,
+                        position,
+                        watermark_args.opacity,
+                        overlay::BlendMode::Normal,
+                    ))
+                })?;
+            }
+        }
+        Some(Commands::Composite(composite_args)) => {
+            let overlay = decode_image(&composite_args.overlay)?;
+            for path in &paths {
+                transform(&mut context, path, |_, img| {
+                    Ok(overlay::composite(
+                        img,
+                        &overlay,
+                        (composite_args.x, composite_args.y),
+                        composite_args.opacity,
+                        composite_args.blend,
+                    ))
+                })?;
+            }
+        }
+        Some(Commands::Caption(caption_args)) => {
+            for path in &paths {
+                caption(&mut context, path, &caption_args)?;
+            }
+        }
+        Some(Commands::Adjust(adjust_args)) => {
+            let adjustment = filter::Adjustment {
+                brightness: adjust_args.brightness.unwrap_or(0.0),
+                contrast: adjust_args.contrast.unwrap_or(0.0),
+                gamma: adjust_args.gamma.unwrap_or(1.0),
+            };
+            for path in &paths {
+                transform(&mut context, path, |_, img| {
+                    Ok(filter::adjust(img, &adjustment))
+                })?;
+            }
+        }
+        Some(Commands::Blur { sigma }) => {
+            for path in &paths {
+                transform(&mut context, path, |_, img| Ok(filter::blur(&img, sigma)))?;
+            }
+        }
+        Some(Commands::Sharpen { sigma, threshold }) => {
+            let sharpen = filter::Sharpen { sigma, threshold };
+            for path in &paths {
+                transform(&mut context, path, |_, img| {
+                    Ok(filter::sharpen(img, &sharpen))
+                })?;
+            }
+        }
+        Some(Commands::Pipeline(pipeline_args)) => {
+            for path in &paths {
+                pipeline(&mut context, path, &pipeline_args)?;
+            }
+        }
+        Some(Commands::Filter { filters }) => {
+            for path in &paths {
+                transform(&mut context, path, |_, img| {
+                    Ok(filters
+                        .iter()
+                        .fold(img, |img, &filter| filter::apply(img, filter)))
+                })?;
+            }
+        }
+        Some(Commands::Mask(mask_args)) => {
+            for path in &paths {
+                mask(&mut context, path, &mask_args)?;
+            }
+        }
+        Some(Commands::Rotate(rotate_args)) => {
+            for path in &paths {
+                transform(&mut context, path, |_, img| {
+                    Ok(match rotate_args.degrees {
+                        Rotation::Rotate90 => img.rotate90(),
+                        Rotation::Rotate180 => img.rotate180(),
+                        Rotation::Rotate270 => img.rotate270(),
+                    })
+                })?;
+            }
+        }
+        Some(Commands::Optimize) => {
+            for path in &paths {
+                optimize(&mut context, path)?;
+            }
+        }
+        Some(Commands::Flip(flip_args)) => {
+            for path in &paths {
+                transform(&mut context, path, |_, img| {
+                    let img = if flip_args.horizontal {
+                        img.fliph()
+                    } else {
+                        img
+                    };
+                    Ok(if flip_args.vertical { img.flipv() } else { img })
+                })?;
+            }
+        }
+        Some(Commands::Show(show_args)) => {
+            for path in &paths {
+                show(&mut context, path, &show_args)?;
+            }
+        }
+        Some(Commands::Ascii(ascii_args)) => {
+            for path in &paths {
+                ascii(&mut context, path, &ascii_args)?;
+            }
+        }
+        Some(Commands::Exif { output }) => {
+            for path in &paths {
+                exif(&mut context, path, output)?;
+            }
+        }
+        Some(Commands::StripMetadata(strip_args)) => {
+            for path in &paths {
+                strip_metadata(&mut context, path, &strip_args)?;
+            }
+        }
+        Some(Commands::Meta(meta_command)) => {
+            for path in &paths {
+                meta(&mut context, path, &meta_command)?;
+            }
+        }
+        Some(Commands::MergeHdr(merge_args)) => merge_hdr(&context, &paths, &merge_args)?,
+        #[cfg(feature = "stitch")]
+        Some(Commands::Stitch(stitch_args)) => stitch(&context, &paths, &stitch_args)?,
+        #[cfg(feature = "watch")]
+        Some(Commands::Watch(watch_args)) => {
+            let [path] = paths.as_slice() else {
+                return Err(Error::WatchPaths.into());
+            };
+            watch(&mut context, path, &watch_args)?;
+        }
+        #[cfg(feature = "ffmpeg")]
+        Some(Commands::Frames(frames_args)) => {
+            for path in &paths {
+                frames(&context, path, &frames_args)?;
+            }
+        }
+        #[cfg(feature = "ffmpeg")]
+        Some(Commands::Video(video_args)) => video(&context, &paths, &video_args)?,
+        Some(Commands::Stego(StegoCommands::Embed(embed_args))) => {
+            for path in &paths {
+                stego_embed(&context, path, &embed_args)?;
+            }
+        }
+        Some(Commands::Stego(StegoCommands::Extract(extract_args))) => {
+            for path in &paths {
+                stego_extract(&mut context, path, &extract_args)?;
+            }
+        }
+        None => {
+            for path in &paths {
+                info(&mut context, path, None, OutputFormat::Text, false)?;
+            }
+        }
+        Some(
+            Commands::Formats
+            | Commands::Doctor
+            | Commands::Completions { .. }
+            | Commands::Generate(_)
+            | Commands::Run { .. },
+        ) => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Reads the paths of `--files-from`, which are separated by NUL characters if there are any and
+/// otherwise by newlines. Empty paths are skipped. The paths are taken as raw bytes, so that file
+/// names that are not valid UTF-8 survive `find -print0`.
+fn read_file_list(mut reader: impl Read) -> io::Result<Vec<PathBuf>> {
+    let mut list = Vec::new();
+    reader.read_to_end(&mut list)?;
+    let separator = if list.contains(&b'\0') { b'\0' } else { b'\n' };
+    list.split(|&byte| byte == separator)
+        .map(|path| match separator {
+            b'\n' => path.strip_suffix(b"\r").unwrap_or(path),
+            _ => path,
+        })
+        .filter(|path| !path.is_empty())
+        .map(bytes_to_path)
+        .collect()
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> io::Result<PathBuf> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> io::Result<PathBuf> {
+    let path = std::str::from_utf8(bytes)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    Ok(PathBuf::from(path))
+}
+
+/// Turns `imy photo.jpg png` into a conversion to png. The last path is only taken as the target
+/// format if no such file exists and it names a known format.
+fn conversion_shorthand(
+    mut paths: Vec<PathBuf>,
+    command: Option<Commands>,
+) -> (Vec<PathBuf>, Option<Commands>) {
+    if command.is_some() || paths.len() < 2 {
+        return (paths, command);
+    }
+    let last = &paths[paths.len() - 1];
+    let Some(target_format) = last
+        .to_str()
+        .filter(|_| !last.exists())
+        .filter(|name| string_to_format(name).is_ok())
+        .map(str::to_owned)
+    else {
+        return (paths, command);
+    };
+    paths.pop();
+    tracing::debug!("Converting to {target_format} with the shorthand");
+    let convert_args = ConvertOptions {
+        target_format,
+        ..Default::default()
+    };
+    (paths, Some(Commands::Convert(convert_args)))
+}
+
+/// Replaces the URLs among the paths with the downloaded images. The outputs of converted downloads
+/// are written to the current directory unless another directory is given.
+#[cfg(feature = "http")]
+fn download_urls(
+    downloads: &download::Downloads,
+    paths: Vec<PathBuf>,
+    command: Option<Commands>,
+) -> miette::Result<(Vec<PathBuf>, Option<Commands>)> {
+    if !paths.iter().any(|path| download::is_url(path)) {
+        return Ok((paths, command));
+    }
+    let paths = paths
+        .into_iter()
+        .map(|path| {
+            if download::is_url(&path) {
+                downloads.download(&path.to_string_lossy())
+            } else {
+                Ok(path)
+            }
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+    let command = match command {
+        Some(Commands::Convert(args)) if args.out_dir.is_none() => {
+            Some(Commands::Convert(ConvertOptions {
+                out_dir: Some(PathBuf::from(".")),
+                ..args
+            }))
+        }
+        command => command,
+    };
+    Ok((paths, command))
+}
+
+/// Fills the options of the command that are not given on the command line from the
+/// configuration.
+fn with_config(config: &Config, mut command: Commands) -> Commands {
+    match &mut command {
+        Commands::Convert(args) => convert_with_config(config, args),
+        #[cfg(feature = "watch")]
+        Commands::Watch(WatchArgs {
+            command: WatchCommands::Convert(args),
+            ..
+        }) => convert_with_config(config, args),
+        Commands::Thumbnail(args) => {
+            args.quality = args.quality.or(config.quality);
+            args.out_dir = args.out_dir.take().or_else(|| config.out_dir.clone());
+        }
+        Commands::Srcset(args) => {
+            args.quality = args.quality.or(config.quality);
+            args.out_dir = args.out_dir.take().or_else(|| config.out_dir.clone());
+        }
+        Commands::Pipeline(args) => {
+            args.out_dir = args.out_dir.take().or_else(|| config.out_dir.clone());
+        }
+        _ => {}
+    }
+    command
+}
+
+fn convert_with_config(config: &Config, args: &mut ConvertOptions) {
+    args.quality = args.quality.or(config.quality);
+    args.jobs = args.jobs.or(config.jobs);
+    args.out_dir = args.out_dir.take().or_else(|| config.out_dir.clone());
+    args.output_template = args
+        .output_template
+        .take()
+        .or_else(|| config.output_template.clone());
+}
+
+const INFO_PLACEHOLDERS: &[&str] = &[
+    "path",
+    "format",
+    "width",
+    "height",
+    "color",
+    "bit_depth",
+    "size",
+];
+
+/// Information about an image that is printed by `info`.
+#[derive(Serialize)]
+struct ImageInfo {
+    path: String,
+    format: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    /// Channels of the decoded image, e.g. "rgba"
+    color: Option<String>,
+    /// Bits per channel of the decoded image
+    bit_depth: Option<u16>,
+    /// Size of the file in bytes
+    size: Option<u64>,
+    /// Layers of OpenEXR files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    layers: Vec<openexr::Layer>,
+}
+
+/// Prints the format, the dimensions, the color type, the bit depth and the size of an image, or of
+/// all images in a directory. The template selects the fields of text output.
+pub fn info(
+    context: &mut Context,
+    path: &Path,
+    template: Option<&str>,
+    output: OutputFormat,
+    print0: bool,
+) -> miette::Result<()> {
+    match to_path_type(path) {
+        Some(PathType::File) => {
+            let info = image_info(path)?;
+            match output {
+                OutputFormat::Text if print0 => print_info0(context, &info, template)?,
+                OutputFormat::Text => print_info(context, &info, template)?,
+                OutputFormat::Json => print_json(context, &info)?,
+            }
+        }
+        Some(PathType::Directory) => {
+            // The files are read in parallel, collecting keeps them in the order of the scan
+            let infos = image_files(&context.walk, path)
+                .par_iter()
+                .map(|path| image_info(path))
+                .collect::<miette::Result<Vec<_>>>()?;
+            if output == OutputFormat::Json {
+                return print_json(context, &infos);
+            }
+            if print0 {
+                for info in &infos {
+                    print_info0(context, info, template)?;
+                }
+                return Ok(());
+            }
+            let mut formats = BTreeMap::<&str, usize>::new();
+            for info in &infos {
+                print_info(context, info, template)?;
+                *formats.entry(&info.format).or_default() += 1;
+            }
+            // The summary would get in the way of scripts that parse the templated output
+            if template.is_none() {
+                let summary = formats
+                    .iter()
+                    .map(|(format, count)| format!("{count} {format}"))
+                    .collect::<Vec<_>>();
+                let line = if summary.is_empty() {
+                    format!("{} image(s)", infos.len())
+                } else {
+                    format!("{} image(s): {}", infos.len(), summary.join(", "))
+                };
+                writeln!(context.stdout, "{line}").map_err(Error::Stdout)?;
+            }
+        }
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    }
+    Ok(())
+}
+
+fn image_info(path: &Path) -> miette::Result<ImageInfo> {
+    let reader = open_image(path)?;
+    tracing::trace!("Opened file: {}", path.display());
+
+    let format = reader.format();
+    // Only the header is decoded, which is enough for the dimensions and the color type
+    let decoder = reader.into_decoder().ok();
+    let dimensions = decoder.as_ref().map(|decoder| decoder.dimensions());
+    let color_type = decoder.as_ref().map(|decoder| decoder.color_type());
+    let layers = if format == Some(ImageFormat::OpenExr) {
+        openexr::layers(path)?
+    } else {
+        Vec::new()
+    };
+    Ok(ImageInfo {
+        path: path.display().to_string(),
+        format: format.map(format_to_string).unwrap_or("unknown".to_owned()),
+        width: dimensions.map(|dimensions| dimensions.0),
+        height: dimensions.map(|dimensions| dimensions.1),
+        color: color_type.map(|color_type| color_type_to_string(color_type).to_owned()),
+        bit_depth: color_type
+            .map(|color_type| color_type.bits_per_pixel() / color_type.channel_count() as u16),
+        size: fs::metadata(path).map(|metadata| metadata.len()).ok(),
+        layers,
+    })
+}
+
+fn print_info(
+    context: &mut Context,
+    info: &ImageInfo,
+    template: Option<&str>,
+) -> miette::Result<()> {
+    let line = match template {
+        Some(template) => render_info(template, info)?,
+        None => {
+            let mut fields = vec![info.path.clone(), info.format.clone()];
+            if let (Some(width), Some(height)) = (info.width, info.height) {
+                fields.push(format!("{width}x{height}"));
+            }
+            fields.extend(info.color.clone());
+            fields.extend(info.bit_depth.map(|bits| format!("{bits}-bit")));
+            fields.extend(info.size.map(format_file_size));
+            fields.join(" ")
+        }
+    };
+    writeln!(context.stdout, "{line}").map_err(Error::Stdout)?;
+
+    if template.is_none() {
+        for layer in &info.layers {
+            let name = openexr::display_layer_name(&layer.name);
+            writeln!(context.stdout, "  {name}: {}", layer.channels.join(", "))
+                .map_err(Error::Stdout)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the templated line of the image, or only its path without a template, terminated by a
+/// NUL character.
+fn print_info0(
+    context: &mut Context,
+    info: &ImageInfo,
+    template: Option<&str>,
+) -> miette::Result<()> {
+    let record = match template {
+        Some(template) => render_info(template, info)?,
+        None => info.path.clone(),
+    };
+    write!(context.stdout, "{record}\0").map_err(Error::Stdout)?;
+    Ok(())
+}
+
+fn render_info(template: &str, info: &ImageInfo) -> miette::Result<String> {
+    let unknown = |value: Option<String>| value.unwrap_or("unknown".to_owned());
+    template::render(template, INFO_PLACEHOLDERS, |name| match name {
+        "path" => Some(info.path.clone()),
+        "format" => Some(info.format.clone()),
+        "width" => Some(unknown(info.width.map(|width| width.to_string()))),
+        "height" => Some(unknown(info.height.map(|height| height.to_string()))),
+        "color" => Some(unknown(info.color.clone())),
+        "bit_depth" => Some(unknown(info.bit_depth.map(|bits| bits.to_string()))),
+        "size" => Some(unknown(info.size.map(|size| size.to_string()))),
+        _ => None,
+    })
+}
+
+fn color_type_to_string(color_type: ColorType) -> &'static str {
+    match color_type {
+        ColorType::L8 | ColorType::L16 => "gray",
+        ColorType::La8 | ColorType::La16 => "gray-alpha",
+        ColorType::Rgb8 | ColorType::Rgb16 | ColorType::Rgb32F => "rgb",
+        ColorType::Rgba8 | ColorType::Rgba16 | ColorType::Rgba32F => "rgba",
+        _ => "unknown",
+    }
+}
+
+/// Parses a number of bytes with an optional decimal or binary unit, e.g. "200KB" or "1.5MiB".
+fn parse_file_size(value: &str) -> Result<u64, String> {
+    const UNITS: &[(&str, f64)] = &[
+        ("kib", 1024.0),
+        ("mib", 1024.0 * 1024.0),
+        ("gib", 1024.0 * 1024.0 * 1024.0),
+        ("kb", 1e3),
+        ("mb", 1e6),
+        ("gb", 1e9),
+        ("k", 1e3),
+        ("m", 1e6),
+        ("g", 1e9),
+        ("b", 1.0),
+    ];
+    let lowercase = value.trim().to_lowercase();
+    let (number, factor) = UNITS
+        .iter()
+        .find_map(|(unit, factor)| Some((lowercase.strip_suffix(unit)?, *factor)))
+        .unwrap_or((&lowercase, 1.0));
+    let number = number.trim().parse::<f64>().map_err(|e| e.to_string())?;
+    if !number.is_finite() || number <= 0.0 {
+        return Err(format!("size must be positive: {value}"));
+    }
+    Ok((number * factor) as u64)
+}
+
+/// Formats a number of bytes with a binary unit, e.g. "1.5 KiB".
+fn format_file_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
+}
+
+fn print_json(context: &mut Context, value: &impl Serialize) -> miette::Result<()> {
+    let json = serde_json::to_string(value).map_err(Error::Json)?;
+    writeln!(context.stdout, "{json}").map_err(Error::Stdout)?;
+    Ok(())
+}
+
+fn formats(context: &mut Context) -> miette::Result<()> {
+    for format in ImageFormat::all() {
+        let extensions = format.extensions_str().join(", ");
+        let decode = if format.reading_enabled() {
+            "decode"
+        } else {
+            "-"
+        };
+        let encode = if format.writing_enabled() {
+            "encode"
+        } else {
+            "-"
+        };
+        writeln!(
+            context.stdout,
+            "{:<10} {:<6} {:<6} {}",
+            format_to_string(format),
+            decode,
+            encode,
+            extensions
+        )
+        .map_err(Error::Stdout)?;
+    }
+    Ok(())
+}
+
+/// Prints the diagnostics of `doctor` followed by the formats.
+fn doctor(context: &mut Context) -> miette::Result<()> {
+    let features = [
+        ("async", cfg!(feature = "async")),
+        ("ffi", cfg!(feature = "ffi")),
+        ("ffmpeg", cfg!(feature = "ffmpeg")),
+        ("http", cfg!(feature = "http")),
+        ("python", cfg!(feature = "python")),
+        ("stitch", cfg!(feature = "stitch")),
+        ("watch", cfg!(feature = "watch")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect::<Vec<_>>();
+    let mut lines = vec![
+        format!("imy {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "features: {}",
+            if features.is_empty() {
+                "none".to_owned()
+            } else {
+                features.join(", ")
+            }
+        ),
+    ];
+    #[cfg(feature = "ffmpeg")]
+    lines.push(format!(
+        "ffmpeg: {}",
+        ffmpeg::version().unwrap_or("not found".to_owned())
+    ));
+    // The versions are taken from Cargo.lock by the build script
+    lines.push("codecs:".to_owned());
+    let codecs = env!("IMY_CODECS");
+    if codecs.is_empty() {
+        lines.push("  unknown".to_owned());
+    }
+    lines.extend(
+        codecs
+            .split(';')
+            .filter(|codec| !codec.is_empty())
+            .map(|codec| format!("  {codec}")),
+    );
+    lines.push("formats:".to_owned());
+    for line in lines {
+        writeln!(context.stdout, "{line}").map_err(Error::Stdout)?;
+    }
+    formats(context)
+}
+
+enum PathType {
+    File,
+    Directory,
+}
+
+fn to_path_type(path: &Path) -> Option<PathType> {
+    if path.is_file() {
+        Some(PathType::File)
+    } else if path.is_dir() {
+        Some(PathType::Directory)
+    } else {
+        tracing::warn!(
+            "Probably no permissions to access the path: {}",
+            path.display()
+        );
+        None
+    }
+}
+
+/// Checks the format of an image, or of all images in a directory, unless `any` is set. Returns the
+/// path that failed the check.
+pub fn is(
+    walk: &WalkOptions,
+    path: &Path,
+    format: &str,
+    any: bool,
+) -> miette::Result<Option<PathBuf>> {
+    let format = dirty_string_to_format(format)?;
+    match to_path_type(path) {
+        Some(PathType::File) => Ok((!is_image_with_type(path, format)?).then(|| path.to_owned())),
+        Some(PathType::Directory) => {
+            // The files are checked in parallel, but the first deciding file in the order of the
+            // scan wins, like in a sequential check
+            let files = image_files(walk, path);
+            let decision = files
+                .par_iter()
+                .map(|file| Ok((file, is_image_with_type(file, format)?)))
+                .find_first(|result: &miette::Result<_>| {
+                    result.as_ref().map_or(true, |&(_, is)| is == any)
+                });
+            match decision.transpose()? {
+                Some((_, true)) => Ok(None),
+                Some((file, false)) => Ok(Some(file.to_owned())),
+                None => Ok(any.then(|| path.to_owned())),
+            }
+        }
+        None => Err(Error::PathAccess {
+            path: path.to_owned(),
+        }
+        .into()),
+    }
+}
+
+/// Converts an image, or all images in a directory, to the target format of the options.
+pub fn convert(context: &mut Context, path: &Path, args: &ConvertOptions) -> miette::Result<()> {
+    convert_files(context, path, args, Selection::All)
+}
+
+/// Converts the files as one batch in the closest directory that contains all of them, which the
+/// outputs in --out-dir are relative to.
+fn convert_listed(
+    context: &mut Context,
+    files: &[&PathBuf],
+    args: &ConvertOptions,
+) -> miette::Result<()> {
+    let files = files
+        .iter()
+        .map(|file| {
+            std::path::absolute(file).map_err(|_| {
+                Error::PathAccess {
+                    path: file.to_path_buf(),
+                }
+                .into()
+            })
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+    let mut dir = files[0].parent().unwrap_or(Path::new("/")).to_owned();
+    for file in &files {
+        while !file.starts_with(&dir) {
+            let Some(parent) = dir.parent() else { break };
+            dir = parent.to_owned();
+        }
+    }
+    convert_files(context, &dir, args, Selection::Files(files))
+}
+
+/// Files of a conversion within its path.
+enum Selection {
+    /// All images in the path
+    All,
+    /// The images in the path with the canonical paths, e.g. those that changed while watching
+    #[cfg(feature = "watch")]
+    Only(BTreeSet<PathBuf>),
+    /// The files, which are inside of the path, e.g. those given with --files-from
+    Files(Vec<PathBuf>),
+}
+
+/// Converts the selected files in the path like [`convert()`].
+fn convert_files(
+    context: &mut Context,
+    path: &Path,
+    args: &ConvertOptions,
+    selection: Selection,
+) -> miette::Result<()> {
+    let target_format = dirty_string_to_format(&args.target_format)?;
+    if let Some(from_format) = &args.from_format {
+        dirty_string_to_format(from_format)?;
+    }
+    tracing::debug!("Target format: {:?}", target_format);
+    if let Some(bit_depth) = args.bit_depth {
+        if bit_depth != 8 && bit_depth != 16 {
+            return Err(Error::UnsupportedBitDepth { bit_depth }.into());
+        }
+    }
+    if args.max_size.is_some() && !matches!(target_format, ImageFormat::Jpeg | ImageFormat::Avif) {
+        return Err(Error::NoQualitySetting {
+            format: format_to_string(target_format),
+        }
+        .into());
+    }
+    if !args.ico_sizes.is_empty() && target_format != ImageFormat::Ico {
+        return Err(Error::IcoSizes {
+            format: format_to_string(target_format),
+        }
+        .into());
+    }
+    if args.verify_pixels && !is_lossless_format(target_format) {
+        return Err(Error::LossyFormat {
+            format: format_to_string(target_format),
+        }
+        .into());
+    }
+
+    let (paths, only): (_, Option<BTreeSet<PathBuf>>) = match (to_path_type(path), selection) {
+        (_, Selection::Files(files)) => (files, None),
+        (Some(PathType::File), _) => (vec![path.to_path_buf()], None),
+        #[cfg(feature = "watch")]
+        (Some(PathType::Directory), Selection::Only(only)) => {
+            (image_files(&context.walk, path), Some(only))
+        }
+        (Some(PathType::Directory), _) => (image_files(&context.walk, path), None),
+        (None, _) => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(Path::new(""))
+    };
+    let fingerprint = options_fingerprint(args);
+    let cache = args
+        .incremental
+        .then(|| Mutex::new(cache::Cache::load(dir)));
+    // Batches are journaled, which excludes single files and the conversions of `watch`
+    let journaled = path.is_dir() && only.is_none();
+    let progress = if args.resume && journaled {
+        journal::read(dir)?
+    } else {
+        None
+    };
+    if progress
+        .as_ref()
+        .is_some_and(|progress| progress.options != fingerprint)
+    {
+        return Err(Error::JournalOptions {
+            path: dir.join(journal::FILE_NAME),
+        }
+        .into());
+    }
+    // Outputs, including those recorded in the cache, and moved originals inside of the input
+    // directory must not be converted again on the next run. A file that is given explicitly is
+    // converted wherever it is, e.g. a download in the temporary directory below --out-dir
+    let excluded = [&args.out_dir, &args.move_original_to]
+        .into_iter()
+        .flatten()
+        .filter(|_| path.is_dir())
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .collect::<Vec<_>>();
+    let paths = paths.into_iter().filter(|source| {
+        if cache
+            .as_ref()
+            .is_some_and(|cache| cache.lock().unwrap().is_output(source))
+            || progress
+                .as_ref()
+                .is_some_and(|progress| progress.is_output(source))
+        {
+            return false;
+        }
+        let path = fs::canonicalize(source);
+        if only
+            .as_ref()
+            .is_some_and(|only| !path.as_ref().is_ok_and(|path| only.contains(path)))
+        {
+            return false;
+        }
+        !excluded
+            .iter()
+            .any(|dir| path.as_ref().is_ok_and(|path| path.starts_with(dir)))
+    });
+    let files = paths
+        .map(|source| {
+            let target = output_path(path, &source, target_format, args.out_dir.as_deref());
+            let target = match &args.output_template {
+                Some(template) => templated_path(template, &source, &target, target_format, args)?,
+                None => target,
+            };
+            Ok((source, target))
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+    let files = resolve_collisions(files, args.on_collision)?;
+    let is_cached = |source: &Path, target: &Path| {
+        cache.as_ref().is_some_and(|cache| {
+            !args.rewrite_all && cache.lock().unwrap().is_fresh(source, target, fingerprint)
+        })
+    };
+    let mut skipped = Vec::new();
+    let files = files
+        .into_iter()
+        .filter(|(source, target)| {
+            let reason = if args.newer_than_output && is_output_fresh(source, target) {
+                "output is up to date"
+            } else if is_cached(source, target) {
+                "output is cached"
+            } else if progress
+                .as_ref()
+                .is_some_and(|progress| progress.is_done(source, target))
+            {
+                "converted before the interruption"
+            } else if args.skip_existing && target.exists() {
+                "output exists"
+            } else {
+                return true;
+            };
+            tracing::debug!("Skipping file ({reason}): {}", source.display());
+            skipped.push((source.clone(), reason));
+            false
+        })
+        .collect::<Vec<_>>();
+
+    if context.dry_run {
+        for (source, reason) in &skipped {
+            report(context, &format!("skip {} ({reason})", source.display()))?;
+        }
+        for (source, target) in &files {
+            report(context, &format!("read {}", source.display()))?;
+            let action = if target.exists() {
+                "overwrite"
+            } else {
+                "write"
+            };
+            report(context, &format!("{action} {}", target.display()))?;
+            if source == target {
+                continue;
+            }
+            if args.delete_original {
+                report(context, &format!("delete {}", source.display()))?;
+            } else if let Some(dir) = &args.move_original_to {
+                let destination = dir.join(relative_path(path, source));
+                report(
+                    context,
+                    &format!("move {} to {}", source.display(), destination.display()),
+                )?;
+            }
+        }
+        return Ok(());
+    }
+
+    // Outputs of earlier incremental conversions are replaced without asking
+    let existing = files
+        .iter()
+        .filter(|(source, target)| {
+            target.exists()
+                && !cache
+                    .as_ref()
+                    .is_some_and(|cache| cache.lock().unwrap().is_output_of(source, target))
+        })
+        .collect::<Vec<_>>();
+    if let Some((_, target)) = existing.first() {
+        if !args.force && !context.assume_yes {
+            // Without a terminal to ask, overwriting has to be requested explicitly
+            if !context.interactive {
+                return Err(Error::OutputExists {
+                    path: target.to_owned(),
+                    count: existing.len(),
+                }
+                .into());
+            }
+            let message = format!(
+                "{} existing file(s) will be overwritten. Continue?",
+                existing.len()
+            );
+            if !confirm(context, &message)? {
+                return Err(Error::Aborted.into());
+            }
+        }
+    }
+
+    let disposed = files
+        .iter()
+        .filter(|(source, target)| source != target)
+        .count();
+    if disposed > 0 && (args.delete_original || args.move_original_to.is_some()) {
+        let action = if args.delete_original {
+            "deleted"
+        } else {
+            "moved"
+        };
+        let message = format!("{disposed} original(s) will be {action}. Continue?");
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let journal = match (journaled, &progress) {
+        (false, _) => None,
+        (true, Some(_)) => Some(journal::Journal::resume(dir)),
+        (true, None) => Some(journal::Journal::create(dir, fingerprint)),
+    };
+    // A read-only input directory can still be converted into another directory, just not resumed
+    let journal = journal
+        .and_then(|journal| {
+            journal
+                .inspect_err(|report| tracing::warn!("Conversion can't be resumed: {report}"))
+                .ok()
+        })
+        .map(Mutex::new);
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(jobs) = args.jobs {
+        pool = pool.num_threads(jobs);
+    }
+    let failures = Mutex::new(Vec::new());
+    let shared = &*context;
+    let result = pool.build().map_err(Error::ThreadPool)?.install(|| {
+        files.par_iter().try_for_each(|(source, target)| {
+            let _span = tracing::info_span!("file", path = %source.display()).entered();
+            let result =
+                convert_file(shared, source, target, target_format, args).and_then(|dimensions| {
+                    // The output replaced the original when the formats are the same
+                    let disposes = source != target
+                        && (args.delete_original || args.move_original_to.is_some());
+                    // Originals are only deleted or moved once their output is known to be good
+                    if args.verify || disposes {
+                        verify_output(target, dimensions).map_err(|reason| {
+                            Error::Verification {
+                                path: target.clone(),
+                                reason,
+                            }
+                        })?;
+                        tracing::debug!("Verified: {}", target.display());
+                    }
+                    if let Some(cache) = &cache {
+                        cache.lock().unwrap().insert(source, target, fingerprint)?;
+                    }
+                    if disposes {
+                        dispose_original(shared, path, source, args)?;
+                    }
+                    if let Some(journal) = &journal {
+                        journal.lock().unwrap().record(source, target)?;
+                    }
+                    Ok(())
+                });
+            match result {
+                Err(report) if args.keep_going || is_verification(&report) => {
+                    tracing::warn!("Failed to convert {}: {report}", source.display());
+                    failures
+                        .lock()
+                        .unwrap()
+                        .push((source.clone(), error_message(&report)));
+                    Ok(())
+                }
+                result => result,
+            }
+        })
+    });
+    // The conversions that succeeded before a failure are kept in the cache
+    if let Some(cache) = cache {
+        cache.into_inner().unwrap().save()?;
+    }
+    result?;
+    let mut failures = failures.into_inner().unwrap();
+    // The journal is kept for resuming with the failed files
+    if let Some(journal) = journal.filter(|_| failures.is_empty()) {
+        journal.into_inner().unwrap().remove()?;
+    }
+
+    failures.sort();
+    if args.keep_going {
+        report(
+            context,
+            &format!("converted: {}", files.len() - failures.len()),
+        )?;
+        report(context, &format!("skipped: {}", skipped.len()))?;
+        report(context, &format!("failed: {}", failures.len()))?;
+        for (source, message) in &failures {
+            report(context, &format!("  {}: {message}", source.display()))?;
+        }
+    }
+    if !failures.is_empty() {
+        let help = failures
+            .iter()
+            .map(|(source, message)| format!("{}: {message}", source.display()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(Error::FailedFiles {
+            count: failures.len(),
+            help,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Hash of the options that change the output of a conversion, which invalidates the cached
+/// outputs of `--incremental` when they change.
+fn options_fingerprint(args: &ConvertOptions) -> u32 {
+    let options = format!(
+        "{:?}",
+        (
+            (
+                &args.target_format,
+                &args.from_format,
+                args.bit_depth,
+                args.tonemap,
+                args.exposure,
+                &args.layer,
+                &args.channel,
+                args.input_colorspace,
+                args.output_colorspace,
+                args.color_type,
+                args.dither_depth,
+                args.quality,
+            ),
+            (
+                args.max_size,
+                &args.ico_sizes,
+                args.blur,
+                &args.sharpen,
+                &args.filter,
+                args.resize,
+                args.scale,
+                args.mode,
+                args.no_auto_orient,
+                args.keep_metadata,
+                args.convert_to_srgb,
+                args.background,
+            ),
+        )
+    );
+    crc32fast::hash(options.as_bytes())
+}
+
+/// Whether the conversion failed because its output didn't pass `--verify`, which doesn't stop the
+/// other conversions.
+fn is_verification(report: &miette::Report) -> bool {
+    matches!(report.downcast_ref(), Some(Error::Verification { .. }))
+}
+
+/// Message of the error followed by its sources, e.g. "Failed to decode file: x.png: Format error".
+fn error_message(report: &miette::Report) -> String {
+    report
+        .chain()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Decodes the output of a conversion and checks that it has the dimensions of the converted
+/// image. Returns the reason if it doesn't.
+fn verify_output(path: &Path, dimensions: (u32, u32)) -> Result<(), String> {
+    let img = decode_image(path).map_err(|report| error_message(&report))?;
+    if img.dimensions() != dimensions {
+        let (width, height) = dimensions;
+        return Err(format!(
+            "expected {width}x{height} pixels, found {}x{}",
+            img.width(),
+            img.height()
+        ));
+    }
+    Ok(())
+}
+
+/// Gives the sources that would overwrite each other's output distinct outputs by numbering them,
+/// or fails. Sources that are converted in place and otherwise the first source keep the output.
+fn resolve_collisions(
+    files: Vec<(PathBuf, PathBuf)>,
+    on_collision: Collision,
+) -> miette::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut taken = files
+        .iter()
+        .filter(|(source, target)| source == target)
+        .map(|(source, target)| (target.clone(), source.clone()))
+        .collect::<BTreeMap<_, _>>();
+    files
+        .into_iter()
+        .map(|(source, target)| {
+            if source == target {
+                return Ok((source, target));
+            }
+            let Some(first) = taken.get(&target) else {
+                taken.insert(target.clone(), source.clone());
+                return Ok((source, target));
+            };
+            if on_collision == Collision::Error {
+                return Err(Error::OutputCollision {
+                    path: target,
+                    first: first.clone(),
+                    second: source,
+                }
+                .into());
+            }
+            let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = target.extension().unwrap_or_default().to_string_lossy();
+            let mut number = 1;
+            let numbered = loop {
+                let numbered = target.with_file_name(format!("{stem}-{number}.{extension}"));
+                if !taken.contains_key(&numbered) {
+                    break numbered;
+                }
+                number += 1;
+            };
+            tracing::warn!(
+                "Writing {} to {} instead of {}, which is the output of {}",
+                source.display(),
+                numbered.display(),
+                target.display(),
+                first.display()
+            );
+            taken.insert(numbered.clone(), source.clone());
+            Ok((source, numbered))
+        })
+        .collect()
+}
+
+/// Whether the output exists and was modified after the source, like the freshness check of make.
+fn is_output_fresh(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(source), modified(output)) {
+        (Ok(source), Ok(output)) => output >= source,
+        _ => false,
+    }
+}
+
+/// Asks the user for confirmation before a destructive operation. The prompt is skipped when
+/// `--yes` was passed or stdin is not a terminal.
+fn confirm(context: &Context, message: &str) -> miette::Result<bool> {
+    if context.assume_yes || !context.interactive {
+        return Ok(true);
+    }
+    eprint!("{message} [y/N] ");
+    if let Some(answer) = context.answer {
+        eprintln!();
+        return Ok(answer);
+    }
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).map_err(Error::Stdin)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Removes a file, moving it to the trash of the operating system if `--trash` was passed.
+fn remove_file(context: &Context, path: &Path) -> miette::Result<()> {
+    if context.use_trash {
+        trash::delete(path).map_err(|source| Error::Trash {
+            path: path.to_owned(),
+            source,
+        })?;
+        tracing::debug!("Moved file to the trash: {}", path.display());
+    } else {
+        fs::remove_file(path).map_err(|source| Error::Delete {
+            path: path.to_owned(),
+            source,
+        })?;
+        tracing::debug!("Deleted file: {}", path.display());
+    }
+    Ok(())
+}
+
+/// Prints a line of the dry run.
+fn report(context: &mut Context, line: &str) -> miette::Result<()> {
+    writeln!(context.stdout, "{line}").map_err(Error::Stdout)?;
+    Ok(())
+}
+
+/// Deletes or moves the original of a conversion as requested by the arguments.
+fn dispose_original(
+    context: &Context,
+    input: &Path,
+    path: &Path,
+    args: &ConvertOptions,
+) -> miette::Result<()> {
+    if args.delete_original {
+        remove_file(context, path)?;
+    } else if let Some(dir) = &args.move_original_to {
+        let destination = dir.join(relative_path(input, path));
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+                path: parent.to_owned(),
+                source,
+            })?;
+        }
+        let map_err = |source| Error::MoveFile {
+            path: path.to_owned(),
+            destination: destination.clone(),
+            source,
+        };
+        // Renaming fails across file systems, where the file has to be copied instead
+        if fs::rename(path, &destination).is_err() {
+            fs::copy(path, &destination).map_err(map_err)?;
+            fs::remove_file(path).map_err(map_err)?;
+        }
+        tracing::debug!("Moved file to: {}", destination.display());
+    }
+    Ok(())
+}
+
+/// Resizes the image and adapts its color space, dynamic range and color type to the target
+/// format as requested by the arguments.
+fn prepare_image(
+    img: DynamicImage,
+    target_format: ImageFormat,
+    args: &ConvertOptions,
+) -> DynamicImage {
+    let img = match args.resize {
+        Some((width, height)) => {
+            tracing::debug!("Resizing to {width}x{height} with mode: {:?}", args.mode);
+            resize::resize(&img, width, height, args.mode)
+        }
+        None => img,
+    };
+    let img = match args.scale {
+        Some(scale) => {
+            tracing::debug!("Scaling by {scale}");
+            resize::scale(&img, scale)
+        }
+        None => img,
+    };
+    let img = match args.blur {
+        Some(sigma) => {
+            tracing::debug!("Blurring with sigma {sigma}");
+            filter::blur(&img, sigma)
+        }
+        None => img,
+    };
+    let img = match &args.sharpen {
+        Some(sharpen) => {
+            tracing::debug!("Sharpening with {sharpen:?}");
+            filter::sharpen(img, sharpen)
+        }
+        None => img,
+    };
+    let img = args.filter.iter().fold(img, |img, &filter| {
+        tracing::debug!("Applying filter: {filter:?}");
+        filter::apply(img, filter)
+    });
+
+    let source_color = img.color();
+    let input_colorspace = args.input_colorspace.unwrap_or(if is_hdr(&img) {
+        color::ColorSpace::Linear
+    } else {
+        color::ColorSpace::Srgb
+    });
+    let output_colorspace = args
+        .output_colorspace
+        .unwrap_or(if is_hdr_format(target_format) {
+            color::ColorSpace::Linear
+        } else {
+            color::ColorSpace::Srgb
+        });
+    tracing::debug!("Color spaces: {input_colorspace:?} -> {output_colorspace:?}");
+
+    let (img, colorspace) = if is_hdr(&img) && !is_hdr_format(target_format) {
+        tracing::debug!("Tone mapping with operator: {:?}", args.tonemap);
+        let img = color::convert_colorspace(img, input_colorspace, color::ColorSpace::Linear);
+        let img = tonemap::tonemap(&img, args.tonemap, args.exposure);
+        (img, color::ColorSpace::Linear)
+    } else {
+        (img, input_colorspace)
+    };
+    let img = color::convert_colorspace(img, colorspace, output_colorspace);
+
+    let (img, source_color) = if source_color.has_alpha() && !encodes_alpha(target_format) {
+        let background = args.background.unwrap_or(image::Rgba([255, 255, 255, 255]));
+        let (img, transparent) = color::flatten(img, background);
+        if transparent && args.background.is_none() {
+            tracing::warn!(
+                "Compositing transparent pixels over white for {}, which has no transparency. \
+                 Pass --background to choose the color",
+                format_to_string(target_format)
+            );
+        }
+        (img, color::flattened_layout(source_color, background))
+    } else {
+        (img, source_color)
+    };
+
+    let output_bits = match args.color_type {
+        Some(color_type) => color::bits_per_channel(color_type.into()),
+        None => color::target_bits_per_channel(source_color, target_format, args.bit_depth),
+    };
+    let img = if args.dither_depth && output_bits == 8 && color::bits_per_channel(img.color()) > 8 {
+        tracing::debug!("Dithering to 8 bits per channel");
+        color::dither_to_8_bit(img, source_color)
+    } else {
+        img
+    };
+
+    let img = match args.color_type {
+        Some(color_type) => color::to_color_type(img, color_type),
+        None => color::adapt_bit_depth(img, source_color, target_format, args.bit_depth),
+    };
+    tracing::debug!("Color type of the output: {:?}", img.color());
+    img
+}
+
+/// Converts an image from the input, usually stdin, and writes the result to stdout, e.g. in a
+/// shell pipeline.
+fn convert_stream(
+    context: &mut Context,
+    mut input: impl Read,
+    args: &ConvertOptions,
+) -> miette::Result<()> {
+    let target_format = dirty_string_to_format(&args.target_format)?;
+    if !args.ico_sizes.is_empty() && target_format != ImageFormat::Ico {
+        return Err(Error::IcoSizes {
+            format: format_to_string(target_format),
+        }
+        .into());
+    }
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes).map_err(Error::Stdin)?;
+    let path = Path::new("-");
+
+    let mut reader = ImageReader::new(io::Cursor::new(bytes));
+    match &args.from_format {
+        Some(format) => reader.set_format(dirty_string_to_format(format)?),
+        None => reader = reader.with_guessed_format().map_err(Error::Stdin)?,
+    }
+    let format = reader.format().ok_or(Error::StdinFormat)?;
+    tracing::debug!("Format of stdin: {:?}", format);
+    if args.layer.is_some() || args.channel.is_some() {
+        tracing::warn!("Ignoring layer and channel selection for stdin");
+    }
+    if args.keep_metadata {
+        tracing::warn!("Ignoring --keep-metadata for stdin");
+    }
+    let (img, _) = decode_source(reader, path, args)?;
+    let img = prepare_image(img, target_format, args);
+
+    let output = match args.max_size {
+        Some(max_size) => encode_to_size(&img, path, target_format, args.quality, max_size)?,
+        None => {
+            let mut output = io::Cursor::new(Vec::new());
+            if args.ico_sizes.is_empty() {
+                encode_with_quality(&img, &mut output, target_format, args.quality)
+            } else {
+                encode_ico(&img, &mut output, &args.ico_sizes)
+            }
+            .map_err(|source| Error::Save {
+                path: path.to_owned(),
+                format: format_to_string(target_format),
+                source,
+            })?;
+            output.into_inner()
+        }
+    };
+    context.data().write_all(&output).map_err(Error::Stdout)?;
+    Ok(())
+}
+
+/// Decodes the source of a conversion, rotated and flipped according to its EXIF orientation and
+/// converted to sRGB as requested by the options. Returns the color profile that describes the
+/// colors of the decoded image.
+fn decode_source<R: io::BufRead + io::Seek>(
+    reader: ImageReader<R>,
+    path: &Path,
+    args: &ConvertOptions,
+) -> miette::Result<(DynamicImage, Option<Vec<u8>>)> {
+    let decode_err = |source| Error::Decode {
+        path: path.to_owned(),
+        source,
+    };
+    let mut decoder = reader.into_decoder().map_err(decode_err)?;
+    let orientation = decoder.orientation().map_err(decode_err)?;
+    let icc = decoder.icc_profile().map_err(decode_err)?;
+    let mut img = DynamicImage::from_decoder(decoder).map_err(decode_err)?;
+    if !args.no_auto_orient {
+        img.apply_orientation(orientation);
+    }
+    match icc {
+        Some(icc) if args.convert_to_srgb => match icc::parse(&icc) {
+            Some(profile) => {
+                tracing::debug!("Converting to sRGB: {}", path.display());
+                Ok((icc::to_srgb(img, &profile), None))
+            }
+            None => {
+                tracing::warn!(
+                    "Keeping the color profile, which can't be converted to sRGB: {}",
+                    path.display()
+                );
+                Ok((img, Some(icc)))
+            }
+        },
+        icc => Ok((img, icc)),
+    }
+}
+
+/// Converts a file and returns the dimensions of the written image, which is the largest icon of
+/// an ico file with several sizes.
+fn convert_file(
+    context: &Context,
+    path: &Path,
+    target_path: &Path,
+    target_format: ImageFormat,
+    args: &ConvertOptions,
+) -> miette::Result<(u32, u32)> {
+    let mut reader = open_image(path)?;
+    tracing::trace!("Opened file: {}", path.display());
+
+    // Formats without magic bytes, e.g. TGA, are only recognized by the extension or the option
+    if let Some(from_format) = &args.from_format {
+        reader.set_format(dirty_string_to_format(from_format)?);
+    }
+    let format = reader.format().ok_or_else(|| Error::UndeterminedFormat {
+        path: path.to_owned(),
+    })?;
+    tracing::debug!("Format of the input file: {:?}", format);
+
+    let (img, icc) =
+        if format == ImageFormat::OpenExr && (args.layer.is_some() || args.channel.is_some()) {
+            let img = openexr::read_layer(path, args.layer.as_deref(), args.channel.as_deref())?;
+            (img, None)
+        } else {
+            if args.layer.is_some() || args.channel.is_some() {
+                tracing::warn!(
+                    "Ignoring layer and channel selection for: {}",
+                    path.display()
+                );
+            }
+            decode_source(reader, path, args)?
+        };
+    tracing::trace!("Decoded file: {}", path.display());
+
+    // Resizing is not allowed together with the verification
+    let source = args.verify_pixels.then(|| img.clone());
+    let geo_tags = if format == ImageFormat::Tiff {
+        geotiff::read(path)?
+    } else {
+        None
+    };
+    let img = prepare_image(img, target_format, args);
+    let dimensions = img.dimensions();
+
+    tracing::debug!("Saving file: {}", target_path.display());
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+            path: parent.to_owned(),
+            source,
+        })?;
+    }
+
+    if context.use_trash && target_path.exists() {
+        remove_file(context, target_path)?;
+    }
+
+    match &geo_tags {
+        Some(geo_tags) if target_format == ImageFormat::Tiff => {
+            geotiff::save_tiff(&img, target_path, geo_tags)?
+        }
+        _ => match args.max_size {
+            Some(max_size) => {
+                let data =
+                    encode_to_size(&img, target_path, target_format, args.quality, max_size)?;
+                atomic::write_data(target_path, &data)?
+            }
+            None if !args.ico_sizes.is_empty() => save_ico(&img, target_path, &args.ico_sizes)?,
+            None => save_with_quality(&img, target_path, target_format, args.quality)?,
+        },
+    }
+    tracing::trace!("Saved file: {}", target_path.display());
+
+    if let Some(geo_tags) = geo_tags.filter(|_| target_format != ImageFormat::Tiff) {
+        let world_file_path = geotiff::world_file_path(target_path);
+        if geotiff::write_world_file(&world_file_path, &geo_tags)? {
+            tracing::warn!(
+                "Exported the geo-referencing to {}, the projection is not preserved",
+                world_file_path.display()
+            );
+        } else {
+            tracing::warn!("Dropping the geo-referencing of: {}", path.display());
+        }
+    }
+
+    // The profile doesn't describe the output anymore if its transfer function was changed
+    let icc = icc.filter(|_| args.output_colorspace.is_none());
+    copy_metadata(path, target_path, target_format, icc, args)?;
+
+    if let Some(source) = source {
+        let output = decode_image(target_path)?;
+        if !same_pixels(&source, &output) {
+            return Err(Error::PixelMismatch {
+                path: target_path.to_owned(),
+            }
+            .into());
+        }
+        tracing::debug!("Verified pixels of: {}", target_path.display());
+    }
+
+    Ok(match args.ico_sizes.iter().max() {
+        Some(&size) if target_format == ImageFormat::Ico => (size, size),
+        _ => dimensions,
+    })
+}
+
+/// Writes the color profile and, with `--keep-metadata`, the other metadata of the source of a
+/// conversion to its output. The EXIF orientation is reset if the pixels were rotated upright.
+fn copy_metadata(
+    path: &Path,
+    target_path: &Path,
+    target_format: ImageFormat,
+    icc: Option<Vec<u8>>,
+    args: &ConvertOptions,
+) -> miette::Result<()> {
+    let mut metadata = if args.keep_metadata {
+        metadata::read(path)?
+    } else {
+        metadata::Metadata::default()
+    };
+    metadata.icc = icc;
+    if metadata == metadata::Metadata::default() {
+        return Ok(());
+    }
+    if !matches!(target_format, ImageFormat::Png | ImageFormat::Jpeg) {
+        tracing::warn!(
+            "Dropping the metadata, which can't be written to {}: {}",
+            format_to_string(target_format),
+            path.display()
+        );
+        return Ok(());
+    }
+    if let Some(exif) = metadata.exif.as_mut().filter(|_| !args.no_auto_orient) {
+        exif::set_orientation(exif, 1);
+    }
+    metadata::write(target_path, &metadata)?;
+    tracing::debug!("Copied metadata to: {}", target_path.display());
+    Ok(())
+}
+
+/// Compares the pixel values of two images independent of their channel layout, e.g. a grayscale
+/// image equals an RGB image with the same gray values.
+fn same_pixels(a: &DynamicImage, b: &DynamicImage) -> bool {
+    if a.dimensions() != b.dimensions() {
+        return false;
+    }
+    if is_hdr(a) || is_hdr(b) {
+        a.to_rgba32f() == b.to_rgba32f()
+    } else {
+        a.to_rgba16() == b.to_rgba16()
+    }
+}
+
+fn append(context: &Context, paths: &[PathBuf], args: &AppendArgs) -> miette::Result<()> {
+    let images = paths
+        .iter()
+        .map(|path| decode_image(path))
+        .collect::<miette::Result<Vec<_>>>()?;
+    let img = append::append(
+        &images,
+        args.direction,
+        args.align,
+        args.gap,
+        args.background,
+    );
+    save_image(context, &without_opaque_alpha(img), &args.output)
+}
+
+fn compare(context: &mut Context, path: &Path, args: &CompareArgs) -> miette::Result<()> {
+    if !args.other.exists() {
+        return Err(Error::PathNotFound {
+            path: args.other.to_owned(),
+        }
+        .into());
+    }
+    let single_file = path.is_file();
+    let (left, right) = if single_file {
+        let name = PathBuf::from(path.file_name().unwrap_or_default());
+        (
+            BTreeMap::from([(name.clone(), path.to_owned())]),
+            BTreeMap::from([(name, args.other.to_owned())]),
+        )
+    } else {
+        (
+            tree_files(&context.walk, path, args.recursive, args.ignore_extension),
+            tree_files(
+                &context.walk,
+                &args.other,
+                args.recursive,
+                args.ignore_extension,
+            ),
+        )
+    };
+
+    let mut summary = CompareSummary::default();
+    let names = left.keys().chain(right.keys()).collect::<BTreeSet<_>>();
+    for name in names {
+        let (path, other) = (left.get(name), right.get(name));
+        let mut compared = ComparedFile {
+            status: "different",
+            path: path.or(other).unwrap().to_owned(),
+            other: other.filter(|_| path.is_some()).cloned(),
+            difference: None,
+        };
+        let line = match (path, other) {
+            (Some(path), None) => {
+                summary.missing += 1;
+                compared.status = "missing";
+                format!("missing   {}", path.display())
+            }
+            (None, Some(other)) => {
+                summary.extra += 1;
+                compared.status = "extra";
+                format!("extra     {}", other.display())
+            }
+            (Some(path), Some(other)) => {
+                let (img, other_img) = (decode_image(path)?, decode_image(other)?);
+                let difference = compare::difference(&img, &other_img);
+                compared.difference = difference;
+                match difference {
+                    Some(difference) if difference.is_identical() => {
+                        summary.identical += 1;
+                        compared.status = "identical";
+                        tracing::debug!("Identical: {}", path.display());
+                        summary.files.push(compared);
+                        continue;
+                    }
+                    Some(difference) => {
+                        summary.different += 1;
+                        if let Some(diff) = &args.diff {
+                            let diff_path = if single_file {
+                                diff.to_owned()
+                            } else {
+                                diff.join(name).with_extension("png")
+                            };
+                            write_diff_image(context, &img, &other_img, &diff_path)?;
+                        }
+                        format!(
+                            "different {}: {} pixel(s), max difference {:.4}, PSNR {:.2} dB, \
+                             SSIM {:.4}",
+                            path.display(),
+                            difference.differing_pixels,
+                            difference.max_difference,
+                            difference.psnr,
+                            difference.ssim
+                        )
+                    }
+                    None => {
+                        summary.different += 1;
+                        format!(
+                            "different {}: size {}x{} vs {}x{}",
+                            path.display(),
+                            img.width(),
+                            img.height(),
+                            other_img.width(),
+                            other_img.height()
+                        )
+                    }
+                }
+            }
+            (None, None) => unreachable!(),
+        };
+        summary.files.push(compared);
+        if args.output == OutputFormat::Text {
+            writeln!(context.stdout, "{line}").map_err(Error::Stdout)?;
+        }
+    }
+    match args.output {
+        OutputFormat::Text => writeln!(
+            context.stdout,
+            "{} identical, {} different, {} missing, {} extra",
+            summary.identical, summary.different, summary.missing, summary.extra
+        )
+        .map_err(Error::Stdout)?,
+        OutputFormat::Json => print_json(context, &summary)?,
+    }
+
+    let count = summary.different + summary.missing + summary.extra;
+    if count > 0 {
+        return Err(Error::Differences { count }.into());
+    }
+    Ok(())
+}
+
+/// Result of the `compare` command in the JSON output.
+#[derive(Serialize, Default)]
+struct CompareSummary {
+    files: Vec<ComparedFile>,
+    identical: usize,
+    different: usize,
+    missing: usize,
+    extra: usize,
+}
+
+/// A file of the compared paths. The PSNR of identical images is infinite and serialized as null.
+#[derive(Serialize)]
+struct ComparedFile {
+    status: &'static str,
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    other: Option<PathBuf>,
+    #[serde(flatten)]
+    difference: Option<compare::Difference>,
+}
+
+/// Hash of an image that is printed by `hash`.
+#[derive(Serialize)]
+struct HashedFile {
+    path: PathBuf,
+    hash: String,
+}
+
+/// Prints the perceptual hash of an image, or of all images in a directory, which are hashed in
+/// parallel.
+fn hash(context: &mut Context, path: &Path, args: &HashArgs) -> miette::Result<()> {
+    let files = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_owned()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    let hashes = files
+        .par_iter()
+        .map(|file| {
+            let img = decode_image(file)?;
+            Ok(HashedFile {
+                path: file.to_owned(),
+                hash: hash::hash(&img, args.algorithm, args.size).to_string(),
+            })
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+    match args.output {
+        OutputFormat::Text => {
+            let terminator = if args.print0 { '\0' } else { '\n' };
+            for hashed in &hashes {
+                write!(
+                    context.stdout,
+                    "{}  {}{terminator}",
+                    hashed.hash,
+                    hashed.path.display()
+                )
+                .map_err(Error::Stdout)?;
+            }
+        }
+        OutputFormat::Json if path.is_file() => print_json(context, &hashes[0])?,
+        OutputFormat::Json => print_json(context, &hashes)?,
+    }
+    Ok(())
+}
+
+/// Group of duplicates that is printed by `dedupe`.
+#[derive(Serialize)]
+struct DuplicateGroup {
+    /// "exact" if all files have identical contents, otherwise "near"
+    kind: &'static str,
+    keep: PathBuf,
+    duplicates: Vec<PathBuf>,
+}
+
+/// Prints groups of identical and similar images, and deletes all but one file of each group if
+/// requested. Files with identical contents are only decoded once.
+fn dedupe(context: &mut Context, path: &Path, args: &DedupeArgs) -> miette::Result<()> {
+    let files = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_owned()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    let identical = identical_files(&files)?;
+    let (clusters, pixels) = if args.exact {
+        let clusters = (0..identical.len()).map(|i| vec![i]).collect::<Vec<_>>();
+        (clusters, vec![None; identical.len()])
+    } else {
+        let decoded = identical
+            .par_iter()
+            .map(|files| {
+                let img = decode_image(&files[0])?;
+                let hash = hash::hash(&img, args.algorithm, 8);
+                Ok((hash, Some(img.width() as u64 * img.height() as u64)))
+            })
+            .collect::<miette::Result<Vec<_>>>()?;
+        let (hashes, pixels): (Vec<_>, Vec<_>) = decoded.into_iter().unzip();
+        (dedupe::group(&hashes, args.threshold), pixels)
+    };
+
+    let mut groups = Vec::new();
+    for cluster in clusters {
+        let mut members = cluster
+            .iter()
+            .flat_map(|&i| identical[i].iter().map(move |file| (file, i)))
+            .map(|(file, i)| (file, pixels[i], fs::metadata(file).ok()))
+            .collect::<Vec<_>>();
+        if members.len() < 2 {
+            continue;
+        }
+        match args.keep {
+            Keep::Largest => members.sort_by_key(|(_, pixels, metadata)| {
+                let size = metadata.as_ref().map(|metadata| metadata.len());
+                std::cmp::Reverse((*pixels, size))
+            }),
+            Keep::Oldest => members.sort_by_key(|(_, _, metadata)| {
+                let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+                (modified.is_none(), modified)
+            }),
+        }
+        let mut members = members.into_iter().map(|(file, _, _)| file.to_owned());
+        groups.push(DuplicateGroup {
+            kind: if cluster.len() == 1 { "exact" } else { "near" },
+            keep: members.next().unwrap(),
+            duplicates: members.collect(),
+        });
+    }
+
+    let duplicates = groups.iter().flat_map(|group| &group.duplicates);
+    let count = duplicates.clone().count();
+    match args.output {
+        OutputFormat::Text if args.print0 => {
+            for duplicate in duplicates.clone() {
+                write!(context.stdout, "{}\0", duplicate.display()).map_err(Error::Stdout)?;
+            }
+        }
+        OutputFormat::Text => {
+            for group in &groups {
+                writeln!(context.stdout, "{} {}", group.kind, group.keep.display())
+                    .map_err(Error::Stdout)?;
+                for duplicate in &group.duplicates {
+                    writeln!(context.stdout, "  {}", duplicate.display()).map_err(Error::Stdout)?;
+                }
+            }
+            writeln!(
+                context.stdout,
+                "{} group(s) with {count} duplicate(s)",
+                groups.len()
+            )
+            .map_err(Error::Stdout)?;
+        }
+        OutputFormat::Json => print_json(context, &groups)?,
+    }
+
+    if !args.delete_duplicates || count == 0 {
+        return Ok(());
+    }
+    if context.dry_run {
+        for duplicate in duplicates {
+            report(context, &format!("delete {}", duplicate.display()))?;
+        }
+        return Ok(());
+    }
+    let message = format!("{count} duplicate(s) will be deleted. Continue?");
+    if !confirm(context, &message)? {
+        return Err(Error::Aborted.into());
+    }
+    for duplicate in duplicates {
+        remove_file(context, duplicate)?;
+    }
+    Ok(())
+}
+
+/// Groups the files with identical contents, keeping the order of the files. Only files that have
+/// the same size as another file are read.
+fn identical_files(files: &[PathBuf]) -> miette::Result<Vec<Vec<PathBuf>>> {
+    let mut sizes = BTreeMap::<u64, Vec<&PathBuf>>::new();
+    for file in files {
+        let metadata = fs::metadata(file).map_err(|source| Error::ReadFile {
+            path: file.to_owned(),
+            source,
+        })?;
+        sizes.entry(metadata.len()).or_default().push(file);
+    }
+    let candidates = sizes
+        .values()
+        .filter(|files| files.len() > 1)
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    let read = |file: &PathBuf| {
+        fs::read(file).map_err(|source| Error::ReadFile {
+            path: file.to_owned(),
+            source,
+        })
+    };
+    let checksums = candidates
+        .par_iter()
+        .map(|&file| Ok((file, crc32fast::hash(&read(file)?))))
+        .collect::<miette::Result<BTreeMap<_, _>>>()?;
+
+    let mut groups = Vec::<Vec<PathBuf>>::new();
+    let mut group_of_checksum = BTreeMap::<(u64, u32), usize>::new();
+    for file in files {
+        let Some(&checksum) = checksums.get(file) else {
+            groups.push(vec![file.to_owned()]);
+            continue;
+        };
+        let size = fs::metadata(file).map(|m| m.len()).unwrap_or_default();
+        match group_of_checksum.get(&(size, checksum)) {
+            // The checksum only makes a difference likely, so the contents are compared as well
+            Some(&group) if read(&groups[group][0])? == read(file)? => {
+                groups[group].push(file.to_owned())
+            }
+            Some(_) => groups.push(vec![file.to_owned()]),
+            None => {
+                group_of_checksum.insert((size, checksum), groups.len());
+                groups.push(vec![file.to_owned()]);
+            }
+        }
+    }
+    Ok(groups)
+}
+
+fn write_diff_image(
+    context: &Context,
+    img: &DynamicImage,
+    other: &DynamicImage,
+    path: &Path,
+) -> miette::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+            path: parent.to_owned(),
+            source,
+        })?;
+    }
+    let diff = compare::diff_image(img, other);
+    save_image(context, &DynamicImage::ImageRgba8(diff), path)?;
+    tracing::debug!("Wrote diff image: {}", path.display());
+    Ok(())
+}
+
+/// Image files of a directory keyed by their path relative to it.
+fn tree_files(
+    walk: &WalkOptions,
+    path: &Path,
+    recursive: bool,
+    ignore_extension: bool,
+) -> BTreeMap<PathBuf, PathBuf> {
+    let files = if recursive {
+        image_files(walk, path)
+    } else {
+        image_files(walk, path)
+            .into_iter()
+            .filter(|file| file.parent() == Some(path))
+            .collect()
+    };
+    files
+        .into_iter()
+        .map(|file| {
+            let relative = file.strip_prefix(path).unwrap_or(&file).to_path_buf();
+            let key = if ignore_extension {
+                relative.with_extension("")
+            } else {
+                relative
+            };
+            (key, file)
+        })
+        .collect()
+}
+
+/// Writes a downscaled copy of each image, turned upright according to its EXIF orientation.
+/// Thumbnails that are newer than their image are kept.
+fn thumbnail(context: &mut Context, path: &Path, args: &ThumbnailArgs) -> miette::Result<()> {
+    let target_format = args
+        .target_format
+        .as_deref()
+        .map(dirty_string_to_format)
+        .transpose()?;
+
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    // Thumbnails of a previous run must not get thumbnails themselves
+    let out_dir = args
+        .out_dir
+        .as_ref()
+        .and_then(|dir| fs::canonicalize(dir).ok());
+    let paths = paths
+        .into_iter()
+        .filter(|path| match (&args.out_dir, &out_dir) {
+            (Some(_), Some(out_dir)) => {
+                !fs::canonicalize(path).is_ok_and(|path| path.starts_with(out_dir))
+            }
+            (Some(_), None) => true,
+            (None, _) => !path
+                .file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().ends_with(&args.suffix)),
+        });
+    let files = paths
+        .map(|source| {
+            let format = target_format.unwrap_or_else(|| {
+                ImageFormat::from_path(&source)
+                    .ok()
+                    .filter(|format| format.writing_enabled())
+                    .unwrap_or(ImageFormat::Png)
+            });
+            let target = thumbnail_path(path, &source, format, args);
+            (source, target, format)
+        })
+        .collect::<Vec<_>>();
+    let (fresh, files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|(source, target, _)| is_output_fresh(source, target));
+
+    if context.dry_run {
+        for (source, _, _) in &fresh {
+            report(
+                context,
+                &format!("skip {} (thumbnail is up to date)", source.display()),
+            )?;
+        }
+        for (source, target, _) in &files {
+            let action = if target.exists() {
+                "overwrite"
+            } else {
+                "write"
+            };
+            report(context, &format!("read {}", source.display()))?;
+            report(context, &format!("{action} {}", target.display()))?;
+        }
+        return Ok(());
+    }
+    for (source, _, _) in &fresh {
+        tracing::debug!(
+            "Skipping file (thumbnail is up to date): {}",
+            source.display()
+        );
+    }
+    // Outdated thumbnails are replaced like any other file, since --out-dir keeps the file names
+    let overwritten = files
+        .iter()
+        .filter(|(_, target, _)| target.exists())
+        .count();
+    if overwritten > 0 {
+        let message = format!("{overwritten} existing file(s) will be overwritten. Continue?");
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let context = &*context;
+    files.par_iter().try_for_each(|(source, target, format)| {
+        let _span = tracing::info_span!("file", path = %source.display()).entered();
+        let (img, _) = decode_source(open_image(source)?, source, &ConvertOptions::default())?;
+        let options = ConvertOptions {
+            // Only shrink, since enlarged thumbnails would just be blurry
+            resize: (img.width() > args.size || img.height() > args.size)
+                .then_some((args.size, args.size)),
+            ..Default::default()
+        };
+        let img = prepare_image(img, *format, &options);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+                path: parent.to_owned(),
+                source,
+            })?;
+        }
+        if context.use_trash && target.exists() {
+            remove_file(context, target)?;
+        }
+        save_with_quality(&img, target, *format, args.quality)?;
+        tracing::debug!("Saved thumbnail: {}", target.display());
+        Ok::<_, miette::Report>(())
+    })
+}
+
+/// Path of the thumbnail of an image, either in the output directory or next to the image with
+/// the suffix appended to its name.
+fn thumbnail_path(input: &Path, path: &Path, format: ImageFormat, args: &ThumbnailArgs) -> PathBuf {
+    let extension = output_extension(path, format);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match &args.out_dir {
+        Some(out_dir) => out_dir
+            .join(relative_path(input, path))
+            .with_file_name(format!("{stem}.{extension}")),
+        None => path.with_file_name(format!("{stem}{}.{extension}", args.suffix)),
+    }
+}
+
+/// Extension of an output in the format. The extension of the image is kept if the format is the
+/// same, e.g. "jpg" instead of "jpeg".
+fn output_extension(path: &Path, format: ImageFormat) -> String {
+    match (ImageFormat::from_path(path), path.extension()) {
+        (Ok(source_format), Some(extension)) if source_format == format => {
+            extension.to_string_lossy().into_owned()
+        }
+        _ => format_to_string(format),
+    }
+}
+
+/// Encodes of an image at several widths that are printed by `srcset`.
+#[derive(Serialize)]
+struct Srcset {
+    source: PathBuf,
+    images: Vec<SrcsetImage>,
+    /// Value of the srcset attribute, which refers to the encodes by file name
+    srcset: String,
+}
+
+#[derive(Serialize)]
+struct SrcsetImage {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+}
+
+/// Writes a resized encode of each image for each width, named like "hero-640w.webp", and prints
+/// the srcset attribute that lists them.
+fn srcset(context: &mut Context, path: &Path, args: &SrcsetArgs) -> miette::Result<()> {
+    let target_format = args
+        .target_format
+        .as_deref()
+        .map(dirty_string_to_format)
+        .transpose()?;
+    let files = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_owned()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    // Encodes of a previous run must not get encodes themselves
+    let out_dir = args
+        .out_dir
+        .as_ref()
+        .and_then(|dir| fs::canonicalize(dir).ok());
+    let files = files
+        .into_iter()
+        .filter(|file| {
+            let in_out_dir = out_dir.as_ref().is_some_and(|out_dir| {
+                fs::canonicalize(file).is_ok_and(|file| file.starts_with(out_dir))
+            });
+            !in_out_dir && !is_srcset_output(file)
+        })
+        .collect::<Vec<_>>();
+
+    // The widths are planned from the headers, so that overwrites are confirmed before decoding
+    let sets = files
+        .par_iter()
+        .map(|source| {
+            let format = target_format.unwrap_or_else(|| {
+                ImageFormat::from_path(source)
+                    .ok()
+                    .filter(|format| format.writing_enabled())
+                    .unwrap_or(ImageFormat::Png)
+            });
+            // Decoding reports why the header of a broken file can't be read
+            let (img_width, img_height) =
+                match output_dimensions(source, &ConvertOptions::default()) {
+                    Some(dimensions) => dimensions,
+                    None => decode_image(source)?.dimensions(),
+                };
+            let skipped = args.widths.iter().filter(|&&width| width > img_width);
+            for width in skipped {
+                tracing::warn!(
+                    "Skipping width {width}, which is larger than the image: {}",
+                    source.display()
+                );
+            }
+
+            let base = match &args.out_dir {
+                Some(out_dir) => out_dir.join(relative_path(path, source)),
+                None => source.to_owned(),
+            };
+            let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = output_extension(source, format);
+            let images = args
+                .widths
+                .iter()
+                .filter(|&&width| width <= img_width)
+                .map(|&width| {
+                    let height = (img_height as f64 * width as f64 / img_width as f64).round();
+                    SrcsetImage {
+                        path: base.with_file_name(format!("{stem}-{width}w.{extension}")),
+                        width,
+                        height: (height as u32).max(1),
+                    }
+                })
+                .collect::<Vec<_>>();
+            let srcset = images
+                .iter()
+                .map(|image| {
+                    let name = image.path.file_name().unwrap_or_default();
+                    format!("{} {}w", name.to_string_lossy(), image.width)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            Ok((
+                Srcset {
+                    source: source.to_owned(),
+                    images,
+                    srcset,
+                },
+                format,
+            ))
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    if context.dry_run {
+        for (set, _) in &sets {
+            report(context, &format!("read {}", set.source.display()))?;
+            for image in &set.images {
+                let action = if image.path.exists() {
+                    "overwrite"
+                } else {
+                    "write"
+                };
+                report(context, &format!("{action} {}", image.path.display()))?;
+            }
+        }
+        return Ok(());
+    }
+    let overwritten = sets
+        .iter()
+        .flat_map(|(set, _)| &set.images)
+        .filter(|image| image.path.exists())
+        .count();
+    if overwritten > 0 {
+        let message = format!("{overwritten} existing file(s) will be overwritten. Continue?");
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let shared = &*context;
+    sets.par_iter().try_for_each(|(set, format)| {
+        let _span = tracing::info_span!("file", path = %set.source.display()).entered();
+        let source = &set.source;
+        let (img, _) = decode_source(open_image(source)?, source, &ConvertOptions::default())?;
+        for image in &set.images {
+            let options = ConvertOptions {
+                resize: Some((image.width, image.height)),
+                mode: resize::Mode::Stretch,
+                ..Default::default()
+            };
+            let img = prepare_image(img.clone(), *format, &options);
+            if let Some(parent) = image.path.parent() {
+                fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+                    path: parent.to_owned(),
+                    source,
+                })?;
+            }
+            if shared.use_trash && image.path.exists() {
+                remove_file(shared, &image.path)?;
+            }
+            save_with_quality(&img, &image.path, *format, args.quality)?;
+            tracing::debug!("Saved file: {}", image.path.display());
+        }
+        Ok::<_, miette::Report>(())
+    })?;
+
+    let sets = sets.into_iter().map(|(set, _)| set).collect::<Vec<_>>();
+    match args.output {
+        OutputFormat::Text if path.is_file() => {
+            writeln!(context.stdout, "srcset=\"{}\"", sets[0].srcset).map_err(Error::Stdout)?;
+        }
+        OutputFormat::Text => {
+            for set in &sets {
+                writeln!(
+                    context.stdout,
+                    "srcset=\"{}\"  {}",
+                    set.srcset,
+                    set.source.display()
+                )
+                .map_err(Error::Stdout)?;
+            }
+        }
+        OutputFormat::Json if path.is_file() => print_json(context, &sets[0])?,
+        OutputFormat::Json => print_json(context, &sets)?,
+    }
+    Ok(())
+}
+
+/// Whether the file is named like an encode of `srcset`, e.g. "hero-640w.webp".
+fn is_srcset_output(path: &Path) -> bool {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    stem.rsplit_once('-').is_some_and(|(_, width)| {
+        width
+            .strip_suffix('w')
+            .is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+/// Writes favicon.ico and the PNG icons of a website, optionally with a web manifest, and prints
+/// the HTML tags that refer to them.
+fn favicon(context: &mut Context, path: &Path, args: &FaviconArgs) -> miette::Result<()> {
+    let icons = icons::favicon();
+    let ico_path = args.out_dir.join("favicon.ico");
+    let manifest_path = args.out_dir.join("site.webmanifest");
+    let targets = std::iter::once(ico_path.clone())
+        .chain(icons.iter().map(|icon| args.out_dir.join(&icon.path)))
+        .chain(args.manifest.then(|| manifest_path.clone()))
+        .collect::<Vec<_>>();
+
+    if context.dry_run {
+        report(context, &format!("read {}", path.display()))?;
+        for target in &targets {
+            let action = if target.exists() {
+                "overwrite"
+            } else {
+                "write"
+            };
+            report(context, &format!("{action} {}", target.display()))?;
+        }
+        return Ok(());
+    }
+    let overwritten = targets.iter().filter(|target| target.exists()).count();
+    if overwritten > 0 {
+        let message = format!("{overwritten} existing file(s) will be overwritten. Continue?");
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let (img, _) = decode_source(open_image(path)?, path, &ConvertOptions::default())?;
+    let largest = icons.iter().map(|icon| icon.size).max().unwrap_or_default();
+    if img.width().max(img.height()) < largest {
+        tracing::warn!(
+            "Enlarging the image to {largest}x{largest}, which makes the largest icons blurry: {}",
+            path.display()
+        );
+    }
+    fs::create_dir_all(&args.out_dir).map_err(|source| Error::CreateDir {
+        path: args.out_dir.to_owned(),
+        source,
+    })?;
+    if context.use_trash && ico_path.exists() {
+        remove_file(context, &ico_path)?;
+    }
+    save_ico(&img, &ico_path, &icons::FAVICON_ICO_SIZES)?;
+    save_icons(context, &img, &icons, &args.out_dir, args.background)?;
+    if args.manifest {
+        let manifest = icons::WebManifest::new(args.name.clone(), &icons);
+        let json = serde_json::to_string_pretty(&manifest).map_err(Error::Json)?;
+        atomic::write_data(&manifest_path, (json + "\n").as_bytes())?;
+    }
+    write!(context.stdout, "{}", icons::favicon_html(args.manifest)).map_err(Error::Stdout)?;
+    Ok(())
+}
+
+/// Writes the app icons of the platforms, or of the spec file, with the directory structure that
+/// the platforms expect.
+fn app_icons(context: &mut Context, path: &Path, args: &IconsArgs) -> miette::Result<()> {
+    let icons = match &args.spec {
+        Some(spec) => {
+            let data = fs::read(spec).map_err(|source| Error::ReadFile {
+                path: spec.to_owned(),
+                source,
+            })?;
+            serde_json::from_slice::<Vec<icons::Icon>>(&data).map_err(|source| Error::IconSpec {
+                path: spec.to_owned(),
+                source,
+            })?
+        }
+        None => icons::platform(args.platform),
+    };
+    let contents_path = (args.spec.is_none() && args.platform != icons::Platform::Android)
+        .then(|| args.out_dir.join(icons::IOS_DIR).join("Contents.json"));
+    let targets = icons
+        .iter()
+        .map(|icon| args.out_dir.join(&icon.path))
+        .chain(contents_path.clone())
+        .collect::<Vec<_>>();
+
+    if context.dry_run {
+        report(context, &format!("read {}", path.display()))?;
+        for target in &targets {
+            let action = if target.exists() {
+                "overwrite"
+            } else {
+                "write"
+            };
+            report(context, &format!("{action} {}", target.display()))?;
+        }
+        return Ok(());
+    }
+    let overwritten = targets.iter().filter(|target| target.exists()).count();
+    if overwritten > 0 {
+        let message = format!("{overwritten} existing file(s) will be overwritten. Continue?");
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let (img, _) = decode_source(open_image(path)?, path, &ConvertOptions::default())?;
+    if img.width() != img.height() {
+        tracing::warn!(
+            "Centering the image, which is not square, in the icons: {}",
+            path.display()
+        );
+    }
+    let largest = icons.iter().map(|icon| icon.size).max().unwrap_or_default();
+    if img.width().max(img.height()) < largest {
+        tracing::warn!(
+            "Enlarging the image to {largest}x{largest}, which makes the largest icons blurry: {}",
+            path.display()
+        );
+    }
+    save_icons(context, &img, &icons, &args.out_dir, args.background)?;
+    if let Some(contents_path) = contents_path {
+        let json = serde_json::to_string_pretty(&icons::ios_contents()).map_err(Error::Json)?;
+        atomic::write_data(&contents_path, (json + "\n").as_bytes())?;
+    }
+    writeln!(
+        context.stdout,
+        "Wrote {} icon(s) to {}",
+        icons.len(),
+        args.out_dir.display()
+    )
+    .map_err(Error::Stdout)?;
+    Ok(())
+}
+
+/// Renders and saves the icons into the output directory, creating their directories.
+fn save_icons(
+    context: &Context,
+    img: &DynamicImage,
+    icons: &[icons::Icon],
+    out_dir: &Path,
+    background: Option<image::Rgba<u8>>,
+) -> miette::Result<()> {
+    let background = background.unwrap_or(image::Rgba([255, 255, 255, 255]));
+    icons.par_iter().try_for_each(|icon| {
+        let path = out_dir.join(&icon.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+                path: parent.to_owned(),
+                source,
+            })?;
+        }
+        save_image(context, &icons::render(img, icon, background), &path)
+    })
+}
+
+fn mask(context: &mut Context, path: &Path, args: &MaskArgs) -> miette::Result<()> {
+    let target_format = dirty_string_to_format(&args.target_format)?;
+    if !supports_alpha(target_format) {
+        return Err(Error::NoAlphaSupport {
+            format: format_to_string(target_format),
+        }
+        .into());
+    }
+    let shape = match args.rounded {
+        Some(radius) => mask::Shape::Rounded(radius),
+        None => mask::Shape::Circle,
+    };
+
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    if context.dry_run {
+        for path in &paths {
+            let target_path = target_path(path, target_format);
+            let action = if target_path.exists() {
+                "overwrite"
+            } else {
+                "write"
+            };
+            report(context, &format!("read {}", path.display()))?;
+            report(context, &format!("{action} {}", target_path.display()))?;
+        }
+        return Ok(());
+    }
+    let overwritten = paths
+        .iter()
+        .filter(|path| target_path(path, target_format).exists())
+        .count();
+    if overwritten > 0 {
+        let message = format!("{overwritten} existing file(s) will be overwritten. Continue?");
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    for path in &paths {
+        let img = mask::apply(&decode_image(path)?, shape);
+        save_image(
+            context,
+            &DynamicImage::ImageRgba8(img),
+            &target_path(path, target_format),
+        )?;
+    }
+    Ok(())
+}
+
+const CAPTION_PLACEHOLDERS: &[&str] = &["name", "stem", "width", "height", "date"];
+
+/// Draws the caption onto the images with the built-in font and overwrites them.
+fn caption(context: &mut Context, path: &Path, args: &CaptionArgs) -> miette::Result<()> {
+    // Unknown placeholders are reported before any file is changed
+    template::render(&args.text, CAPTION_PLACEHOLDERS, |name| {
+        CAPTION_PLACEHOLDERS.contains(&name).then(String::new)
+    })?;
+    let scale = ((args.size as f32 / 7.0).round() as u32).max(1);
+    transform(context, path, |file, img| {
+        let text = template::render(&args.text, CAPTION_PLACEHOLDERS, |name| match name {
+            "name" => Some(file.file_name()?.to_string_lossy().into_owned()),
+            "stem" => Some(file.file_stem()?.to_string_lossy().into_owned()),
+            "width" => Some(img.width().to_string()),
+            "height" => Some(img.height().to_string()),
+            "date" => Some(capture_date(file).unwrap_or("unknown".to_owned())),
+            _ => None,
+        })?;
+        let label = font::render_label(&text, scale, args.color, args.outline);
+        let position = overlay::position(
+            args.gravity,
+            img.dimensions(),
+            label.dimensions(),
+            args.margin,
+        );
+        Ok(overlay::composite(
+            img,
+            &DynamicImage::ImageRgba8(label),
+            position,
+            1.0,
+            overlay::BlendMode::Normal,
+        ))
+    })
+}
+
+/// Date on which a photo was taken according to its EXIF data, e.g. "2024-05-01".
+fn capture_date(path: &Path) -> Option<String> {
+    let exif = read_exif(path).ok()??;
+    let date_time = exif.date_time_original.or(exif.date_time)?;
+    Some(date_time.get(..10)?.replace(':', "-"))
+}
+
+/// Runs the jobs of the job file in order, each as a pipeline over its source.
+fn run_jobs(context: &mut Context, job_file: &Path) -> miette::Result<()> {
+    let jobs = jobs::read(job_file)?.jobs;
+    let walk = context.walk.clone();
+    for job in &jobs {
+        if !job.source.exists() {
+            return Err(Error::PathNotFound {
+                path: job.source.to_owned(),
+            }
+            .into());
+        }
+        context.walk = WalkOptions {
+            include: [&walk.include[..], &job.include[..]].concat(),
+            exclude: [&walk.exclude[..], &job.exclude[..]].concat(),
+            ..walk.clone()
+        };
+        let args = PipelineArgs {
+            stages: job.pipeline_stages(),
+            out_dir: job.out_dir.clone(),
+        };
+        pipeline(context, &job.source, &args)?;
+        let name = job.name.as_deref().unwrap_or_default();
+        if !context.dry_run {
+            writeln!(context.stdout, "Finished {name}").map_err(Error::Stdout)?;
+        }
+    }
+    context.walk = walk;
+    Ok(())
+}
+
+/// Converts the images in the directory as they are created or modified, until the watcher fails.
+#[cfg(feature = "watch")]
+fn watch(context: &mut Context, path: &Path, args: &WatchArgs) -> miette::Result<()> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    if !matches!(to_path_type(path), Some(PathType::Directory)) {
+        return Err(Error::WatchPaths.into());
+    }
+    let watch_err = |source| Error::Watch {
+        path: path.to_owned(),
+        source,
+    };
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths {
+                    let _ = sender.send(path);
+                }
+            }
+            Ok(_) => {}
+            Err(error) => tracing::warn!("Failed to receive file events: {error}"),
+        })
+        .map_err(watch_err)?;
+    watcher
+        .watch(path, RecursiveMode::Recursive)
+        .map_err(watch_err)?;
+    report(context, &format!("Watching {}", path.display()))?;
+    watch_events(context, path, args, &receiver)
+}
+
+/// Converts the changed files that are received until the sender is dropped. Failed conversions
+/// are reported without ending the watch.
+#[cfg(feature = "watch")]
+fn watch_events(
+    context: &mut Context,
+    path: &Path,
+    args: &WatchArgs,
+    receiver: &mpsc::Receiver<PathBuf>,
+) -> miette::Result<()> {
+    let WatchCommands::Convert(convert_args) = &args.command;
+    // The outputs of modified sources are replaced without asking, other confirmations like
+    // deleting the originals are still asked
+    let convert_args = &ConvertOptions {
+        force: true,
+        ..convert_args.clone()
+    };
+    let target_format = dirty_string_to_format(&convert_args.target_format)?;
+    let debounce = Duration::from_millis(args.debounce);
+    while let Ok(changed) = receiver.recv() {
+        let mut changed = BTreeSet::from([changed]);
+        while let Ok(changed_path) = receiver.recv_timeout(debounce) {
+            changed.insert(changed_path);
+        }
+        // The outputs trigger events themselves, but must not be converted again. Files that
+        // don't exist anymore, e.g. temporary files, are dropped by the canonicalization
+        let changed = changed
+            .into_iter()
+            .filter(|file| ImageFormat::from_path(file).ok() != Some(target_format))
+            .filter_map(|file| fs::canonicalize(file).ok())
+            .collect::<BTreeSet<_>>();
+        if changed.is_empty() {
+            continue;
+        }
+        tracing::debug!("Converting {} changed file(s)", changed.len());
+        if let Err(report) = convert_files(context, path, convert_args, Selection::Only(changed)) {
+            tracing::error!("Failed to convert: {}", error_message(&report));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the stages of a pipeline. Only the last stage can be a conversion.
+fn parse_stages(texts: &[String]) -> miette::Result<Vec<Stage>> {
+    let stages = texts
+        .iter()
+        .map(|stage| {
+            let invalid = |help: String| Error::InvalidStage {
+                stage: stage.to_owned(),
+                help,
+            };
+            let parser =
+                StageParser::try_parse_from(stage.split_whitespace()).map_err(|error| {
+                    let message = error.kind().as_str().unwrap_or("invalid arguments");
+                    invalid(format!(
+                        "{message}. Run `imy pipeline --help` for the stages"
+                    ))
+                })?;
+            match parser.stage {
+                Stage::Resize {
+                    size: (None, _) | (_, None),
+                    mode,
+                } if mode != resize::Mode::Fit => Err(invalid(format!(
+                    "Both dimensions are required for the resize mode: {mode:?}"
+                ))),
+                stage => Ok(stage),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(index) = stages[..stages.len().saturating_sub(1)]
+        .iter()
+        .position(|stage| matches!(stage, Stage::Convert { .. }))
+    {
+        return Err(Error::InvalidStage {
+            stage: texts[index].to_owned(),
+            help: "convert has to be the last stage".to_owned(),
+        }
+        .into());
+    }
+    Ok(stages)
+}
+
+/// Applies an operation of a pipeline to the image.
+fn apply_stage(img: DynamicImage, stage: &Stage) -> DynamicImage {
+    match stage {
+        Stage::Resize {
+            size: (width, height),
+            mode,
+        } => {
+            let (width, height) = (width.unwrap_or(u32::MAX), height.unwrap_or(u32::MAX));
+            resize::resize(&img, width, height, *mode)
+        }
+        Stage::Scale { factor } => resize::scale(&img, *factor),
+        Stage::Rotate(RotateArgs { degrees }) => match degrees {
+            Rotation::Rotate90 => img.rotate90(),
+            Rotation::Rotate180 => img.rotate180(),
+            Rotation::Rotate270 => img.rotate270(),
+        },
+        Stage::Flip(FlipArgs {
+            horizontal,
+            vertical,
+        }) => {
+            let img = if *horizontal { img.fliph() } else { img };
+            if *vertical {
+                img.flipv()
+            } else {
+                img
+            }
+        }
+        Stage::Blur { sigma } => filter::blur(&img, *sigma),
+        Stage::Sharpen { sharpen } => filter::sharpen(img, sharpen),
+        Stage::Adjust(args) => filter::adjust(
+            img,
+            &filter::Adjustment {
+                brightness: args.brightness.unwrap_or(0.0),
+                contrast: args.contrast.unwrap_or(0.0),
+                gamma: args.gamma.unwrap_or(1.0),
+            },
+        ),
+        Stage::Filter { filters } => filters
+            .iter()
+            .fold(img, |img, &filter| filter::apply(img, filter)),
+        Stage::Convert { .. } => img,
+    }
+}
+
+/// Decodes each image once, applies the stages in order and encodes the result, either in the
+/// format of the final convert stage or by overwriting the image.
+fn pipeline(context: &mut Context, path: &Path, args: &PipelineArgs) -> miette::Result<()> {
+    let stages = parse_stages(&args.stages)?;
+    let (target_format, quality) = match stages.last() {
+        Some(Stage::Convert {
+            target_format,
+            quality,
+        }) => (Some(dirty_string_to_format(target_format)?), *quality),
+        _ => (None, None),
+    };
+
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    // Outputs inside of the input directory must not be processed again on the next run
+    let out_dir = args
+        .out_dir
+        .as_ref()
+        .and_then(|dir| fs::canonicalize(dir).ok());
+    let files = paths
+        .into_iter()
+        .filter(|source| {
+            !out_dir.as_ref().is_some_and(|out_dir| {
+                fs::canonicalize(source).is_ok_and(|source| source.starts_with(out_dir))
+            })
+        })
+        .map(|source| {
+            let format = match target_format {
+                Some(format) => format,
+                None => ImageFormat::from_path(&source).map_err(|_| Error::UndeterminedFormat {
+                    path: source.clone(),
+                })?,
+            };
+            let target = match (&args.out_dir, target_format) {
+                (Some(out_dir), None) => out_dir.join(relative_path(path, &source)),
+                (out_dir, Some(format)) => output_path(path, &source, format, out_dir.as_deref()),
+                (None, None) => source.clone(),
+            };
+            Ok((source, target, format))
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    if context.dry_run {
+        for (source, target, _) in &files {
+            let action = if target.exists() {
+                "overwrite"
+            } else {
+                "write"
+            };
+            report(context, &format!("read {}", source.display()))?;
+            report(context, &format!("{action} {}", target.display()))?;
+        }
+        return Ok(());
+    }
+    let overwritten = files
+        .iter()
+        .filter(|(_, target, _)| target.exists())
+        .count();
+    if overwritten > 0 {
+        let message = format!("{overwritten} existing file(s) will be overwritten. Continue?");
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let context = &*context;
+    files.par_iter().try_for_each(|(source, target, format)| {
+        let _span = tracing::info_span!("file", path = %source.display()).entered();
+        let (img, _) = decode_source(open_image(source)?, source, &ConvertOptions::default())?;
+        let img = stages.iter().fold(img, apply_stage);
+        let img = prepare_image(img, *format, &ConvertOptions::default());
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+                path: parent.to_owned(),
+                source,
+            })?;
+        }
+        if context.use_trash && target.exists() {
+            remove_file(context, target)?;
+        }
+        save_with_quality(&img, target, *format, quality)?;
+        tracing::debug!("Saved file: {}", target.display());
+        Ok::<_, miette::Report>(())
+    })
+}
+
+/// Applies the transformation to the images and overwrites them in their format.
+fn transform(
+    context: &mut Context,
+    path: &Path,
+    transformation: impl Fn(&Path, DynamicImage) -> miette::Result<DynamicImage>,
+) -> miette::Result<()> {
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    if context.dry_run {
+        for path in &paths {
+            report(context, &format!("read {}", path.display()))?;
+            report(context, &format!("overwrite {}", path.display()))?;
+        }
+        return Ok(());
+    }
+    if !paths.is_empty() {
+        let message = format!("{} file(s) will be overwritten. Continue?", paths.len());
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    for path in &paths {
+        let _span = tracing::info_span!("file", path = %path.display()).entered();
+        let img = transformation(path, decode_image(path)?)?;
+        save_image(context, &img, path)?;
+    }
+    Ok(())
+}
+
+/// Optimizes the PNG and JPEG files and prints the bytes saved per file and in total. Files are
+/// only replaced if the optimized version is smaller.
+fn optimize(context: &mut Context, path: &Path) -> miette::Result<()> {
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    if !paths.is_empty() && !context.dry_run {
+        let message = format!("{} file(s) will be overwritten. Continue?", paths.len());
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let (mut saved, mut optimized) = (0, 0);
+    for path in &paths {
+        let data = fs::read(path).map_err(|source| Error::ReadFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        let output = match image::guess_format(&data) {
+            Ok(ImageFormat::Png) => optimize::optimize_png(&data),
+            Ok(ImageFormat::Jpeg) => optimize::optimize_jpeg(&data),
+            _ => {
+                tracing::debug!(
+                    "Skipping file that is neither PNG nor JPEG: {}",
+                    path.display()
+                );
+                continue;
+            }
+        };
+        let Some(output) = output else {
+            tracing::warn!("Skipping file that can't be optimized: {}", path.display());
+            continue;
+        };
+        if output.len() >= data.len() {
+            if context.dry_run {
+                report(
+                    context,
+                    &format!("skip {} (already optimal)", path.display()),
+                )?;
+            } else {
+                writeln!(context.stdout, "{}: already optimal", path.display())
+                    .map_err(Error::Stdout)?;
+            }
+            continue;
+        }
+
+        let difference = (data.len() - output.len()) as u64;
+        saved += difference;
+        optimized += 1;
+        if context.dry_run {
+            report(context, &format!("read {}", path.display()))?;
+            report(
+                context,
+                &format!(
+                    "overwrite {} (saves {})",
+                    path.display(),
+                    format_file_size(difference)
+                ),
+            )?;
+            continue;
+        }
+        if context.use_trash {
+            remove_file(context, path)?;
+        }
+        atomic::write_data(path, &output)?;
+        writeln!(
+            context.stdout,
+            "{}: {} -> {}, saved {}",
+            path.display(),
+            format_file_size(data.len() as u64),
+            format_file_size(output.len() as u64),
+            format_file_size(difference)
+        )
+        .map_err(Error::Stdout)?;
+    }
+    let total = if context.dry_run {
+        format!(
+            "Would save {} in {optimized} file(s)",
+            format_file_size(saved)
+        )
+    } else {
+        format!("Saved {} in {optimized} file(s)", format_file_size(saved))
+    };
+    writeln!(context.stdout, "{total}").map_err(Error::Stdout)?;
+    Ok(())
+}
+
+/// Removes metadata from an image, or from all images in a directory, without re-encoding them.
+fn strip_metadata(
+    context: &mut Context,
+    path: &Path,
+    args: &StripMetadataArgs,
+) -> miette::Result<()> {
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    if !paths.is_empty() && !context.dry_run {
+        let message = format!("{} file(s) will be overwritten. Continue?", paths.len());
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+
+    let mut stripped = 0;
+    for path in &paths {
+        let data = fs::read(path).map_err(|source| Error::ReadFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        let Some(output) = metadata::strip(&data, args.gps_only) else {
+            tracing::warn!(
+                "Skipping file that is neither a well-formed PNG nor JPEG: {}",
+                path.display()
+            );
+            continue;
+        };
+        if output == data {
+            if context.dry_run {
+                report(context, &format!("skip {} (no metadata)", path.display()))?;
+            } else {
+                writeln!(context.stdout, "{}: no metadata", path.display())
+                    .map_err(Error::Stdout)?;
+            }
+            continue;
+        }
+        if !args.gps_only
+            && exif::parse(&metadata::read(path)?.exif.unwrap_or_default())
+                .is_some_and(|exif| exif.orientation.is_some_and(|o| o != 1))
+        {
+            tracing::warn!(
+                "Removing the EXIF orientation changes how the image is displayed: {}",
+                path.display()
+            );
+        }
+
+        stripped += 1;
+        if context.dry_run {
+            report(context, &format!("read {}", path.display()))?;
+            report(
+                context,
+                &format!("overwrite {} (strips metadata)", path.display()),
+            )?;
+            continue;
+        }
+        if context.use_trash {
+            remove_file(context, path)?;
+        }
+        atomic::write_data(path, &output)?;
+        writeln!(context.stdout, "{}: stripped", path.display()).map_err(Error::Stdout)?;
+    }
+    let total = if context.dry_run {
+        format!("Would strip {stripped} file(s)")
+    } else {
+        format!("Stripped {stripped} file(s)")
+    };
+    writeln!(context.stdout, "{total}").map_err(Error::Stdout)?;
+    Ok(())
+}
+
+/// EXIF data of a file that is printed by `exif`.
+#[derive(Serialize)]
+struct ExifEntry {
+    path: String,
+    #[serde(flatten)]
+    exif: exif::Exif,
+}
+
+/// Prints the EXIF data of an image, or of all images in a directory. Images without EXIF data are
+/// listed without fields.
+fn exif(context: &mut Context, path: &Path, output: OutputFormat) -> miette::Result<()> {
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    let mut entries = Vec::new();
+    for path in &paths {
+        let exif = read_exif(path)?.unwrap_or_default();
+        if output == OutputFormat::Text {
+            writeln!(context.stdout, "{}", path.display()).map_err(Error::Stdout)?;
+            for (name, value) in exif.fields() {
+                writeln!(context.stdout, "  {name}: {value}").map_err(Error::Stdout)?;
+            }
+        }
+        entries.push(ExifEntry {
+            path: path.display().to_string(),
+            exif,
+        });
+    }
+    match output {
+        OutputFormat::Text => Ok(()),
+        OutputFormat::Json if path.is_file() => print_json(context, &entries[0]),
+        OutputFormat::Json => print_json(context, &entries),
+    }
+}
+
+/// Reads the EXIF data of an image. TIFF files are EXIF data themselves.
+fn read_exif(path: &Path) -> miette::Result<Option<exif::Exif>> {
+    if let Some(data) = metadata::read(path)?.exif {
+        return Ok(exif::parse(&data));
+    }
+    let data = fs::read(path).map_err(|source| Error::ReadFile {
+        path: path.to_owned(),
+        source,
+    })?;
+    Ok(exif::parse(&data))
+}
+
+/// Draws an image, or all images in a directory below their paths, in the terminal.
+fn show(context: &mut Context, path: &Path, args: &ShowArgs) -> miette::Result<()> {
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    let protocol = args.protocol.unwrap_or_else(preview::Protocol::detect);
+    tracing::debug!("Preview protocol: {protocol:?}");
+    let columns = args.width.unwrap_or_else(terminal_columns);
+    for file in &paths {
+        if path.is_dir() {
+            writeln!(context.stdout, "{}", file.display()).map_err(Error::Stdout)?;
+        }
+        let img = decode_image(file)?;
+        let preview =
+            preview::render(&img, protocol, columns).map_err(|source| Error::Preview {
+                path: file.to_owned(),
+                source,
+            })?;
+        write!(context.stdout, "{preview}").map_err(Error::Stdout)?;
+    }
+    Ok(())
+}
+
+/// Prints an image, or all images in a directory below their paths, as ASCII art.
+fn ascii(context: &mut Context, path: &Path, args: &AsciiArgs) -> miette::Result<()> {
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    let columns = args.width.unwrap_or_else(terminal_columns);
+    for file in &paths {
+        if path.is_dir() {
+            writeln!(context.stdout, "{}", file.display()).map_err(Error::Stdout)?;
+        }
+        let img = decode_image(file)?;
+        write!(
+            context.stdout,
+            "{}",
+            preview::ascii(&img, columns, args.color)
+        )
+        .map_err(Error::Stdout)?;
+    }
+    Ok(())
+}
+
+/// Width of the terminal from $COLUMNS, or 80 columns.
+fn terminal_columns() -> u32 {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+fn meta(context: &mut Context, path: &Path, command: &MetaCommands) -> miette::Result<()> {
+    let paths = match to_path_type(path) {
+        Some(PathType::File) => vec![path.to_path_buf()],
+        Some(PathType::Directory) => image_files(&context.walk, path),
+        None => {
+            return Err(Error::PathAccess {
+                path: path.to_owned(),
+            }
+            .into())
+        }
+    };
+    match command {
+        MetaCommands::Export { sidecar: true } => {
+            let overwritten = paths
+                .iter()
+                .filter(|path| sidecar_path(path).exists())
+                .count();
+            if overwritten > 0 && !context.dry_run {
+                let message =
+                    format!("{overwritten} existing file(s) will be overwritten. Continue?");
+                if !confirm(context, &message)? {
+                    return Err(Error::Aborted.into());
+                }
+            }
+            for path in &paths {
+                let sidecar_path = sidecar_path(path);
+                if context.dry_run {
+                    report(context, &format!("read {}", path.display()))?;
+                    let action = if sidecar_path.exists() {
+                        "overwrite"
+                    } else {
+                        "write"
+                    };
+                    report(context, &format!("{action} {}", sidecar_path.display()))?;
+                    continue;
+                }
+                let metadata = metadata::read(path)?;
+                let json =
+                    serde_json::to_string_pretty(&metadata).map_err(|source| Error::Sidecar {
+                        path: sidecar_path.clone(),
+                        source,
+                    })?;
+                if context.use_trash && sidecar_path.exists() {
+                    remove_file(context, &sidecar_path)?;
+                }
+                atomic::write_data(&sidecar_path, (json + "\n").as_bytes())?;
+                tracing::debug!("Exported metadata to: {}", sidecar_path.display());
+            }
+        }
+        MetaCommands::Export { sidecar: false } => {
+            for path in &paths {
+                let metadata = metadata::read(path)?;
+                let entry = metadata::Entry {
+                    path: path.display().to_string(),
+                    metadata: &metadata,
+                };
+                let json = serde_json::to_string(&entry).map_err(|source| Error::Sidecar {
+                    path: sidecar_path(path),
+                    source,
+                })?;
+                writeln!(context.stdout, "{json}").map_err(Error::Stdout)?;
+            }
+        }
+        MetaCommands::Import => {
+            let files = paths
+                .iter()
+                .map(|path| (path, sidecar_path(path)))
+                .filter(|(path, sidecar_path)| {
+                    let exists = sidecar_path.exists();
+                    if !exists {
+                        tracing::debug!("No sidecar for: {}", path.display());
+                    }
+                    exists
+                })
+                .collect::<Vec<_>>();
+            if !files.is_empty() && !context.dry_run {
+                let message = format!("{} file(s) will be overwritten. Continue?", files.len());
+                if !confirm(context, &message)? {
+                    return Err(Error::Aborted.into());
+                }
+            }
+            for (path, sidecar_path) in &files {
+                if context.dry_run {
+                    report(context, &format!("read {}", sidecar_path.display()))?;
+                    report(context, &format!("overwrite {}", path.display()))?;
+                    continue;
+                }
+                let json = fs::read_to_string(sidecar_path).map_err(|source| Error::ReadFile {
+                    path: sidecar_path.clone(),
+                    source,
+                })?;
+                let metadata = serde_json::from_str(&json).map_err(|source| Error::Sidecar {
+                    path: sidecar_path.clone(),
+                    source,
+                })?;
+                let data = metadata::embed(path, &metadata)?;
+                if context.use_trash {
+                    remove_file(context, path)?;
+                }
+                atomic::write_data(path, &data)?;
+                tracing::debug!("Imported metadata from: {}", sidecar_path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sidecar of an image, which keeps the extension so that "photo.jpg" and "photo.png" don't
+/// share "photo.json".
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar_path = path.as_os_str().to_owned();
+    sidecar_path.push(".json");
+    PathBuf::from(sidecar_path)
+}
+
+fn merge_hdr(context: &Context, paths: &[PathBuf], args: &MergeHdrArgs) -> miette::Result<()> {
+    if !args.exposure_times.is_empty() && args.exposure_times.len() != paths.len() {
+        return Err(Error::ExposureTimeCount {
+            expected: paths.len(),
+            found: args.exposure_times.len(),
+        }
+        .into());
+    }
+
+    let images = paths
+        .iter()
+        .map(|path| decode_image(path))
+        .collect::<miette::Result<Vec<_>>>()?;
+    if let Some((index, _)) = images
+        .iter()
+        .enumerate()
+        .find(|(_, img)| img.dimensions() != images[0].dimensions())
+    {
+        return Err(Error::DimensionMismatch {
+            path: paths[index].to_owned(),
+        }
+        .into());
+    }
+
+    let exposure_times =
+        (!args.exposure_times.is_empty()).then_some(args.exposure_times.as_slice());
+    let merged = hdr::merge(&images, exposure_times);
+    save_image(context, &merged, &args.output)
+}
+
+#[cfg(feature = "stitch")]
+fn stitch(context: &Context, paths: &[PathBuf], args: &StitchArgs) -> miette::Result<()> {
+    let images = paths
+        .iter()
+        .map(|path| decode_image(path))
+        .collect::<miette::Result<Vec<_>>>()?;
+    let panorama = stitch::stitch(&images);
+    save_image(context, &panorama, &args.output)
+}
+
+#[cfg(feature = "ffmpeg")]
+fn frames(context: &Context, path: &Path, args: &FramesArgs) -> miette::Result<()> {
+    let stills = ffmpeg::extract_frames(path, args.every, &args.output_dir)?;
+    tracing::debug!("Extracted {} stills from: {}", stills.len(), path.display());
+    let Some(target_format) = &args.target_format else {
+        return Ok(());
+    };
+
+    let convert_args = ConvertOptions {
+        target_format: target_format.to_owned(),
+        ..Default::default()
+    };
+    let target_format = dirty_string_to_format(target_format)?;
+    if target_format == ImageFormat::Png {
+        return Ok(());
+    }
+    for still in &stills {
+        let target_path = target_path(still, target_format);
+        convert_file(context, still, &target_path, target_format, &convert_args)?;
+        fs::remove_file(still).map_err(|source| Error::Delete {
+            path: still.to_owned(),
+            source,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "ffmpeg")]
+fn video(context: &Context, paths: &[PathBuf], args: &VideoArgs) -> miette::Result<()> {
+    let mut stills = Vec::new();
+    for path in paths {
+        match to_path_type(path) {
+            Some(PathType::File) => stills.push(path.to_owned()),
+            Some(PathType::Directory) => stills.extend(image_files(&context.walk, path)),
+            None => {
+                return Err(Error::PathAccess {
+                    path: path.to_owned(),
+                }
+                .into())
+            }
+        }
+    }
+    stills.sort();
+
+    if args.output.exists() {
+        let message = format!("{} will be overwritten. Continue?", args.output.display());
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+        if context.use_trash {
+            remove_file(context, &args.output)?;
+        }
+    }
+
+    let frames_dir = std::env::temp_dir().join(format!("imy-video-{}", std::process::id()));
+    fs::create_dir_all(&frames_dir).map_err(|source| Error::CreateDir {
+        path: frames_dir.clone(),
+        source,
+    })?;
+    let result = write_video_frames(context, &stills, args.size, &frames_dir)
+        .and_then(|()| ffmpeg::encode_video(&frames_dir, args.fps, &args.output));
+    if let Err(error) = fs::remove_dir_all(&frames_dir) {
+        tracing::warn!("Failed to remove {}: {error}", frames_dir.display());
+    }
+    result
+}
+
+/// Writes the stills as numbered PNG files with the same size, which is required by ffmpeg. The
+/// size is rounded down to even numbers for the chroma subsampling of most codecs.
+#[cfg(feature = "ffmpeg")]
+fn write_video_frames(
+    context: &Context,
+    stills: &[PathBuf],
+    size: Option<(u32, u32)>,
+    frames_dir: &Path,
+) -> miette::Result<()> {
+    let mut size = size;
+    for (index, still) in stills.iter().enumerate() {
+        let img = decode_image(still)?;
+        let (width, height) = *size.get_or_insert(img.dimensions());
+        let (width, height) = ((width & !1).max(2), (height & !1).max(2));
+        let img = if img.dimensions() == (width, height) {
+            img
+        } else {
+            resize::resize(&img, width, height, resize::Mode::Fill)
+        };
+        let img = DynamicImage::ImageRgb8(img.into_rgb8());
+        save_image(context, &img, &frames_dir.join(format!("{index:05}.png")))?;
+    }
+    Ok(())
+}
+
+fn generate(context: &Context, args: &GenerateArgs) -> miette::Result<()> {
+    let style = generate::Style {
+        color: args.color,
+        background: args.background,
+        seed: args.seed,
+    };
+    let mut img = generate::generate(args.pattern, args.size, &style);
+    if let Some(text) = &args.text {
+        generate::draw_placeholder_text(&mut img, text, args.color);
+    }
+    save_image(context, &without_opaque_alpha(img), &args.output)
+}
+
+/// Drops the alpha channel if every pixel is opaque, so that formats without alpha can be saved.
+fn without_opaque_alpha(img: image::RgbaImage) -> DynamicImage {
+    if img.pixels().all(|pixel| pixel[3] == 255) {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(img).into_rgb8())
+    } else {
+        DynamicImage::ImageRgba8(img)
+    }
+}
+
+fn stego_embed(context: &Context, path: &Path, args: &StegoEmbedArgs) -> miette::Result<()> {
+    let target_path = args
+        .output
+        .clone()
+        .unwrap_or_else(|| target_path(path, ImageFormat::Png));
+    if let Some(format) = ImageFormat::from_path(&target_path)
+        .ok()
+        .filter(|format| !is_lossless_format(*format))
+    {
+        return Err(Error::LossyFormat {
+            format: format_to_string(format),
+        }
+        .into());
+    }
+
+    let data = fs::read(&args.file).map_err(|source| Error::ReadFile {
+        path: args.file.to_owned(),
+        source,
+    })?;
+    let img = decode_image(path)?;
+    let img = stego::embed(&img, &data, args.passphrase.as_deref())?;
+    tracing::debug!("Embedded {} bytes into: {}", data.len(), path.display());
+
+    if target_path.exists() {
+        let message = format!("{} will be overwritten. Continue?", target_path.display());
+        if !confirm(context, &message)? {
+            return Err(Error::Aborted.into());
+        }
+    }
+    save_image(context, &img, &target_path)
+}
+
+fn stego_extract(
+    context: &mut Context,
+    path: &Path,
+    args: &StegoExtractArgs,
+) -> miette::Result<()> {
+    let img = decode_image(path)?;
+    let data = stego::extract(&img, args.passphrase.as_deref())?;
+    match &args.output {
+        Some(output) => atomic::write_data(output, &data)?,
+        None => context.data().write_all(&data).map_err(Error::Stdout)?,
+    }
+    Ok(())
+}
+
+fn decode_image(path: &Path) -> miette::Result<DynamicImage> {
+    let img = open_image(path)?.decode().map_err(|source| Error::Decode {
+        path: path.to_owned(),
+        source,
+    })?;
+    tracing::trace!("Decoded file: {}", path.display());
+    Ok(img)
+}
+
+/// Saves an image in the format given by the extension of the path.
+fn save_image(context: &Context, img: &DynamicImage, path: &Path) -> miette::Result<()> {
+    if context.use_trash && path.exists() {
+        remove_file(context, path)?;
+    }
+    let format = ImageFormat::from_path(path).map_err(|source| Error::Save {
+        path: path.to_owned(),
+        format: "unknown".to_owned(),
+        source,
+    })?;
+    atomic::write(path, |writer| {
+        img.write_to(writer, format).map_err(|source| {
+            Error::Save {
+                path: path.to_owned(),
+                format: format_to_string(format),
+                source,
+            }
+            .into()
+        })
+    })?;
+    tracing::trace!("Saved file: {}", path.display());
+    Ok(())
+}
+
+/// Saves an image as an ico file with an icon for each size.
+fn save_ico(img: &DynamicImage, path: &Path, sizes: &[u32]) -> miette::Result<()> {
+    atomic::write(path, |writer| {
+        encode_ico(img, writer, sizes).map_err(|source| {
+            Error::Save {
+                path: path.to_owned(),
+                format: format_to_string(ImageFormat::Ico),
+                source,
+            }
+            .into()
+        })
+    })
+}
+
+/// Encodes an ico file with an icon for each size, in ascending order. Non-square images are
+/// centered in the icons with transparent padding.
+fn encode_ico(img: &DynamicImage, writer: impl io::Write, sizes: &[u32]) -> image::ImageResult<()> {
+    let mut sizes = sizes.to_vec();
+    sizes.sort_unstable();
+    sizes.dedup();
+    let frames = sizes
+        .iter()
+        .map(|&size| {
+            tracing::debug!("Encoding icon of {size}x{size}");
+            let icon = resize::resize(img, size, size, resize::Mode::Contain).into_rgba8();
+            IcoFrame::as_png(icon.as_raw(), size, size, image::ExtendedColorType::Rgba8)
+        })
+        .collect::<image::ImageResult<Vec<_>>>()?;
+    IcoEncoder::new(writer).encode_images(&frames)
+}
+
+/// Saves an image with the quality passed to the encoder of lossy formats.
+fn save_with_quality(
+    img: &DynamicImage,
+    path: &Path,
+    format: ImageFormat,
+    quality: Option<u8>,
+) -> miette::Result<()> {
+    atomic::write(path, |writer| {
+        encode_with_quality(img, writer, format, quality).map_err(|source| {
+            Error::Save {
+                path: path.to_owned(),
+                format: format_to_string(format),
+                source,
+            }
+            .into()
+        })
+    })
+}
+
+fn encode_with_quality(
+    img: &DynamicImage,
+    mut writer: impl io::Write + io::Seek,
+    format: ImageFormat,
+    quality: Option<u8>,
+) -> image::ImageResult<()> {
+    let Some(quality) = quality.filter(|_| matches!(format, ImageFormat::Jpeg | ImageFormat::Avif))
+    else {
+        if quality.is_some() {
+            tracing::warn!(
+                "Ignoring the quality for format: {}",
+                format_to_string(format)
+            );
+        }
+        return img.write_to(&mut writer, format);
+    };
+    // Both encoders expect a quality of at least 1
+    let quality = quality.max(1);
+    match format {
+        ImageFormat::Jpeg => img.write_with_encoder(JpegEncoder::new_with_quality(writer, quality)),
+        _ => img.write_with_encoder(AvifEncoder::new_with_speed_quality(writer, 4, quality)),
+    }
+}
+
+/// Encodes an image with the highest quality up to `quality` whose output is at most `max_size`
+/// bytes, found by a binary search over the quality.
+fn encode_to_size(
+    img: &DynamicImage,
+    path: &Path,
+    format: ImageFormat,
+    quality: Option<u8>,
+    max_size: u64,
+) -> miette::Result<Vec<u8>> {
+    let encode = |quality| -> miette::Result<Vec<u8>> {
+        let mut output = io::Cursor::new(Vec::new());
+        encode_with_quality(img, &mut output, format, Some(quality)).map_err(|source| {
+            Error::Save {
+                path: path.to_owned(),
+                format: format_to_string(format),
+                source,
+            }
+        })?;
+        Ok(output.into_inner())
+    };
+    let (mut low, mut high) = (1, quality.unwrap_or(100).max(1));
+    let mut best = None;
+    while low <= high {
+        let quality = low + (high - low) / 2;
+        let data = encode(quality)?;
+        if data.len() as u64 <= max_size {
+            best = Some((quality, data));
+            low = quality + 1;
+        } else {
+            high = quality - 1;
+        }
+    }
+    let (quality, data) = best.ok_or_else(|| Error::SizeBudget {
+        path: path.to_owned(),
+        max_size: format_file_size(max_size),
+    })?;
+    tracing::debug!("Encoded with quality {quality} into {} bytes", data.len());
+    Ok(data)
+}
+
+fn is_hdr(img: &DynamicImage) -> bool {
+    matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F)
+}
+
+fn is_hdr_format(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::OpenExr | ImageFormat::Hdr)
+}
+
+/// Formats that can store an alpha channel.
+fn supports_alpha(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Png
+            | ImageFormat::WebP
+            | ImageFormat::Tiff
+            | ImageFormat::Tga
+            | ImageFormat::Qoi
+            | ImageFormat::Ico
+            | ImageFormat::Avif
+            | ImageFormat::OpenExr
+            | ImageFormat::Farbfeld
+    )
+}
+
+/// Formats whose encoders accept an alpha channel. Unlike `supports_alpha`, this includes formats
+/// that store it poorly, e.g. with the binary transparency of GIF.
+fn encodes_alpha(format: ImageFormat) -> bool {
+    supports_alpha(format)
+        || matches!(
+            format,
+            ImageFormat::Gif | ImageFormat::Bmp | ImageFormat::Pnm
+        )
+}
+
+/// Formats that store the pixels without loss, given a suitable color type.
+fn is_lossless_format(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Png
+            | ImageFormat::Bmp
+            | ImageFormat::Tiff
+            | ImageFormat::Tga
+            | ImageFormat::Pnm
+            | ImageFormat::Qoi
+            | ImageFormat::WebP
+            | ImageFormat::Farbfeld
+            | ImageFormat::OpenExr
+    )
+}
+
+fn target_path(path: &Path, target_format: ImageFormat) -> PathBuf {
+    path.with_extension(format_to_string(target_format))
+}
+
+/// Path of the converted image. With an output directory, the path relative to the input is
+/// recreated in it, so that files with the same name in different subdirectories don't collide.
+fn output_path(
+    input: &Path,
+    path: &Path,
+    target_format: ImageFormat,
+    out_dir: Option<&Path>,
+) -> PathBuf {
+    match out_dir {
+        Some(out_dir) => target_path(&out_dir.join(relative_path(input, path)), target_format),
+        None => target_path(path, target_format),
+    }
+}
+
+const OUTPUT_PLACEHOLDERS: &[&str] = &["stem", "ext", "format", "width", "height", "hash", "date"];
+
+/// Path of the output of a conversion with the file name rendered from the template, in the
+/// directory of the default output path `target`.
+fn templated_path(
+    template: &str,
+    source: &Path,
+    target: &Path,
+    target_format: ImageFormat,
+    args: &ConvertOptions,
+) -> miette::Result<PathBuf> {
+    let unknown = |value: Option<String>| value.unwrap_or("unknown".to_owned());
+    let dimensions = || output_dimensions(source, args);
+    let name = template::render(template, OUTPUT_PLACEHOLDERS, |name| match name {
+        "stem" => Some(source.file_stem()?.to_string_lossy().into_owned()),
+        "ext" | "format" => Some(format_to_string(target_format)),
+        "width" => Some(unknown(dimensions().map(|(width, _)| width.to_string()))),
+        "height" => Some(unknown(dimensions().map(|(_, height)| height.to_string()))),
+        "hash" => Some(unknown(
+            fs::read(source)
+                .ok()
+                .map(|data| format!("{:08x}", crc32fast::hash(&data))),
+        )),
+        "date" => Some(unknown(capture_date(source))),
+        _ => None,
+    })?;
+    Ok(target.with_file_name(name))
+}
+
+/// Dimensions of the output of a conversion, determined from the header of the source without
+/// decoding its pixels.
+fn output_dimensions(path: &Path, args: &ConvertOptions) -> Option<(u32, u32)> {
+    let mut decoder = open_image(path).ok()?.into_decoder().ok()?;
+    let (width, height) = decoder.dimensions();
+    let orientation = decoder.orientation().ok()?;
+    let size = match orientation {
+        Orientation::Rotate90
+        | Orientation::Rotate270
+        | Orientation::Rotate90FlipH
+        | Orientation::Rotate270FlipH
+            if !args.no_auto_orient =>
+        {
+            (height, width)
+        }
+        _ => (width, height),
+    };
+    let size = match args.resize {
+        Some((width, height)) => resize::dimensions(size, width, height, args.mode),
+        None => size,
+    };
+    Some(match args.scale {
+        Some(scale) => resize::scaled_dimensions(size, scale),
+        None => size,
+    })
+}
+
+/// Path of a file relative to the input it was found in, or its name if it is the input itself.
+fn relative_path<'a>(input: &Path, path: &'a Path) -> &'a Path {
+    match path.strip_prefix(input) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        _ => Path::new(path.file_name().unwrap_or(path.as_os_str())),
+    }
+}
+
+/// Checks that the value is a valid glob for `--include` and `--exclude`.
+fn parse_glob(value: &str) -> Result<String, String> {
+    OverrideBuilder::new("")
+        .add(value)
+        .map_err(|error| error.to_string())?;
+    Ok(value.to_owned())
+}
+
+/// Image files in the directory and its subdirectories. Entries are sorted by name within each
+/// directory, so that the order is the same across runs and platforms.
+fn image_files(walk: &WalkOptions, path: &Path) -> Vec<PathBuf> {
+    let mut overrides = OverrideBuilder::new(path);
+    let globs = walk.include.iter().cloned();
+    let globs = globs.chain(walk.exclude.iter().map(|glob| format!("!{glob}")));
+    for glob in globs {
+        // The globs are validated when parsing the arguments
+        if let Err(error) = overrides.add(&glob) {
+            tracing::warn!("Ignoring invalid glob {glob}: {error}");
+        }
+    }
+    let mut builder = WalkBuilder::new(path);
+    builder.hidden(!walk.hidden);
+    builder.max_depth(if walk.no_recursive {
+        Some(1)
+    } else {
+        walk.max_depth
+    });
+    if walk.no_ignore {
+        builder
+            .ignore(false)
+            .git_ignore(false)
+            .git_global(false)
+            .git_exclude(false)
+            .parents(false);
+    }
+    match overrides.build() {
+        Ok(overrides) => {
+            builder.overrides(overrides);
+        }
+        Err(error) => tracing::warn!("Ignoring the include and exclude globs: {error}"),
+    }
+    builder
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+        .flatten()
+        .filter(|entry| is_image_file(entry.path()).unwrap_or(false))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Opens an image and determines its format from the magic bytes, falling back to the extension
+/// when the contents are inconclusive.
+fn open_image(path: &Path) -> miette::Result<ImageReader<BufReader<File>>> {
+    ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|source| {
+            Error::Open {
+                path: path.to_owned(),
+                source,
+            }
+            .into()
+        })
+}
+
+fn is_image_file(path: &Path) -> miette::Result<bool> {
+    let reader = open_image(path)?;
+    Ok(reader.format().is_some())
+}
+
+fn is_image_with_type(path: &Path, format: ImageFormat) -> miette::Result<bool> {
+    let reader = open_image(path)?;
+    Ok(reader.format() == Some(format))
+}
+
+fn string_to_log_level(level: &str) -> miette::Result<Level> {
+    Ok(match level {
+        "trace" => Level::TRACE,
+        "debug" => Level::DEBUG,
+        "info" => Level::INFO,
+        "warn" => Level::WARN,
+        "error" => Level::ERROR,
+        _ => {
+            return Err(Error::UnknownLogLevel {
+                level: level.to_owned(),
+            }
+            .into())
+        }
+    })
+}
+
+/// Parses the name of a format, ignoring case and surrounding whitespace, e.g. "PNG" or "jpg".
+pub fn dirty_string_to_format(format: &str) -> miette::Result<ImageFormat> {
+    let format = format.to_lowercase().trim().to_string();
+    string_to_format(&format)
+}
+
+/// Canonical names of the formats. They are used for parsing as well as printing, so that both
+/// directions always round-trip.
+const FORMATS: &[(&str, ImageFormat)] = &[
+    ("png", ImageFormat::Png),
+    ("jpeg", ImageFormat::Jpeg),
+    ("gif", ImageFormat::Gif),
+    ("webp", ImageFormat::WebP),
+    ("pnm", ImageFormat::Pnm),
+    ("tiff", ImageFormat::Tiff),
+    ("tga", ImageFormat::Tga),
+    ("dds", ImageFormat::Dds),
+    ("bmp", ImageFormat::Bmp),
+    ("ico", ImageFormat::Ico),
+    ("hdr", ImageFormat::Hdr),
+    ("openexr", ImageFormat::OpenExr),
+    ("farbfeld", ImageFormat::Farbfeld),
+    ("avif", ImageFormat::Avif),
+    ("qoi", ImageFormat::Qoi),
+    ("pcx", ImageFormat::Pcx),
+];
+
+/// Additional names that are accepted when parsing, on top of the file extensions known to `image`.
+const FORMAT_ALIASES: &[(&str, ImageFormat)] = &[("jpe", ImageFormat::Jpeg)];
+
+fn string_to_format(format: &str) -> miette::Result<ImageFormat> {
+    if let Some((_, image_format)) = FORMATS
+        .iter()
+        .chain(FORMAT_ALIASES)
+        .find(|(name, _)| *name == format)
+    {
+        return Ok(*image_format);
+    }
+    if let Some(image_format) = ImageFormat::from_extension(format) {
+        return Ok(image_format);
+    }
+    if matches!(format, "heif" | "heic") {
+        return Err(Error::UnsupportedFormat {
+            format: format.to_owned(),
+            help: "HEIF is not supported, did you mean 'avif'?".to_owned(),
+        }
+        .into());
+    }
+    let valid_formats = FORMATS
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let help = match closest_format_name(format) {
+        Some(name) => format!("Did you mean '{name}'? Valid formats are: {valid_formats}"),
+        None => format!("Valid formats are: {valid_formats}"),
+    };
+    Err(Error::UnknownFormat {
+        format: format.to_owned(),
+        help,
+    }
+    .into())
+}
+
+fn format_names() -> impl Iterator<Item = &'static str> {
+    FORMATS
+        .iter()
+        .chain(FORMAT_ALIASES)
+        .map(|(name, _)| *name)
+        .chain(ImageFormat::all().flat_map(|format| format.extensions_str().iter().copied()))
+}
+
+fn closest_format_name(format: &str) -> Option<&'static str> {
+    format_names()
+        .map(|name| (strsim::damerau_levenshtein(format, name), name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Canonical name of a format, e.g. "jpeg".
+pub fn format_to_string(format: ImageFormat) -> String {
+    FORMATS
+        .iter()
+        .find(|(_, image_format)| *image_format == format)
+        .map(|(name, _)| *name)
+        .or_else(|| format.extensions_str().first().copied())
+        .unwrap_or_else(|| {
+            tracing::warn!("No name known for format: {:?}", format);
+            "unknown"
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str;
+
+    use image::RgbImage;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    struct Tester {
+        temp_dir: TempDir,
+    }
+
+    impl Tester {
+        fn new() -> Self {
+            Self {
+                temp_dir: TempDir::new("").unwrap(),
+            }
+        }
+
+        fn path_buf(&self) -> PathBuf {
+            self.temp_dir.path().to_path_buf()
+        }
+
+        fn save_empty_image(
+            &self,
+            path: impl AsRef<Path>,
+            size: u32,
+            format: ImageFormat,
+        ) -> PathBuf {
+            let img = RgbImage::new(size, size);
+            let path = self.temp_dir.path().join(path);
+            if let Some(parent_path) = path.parent() {
+                fs::create_dir_all(parent_path).unwrap();
+            }
+            dbg!(&path);
+            img.save_with_format(&path, format).unwrap();
+            path
+        }
+    }
+
+    #[test]
+    fn convert_jpg_to_png() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.jpg", SIZE, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let output_path = input_path.with_extension("png");
+        let reader = ImageReader::open(&output_path).unwrap();
+        assert_eq!(reader.format(), Some(ImageFormat::Png));
+        assert_eq!(reader.into_dimensions().unwrap(), (SIZE, SIZE));
+
+        let reader = ImageReader::open(&output_path).unwrap();
+        let result = reader.decode();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn convert_folder_jpg_to_png() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let folder_path = PathBuf::from("folder");
+        let input_path0 =
+            tester.save_empty_image(folder_path.join("my_image0.jpg"), SIZE, ImageFormat::Jpeg);
+        let input_path1 =
+            tester.save_empty_image(folder_path.join("my_image1.jpg"), SIZE, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![tester.path_buf().join(folder_path)],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        fn assert_file(path: &Path) {
+            let output_path = path.with_extension("png");
+
+            let reader = ImageReader::open(&output_path).unwrap();
+            assert_eq!(reader.format(), Some(ImageFormat::Png));
+            assert_eq!(reader.into_dimensions().unwrap(), (SIZE, SIZE));
+
+            let reader = ImageReader::open(&output_path).unwrap();
+            let result = reader.decode();
+            assert!(result.is_ok());
+        }
+
+        assert_file(&input_path0);
+        assert_file(&input_path1);
+    }
+
+    #[test]
+    fn is_not_png() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.jpg", SIZE, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Is {
+                format: "png".to_owned(),
+                any: false,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        assert!(run(context, args).is_err());
+    }
+
+    #[test]
+    fn is_png() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.png", SIZE, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Is {
+                format: "png".to_owned(),
+                any: false,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        assert!(run(context, args).is_ok());
+    }
+
+    #[test]
+    fn info_png() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.png", SIZE, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Info {
+                format: None,
+                output: OutputFormat::Text,
+                print0: false,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let size = fs::metadata(&input_path).unwrap().len();
+        let expected = format!(
+            "{} png {SIZE}x{SIZE} rgb 8-bit {size} B\n",
+            input_path.display()
+        );
+        let found = str::from_utf8(&stdout).unwrap();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn convert_overwrites_existing_with_yes() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.jpg", SIZE, ImageFormat::Jpeg);
+        let output_path = tester.save_empty_image("my_image.png", 1, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            yes: true,
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let reader = ImageReader::open(&output_path).unwrap();
+        assert_eq!(reader.into_dimensions().unwrap(), (SIZE, SIZE));
+    }
+
+    #[test]
+    fn convert_overwrite_policy() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.jpg", SIZE, ImageFormat::Jpeg);
+        let output_path = tester.save_empty_image("my_image.png", 1, ImageFormat::Png);
+
+        let convert = |force, skip_existing| {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "png".to_owned(),
+                    force,
+                    skip_existing,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        let dimensions = || {
+            let reader = ImageReader::open(&output_path).unwrap();
+            reader.into_dimensions().unwrap()
+        };
+
+        let report = convert(false, false).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::output_exists");
+        convert(false, true).unwrap();
+        assert_eq!(dimensions(), (1, 1));
+        convert(true, false).unwrap();
+        assert_eq!(dimensions(), (SIZE, SIZE));
+    }
+
+    #[test]
+    fn convert_dry_run() {
+        let tester = Tester::new();
+        tester.save_empty_image("a.jpg", 8, ImageFormat::Jpeg);
+        tester.save_empty_image("b.jpg", 8, ImageFormat::Jpeg);
+        tester.save_empty_image("b.png", 8, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            dry_run: true,
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "webp".to_owned(),
+                delete_original: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let output = String::from_utf8(stdout).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].starts_with("read") && lines[0].ends_with("a.jpg"));
+        assert!(lines[1].starts_with("write") && lines[1].ends_with("a.webp"));
+        assert!(lines[2].starts_with("delete") && lines[2].ends_with("a.jpg"));
+        assert!(tester.path_buf().join("a.jpg").exists());
+        assert!(!tester.path_buf().join("a.webp").exists());
+    }
+
+    #[test]
+    fn completions_list_subcommands() {
+        let args = Args {
+            command: Some(Commands::Completions {
+                shell: clap_complete::Shell::Bash,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let script = str::from_utf8(&stdout).unwrap();
+        assert!(script.contains("convert"));
+        assert!(script.contains("--target-format"));
+    }
+
+    #[test]
+    fn doctor_lists_codecs_and_formats() {
+        let args = Args {
+            command: Some(Commands::Doctor),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let found = str::from_utf8(&stdout).unwrap();
+        assert!(found.starts_with(&format!("imy {}", env!("CARGO_PKG_VERSION"))));
+        assert!(found.lines().any(|line| line.starts_with("  image ")));
+        assert!(found.lines().any(|line| line.starts_with("png")));
+    }
+
+    #[test]
+    fn formats_lists_png() {
+        let args = Args {
+            command: Some(Commands::Formats),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let found = str::from_utf8(&stdout).unwrap();
+        assert!(found
+            .lines()
+            .any(|line| line.starts_with("png") && line.contains("decode encode")));
+    }
+
+    #[test]
+    fn quiet_prints_nothing() {
+        let tester = Tester::new();
+        tester.save_empty_image("a.png", 8, ImageFormat::Png);
+
+        for command in [
+            Commands::Formats,
+            Commands::Info {
+                format: None,
+                output: OutputFormat::Text,
+                print0: false,
+            },
+        ] {
+            let args = Args {
+                paths: vec![tester.path_buf()],
+                quiet: true,
+                command: Some(command),
+                ..Default::default()
+            };
+            let mut stdout = Vec::new();
+            run(Context::new(&mut stdout), args).unwrap();
+            assert!(stdout.is_empty());
+        }
+
+        let verbose = Args::try_parse_from(["imy", "-vv", "."]).unwrap();
+        assert_eq!(verbose.verbose, 2);
+        Args::try_parse_from(["imy", "-v", "--log-level", "debug", "."]).unwrap_err();
+    }
+
+    #[test]
+    fn quiet_keeps_data() {
+        let mut input = io::Cursor::new(Vec::new());
+        RgbImage::new(4, 2)
+            .write_to(&mut input, ImageFormat::Png)
+            .unwrap();
+
+        // `run` reads the real stdin for "-", so the stream is converted with the quiet context
+        // that `run` sets up
+        let args = Args::try_parse_from(["imy", "-q", "-", "convert", "-t", "jpeg"]).unwrap();
+        assert!(args.quiet);
+        let Some(Commands::Convert(convert_args)) = args.command else {
+            panic!("expected convert");
+        };
+        let mut sink = io::sink();
+        let mut stdout = Vec::new();
+        let mut context = Context::new(&mut sink);
+        context.data = Some(&mut stdout);
+        convert_stream(&mut context, input.get_ref().as_slice(), &convert_args).unwrap();
+        let img = image::load_from_memory_with_format(&stdout, ImageFormat::Jpeg).unwrap();
+        assert_eq!(img.dimensions(), (4, 2));
+
+        let args = Args::try_parse_from(["imy", "-q", "completions", "bash"]).unwrap();
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+        assert!(String::from_utf8(stdout).unwrap().contains("_imy()"));
+    }
+
+    #[test]
+    fn log_file_rotation() {
+        let tester = Tester::new();
+        let path = tester.path_buf().join("imy.log");
+        for run in 0..5 {
+            log_file::rotate(&path, 4).unwrap();
+            fs::write(&path, format!("run {run}")).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "run 4");
+        let rotated = |index| fs::read_to_string(log_file::rotated_path(&path, index)).unwrap();
+        assert_eq!(
+            (rotated(1), rotated(3)),
+            ("run 3".to_owned(), "run 1".to_owned())
+        );
+        assert!(!log_file::rotated_path(&path, 4).exists());
+
+        // Small files are appended to
+        log_file::rotate(&path, 1024).unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn log_format() {
+        let args = Args::try_parse_from(["imy", "--log-format", "json", "."]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Json);
+        Args::try_parse_from(["imy", "--log-format", "xml", "."]).unwrap_err();
+    }
+
+    #[test]
+    fn format_aliases_and_suggestions() {
+        assert_eq!(string_to_format("tif").unwrap(), ImageFormat::Tiff);
+        assert_eq!(string_to_format("jpe").unwrap(), ImageFormat::Jpeg);
+        assert_eq!(closest_format_name("jepg"), Some("jpeg"));
+        assert_eq!(closest_format_name("something"), None);
+    }
+
+    #[test]
+    fn convert_without_extension() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image", SIZE, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let reader = ImageReader::open(input_path.with_extension("png")).unwrap();
+        assert_eq!(reader.format(), Some(ImageFormat::Png));
+    }
+
+    #[test]
+    fn format_names_round_trip() {
+        for format in ImageFormat::all() {
+            assert_eq!(string_to_format(&format_to_string(format)).unwrap(), format);
+            for extension in format.extensions_str() {
+                assert_eq!(string_to_format(extension).unwrap(), format);
+            }
+        }
+    }
+
+    #[test]
+    fn convert_from_format() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("image.tga", 8, ImageFormat::Tga);
+        let renamed_path = tester.path_buf().join("image.dat");
+        fs::copy(&input_path, &renamed_path).unwrap();
+
+        let convert = |path: &Path, from_format: Option<&str>| {
+            let args = Args {
+                paths: vec![path.to_owned()],
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "png".to_owned(),
+                    from_format: from_format.map(str::to_owned),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+
+        convert(&input_path, None).unwrap();
+        fs::remove_file(input_path.with_extension("png")).unwrap();
+        let report = convert(&renamed_path, None).unwrap_err();
+        assert_eq!(
+            report.code().unwrap().to_string(),
+            "imy::undetermined_format"
+        );
+        convert(&renamed_path, Some("tga")).unwrap();
+        assert!(renamed_path.with_extension("png").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn convert_non_utf8_path() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let tester = Tester::new();
+        let file_name = OsStr::from_bytes(b"my_image_\xff.jpg");
+        let input_path = tester.save_empty_image(file_name, 32, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        assert!(input_path.with_extension("png").exists());
+    }
+
+    #[test]
+    fn unknown_format_diagnostic() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.png", 32, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![input_path],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "jepg".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        let report = run(context, args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::unknown_format");
+        assert!(report.help().unwrap().to_string().contains("'jpeg'"));
+    }
+
+    #[test]
+    fn info_png_template() {
+        const SIZE: u32 = 32;
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.png", SIZE, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Info {
+                format: Some("{width}x{height} {format} {size}".to_owned()),
+                output: OutputFormat::Text,
+                print0: false,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let size = fs::metadata(&input_path).unwrap().len();
+        let expected = format!("{SIZE}x{SIZE} png {size}\n");
+        let found = str::from_utf8(&stdout).unwrap();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn convert_keeps_16_bit() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("my_image.png");
+        image::ImageBuffer::<image::Rgb<u16>, _>::new(8, 8)
+            .save(&input_path)
+            .unwrap();
+
+        for (bit_depth, expected) in [(None, ColorType::Rgb16), (Some(8), ColorType::Rgb8)] {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                yes: true,
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "tiff".to_owned(),
+                    bit_depth,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+
+            let mut stdout = io::stdout();
+            let context = Context::new(&mut stdout);
+
+            run(context, args).unwrap();
+
+            let img = image::open(input_path.with_extension("tiff")).unwrap();
+            assert_eq!(img.color(), expected);
+        }
+    }
+
+    #[test]
+    fn convert_hdr_tonemaps() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("my_image.exr");
+        image::Rgb32FImage::from_pixel(4, 4, image::Rgb([4.0, 1.0, 0.0]))
+            .save(&input_path)
+            .unwrap();
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                tonemap: tonemap::Operator::Reinhard,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let img = image::open(input_path.with_extension("png"))
+            .unwrap()
+            .to_rgb8();
+        let pixel = img.get_pixel(0, 0);
+        assert!(pixel[0] < 255 && pixel[0] > pixel[1] && pixel[1] > pixel[2]);
+    }
+
+    #[test]
+    fn merge_hdr_exposures() {
+        const RADIANCE: f32 = 0.2;
+        let tester = Tester::new();
+        let exposure_times = vec![0.5, 1.0, 2.0];
+        let paths = exposure_times
+            .iter()
+            .enumerate()
+            .map(|(index, time)| {
+                let value = color::linear_to_srgb(RADIANCE * time);
+                let path = tester.path_buf().join(format!("exposure{index}.png"));
+                RgbImage::from_pixel(4, 4, image::Rgb([(value * 255.0).round() as u8; 3]))
+                    .save(&path)
+                    .unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+        let output = tester.path_buf().join("merged.exr");
+
+        let args = Args {
+            paths,
+            command: Some(Commands::MergeHdr(MergeHdrArgs {
+                output: output.clone(),
+                exposure_times,
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let merged = image::open(&output).unwrap().into_rgb32f();
+        let value = merged.get_pixel(0, 0)[0];
+        assert!((value - RADIANCE).abs() < 0.01, "{value}");
+    }
+
+    #[test]
+    fn exr_layers_and_channels() {
+        use exr::prelude::{AnyChannel, AnyChannels, FlatSamples, SmallVec, WritableImage};
+
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("render.exr");
+        let channel =
+            |name: &str, value: f32| AnyChannel::new(name, FlatSamples::F32(vec![value; 16]));
+        let channels = AnyChannels::sort(SmallVec::from_vec(vec![
+            channel("diffuse.R", 0.5),
+            channel("diffuse.G", 0.5),
+            channel("diffuse.B", 0.5),
+            channel("Z", 0.25),
+        ]));
+        exr::prelude::Image::from_channels((4, 4), channels)
+            .write()
+            .to_file(&input_path)
+            .unwrap();
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Info {
+                format: None,
+                output: OutputFormat::Text,
+                print0: false,
+            }),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        let context = Context::new(&mut stdout);
+        run(context, args).unwrap();
+        let found = str::from_utf8(&stdout).unwrap();
+        assert!(found.contains("  diffuse: B, G, R\n"));
+        assert!(found.contains("  (default): Z\n"));
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                tonemap: tonemap::Operator::Clamp,
+                channel: Some("Z".to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+        run(context, args).unwrap();
+
+        let img = image::open(input_path.with_extension("png")).unwrap();
+        let value = color::linear_to_srgb(0.25);
+        assert_eq!(
+            img.to_rgb8().get_pixel(0, 0)[0],
+            (value * 255.0).round() as u8
+        );
+    }
+
+    #[test]
+    fn convert_linear_to_srgb() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("linear.png");
+        image::GrayImage::from_pixel(4, 4, image::Luma([64]))
+            .save(&input_path)
+            .unwrap();
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            yes: true,
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "tiff".to_owned(),
+                input_colorspace: Some(color::ColorSpace::Linear),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let img = image::open(input_path.with_extension("tiff")).unwrap();
+        assert_eq!(img.color(), ColorType::L8);
+        let expected = color::linear_to_srgb(64.0 / 255.0) * 255.0;
+        assert_eq!(img.to_luma8().get_pixel(0, 0)[0], expected.round() as u8);
+    }
+
+    #[test]
+    fn convert_to_color_type() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("my_image.jpg", 8, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                color_type: Some(color::OutputColorType::L8),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let img = image::open(input_path.with_extension("png")).unwrap();
+        assert_eq!(img.color(), ColorType::L8);
+    }
+
+    #[test]
+    fn convert_resize_modes() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("wide.png");
+        RgbImage::new(16, 8).save(&input_path).unwrap();
+
+        let modes = [
+            (resize::Mode::Fit, (4, 2), ColorType::Rgb8),
+            (resize::Mode::Fill, (4, 4), ColorType::Rgb8),
+            (resize::Mode::Stretch, (4, 4), ColorType::Rgb8),
+            (resize::Mode::Cover, (8, 4), ColorType::Rgb8),
+            (resize::Mode::Contain, (4, 4), ColorType::Rgba8),
+        ];
+        for (mode, dimensions, color) in modes {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                yes: true,
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "tiff".to_owned(),
+                    resize: Some((4, 4)),
+                    mode,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+
+            let mut stdout = io::stdout();
+            let context = Context::new(&mut stdout);
+
+            run(context, args).unwrap();
+
+            let img = image::open(input_path.with_extension("tiff")).unwrap();
+            assert_eq!(img.dimensions(), dimensions, "{mode:?}");
+            assert_eq!(img.color(), color, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn convert_scale() {
+        let tester = Tester::new();
+        let small_path = tester.path_buf().join("small.png");
+        let large_path = tester.path_buf().join("large.png");
+        RgbImage::new(16, 8).save(&small_path).unwrap();
+        RgbImage::new(64, 32).save(&large_path).unwrap();
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            tester.path_buf().as_os_str(),
+            "convert".as_ref(),
+            "-t".as_ref(),
+            "bmp".as_ref(),
+            "--scale".as_ref(),
+            "50%".as_ref(),
+        ])
+        .unwrap();
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let small = image::open(small_path.with_extension("bmp")).unwrap();
+        let large = image::open(large_path.with_extension("bmp")).unwrap();
+        assert_eq!(small.dimensions(), (8, 4));
+        assert_eq!(large.dimensions(), (32, 16));
+        assert_eq!(resize::parse_scale("0.5"), Ok(0.5));
+        assert!(resize::parse_scale("0%").is_err());
+    }
+
+    #[test]
+    fn convert_auto_orient() {
+        let tester = Tester::new();
+        let jpeg = tester.path_buf().join("phone.jpg");
+        let img = RgbImage::from_fn(16, 8, |x, _| image::Rgb([if x < 8 { 255 } else { 0 }; 3]));
+        img.save(&jpeg).unwrap();
+        let metadata = metadata::Metadata {
+            exif: Some(exif_data()),
+            ..Default::default()
+        };
+        metadata::write(&jpeg, &metadata).unwrap();
+
+        let convert = |no_auto_orient| Args {
+            paths: vec![jpeg.clone()],
+            yes: true,
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                no_auto_orient,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), convert(false)).unwrap();
+        // The orientation 6 rotates by 90 degrees clockwise, which moves the left half to the top
+        let output = image::open(jpeg.with_extension("png")).unwrap().to_luma8();
+        assert_eq!(output.dimensions(), (8, 16));
+        assert!(output.get_pixel(4, 4)[0] > 200 && output.get_pixel(4, 12)[0] < 50);
+
+        run(Context::new(&mut stdout), convert(true)).unwrap();
+        let output = image::open(jpeg.with_extension("png")).unwrap();
+        assert_eq!(output.dimensions(), (16, 8));
+    }
+
+    #[test]
+    fn convert_keep_metadata() {
+        let tester = Tester::new();
+        let jpeg = tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+        let metadata = metadata::Metadata {
+            exif: Some(exif_data()),
+            icc: Some(vec![7; 100]),
+            text: [("comment".to_owned(), "sunset".to_owned())].into(),
+        };
+        metadata::write(&jpeg, &metadata).unwrap();
+
+        let args = Args {
+            paths: vec![jpeg.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                keep_metadata: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let kept = metadata::read(&jpeg.with_extension("png")).unwrap();
+        assert_eq!((&kept.icc, &kept.text), (&metadata.icc, &metadata.text));
+        let exif = exif::parse(&kept.exif.unwrap()).unwrap();
+        assert_eq!(
+            (exif.make.as_deref(), exif.orientation),
+            (Some("Canon"), Some(1))
+        );
+    }
+
+    /// ICC profile with linear tone curves and the red and green primaries of sRGB swapped.
+    fn swapped_primaries_profile() -> Vec<u8> {
+        let xyz = |values: [f32; 3]| {
+            let mut data = b"XYZ \0\0\0\0".to_vec();
+            for value in values {
+                data.extend(((value * 65536.0).round() as i32).to_be_bytes());
+            }
+            data
+        };
+        // A curve without entries is the identity
+        let curve = b"curv\0\0\0\0\0\0\0\0".to_vec();
+        let tags = [
+            (b"rXYZ", xyz([0.3851, 0.7169, 0.0971])),
+            (b"gXYZ", xyz([0.4361, 0.2225, 0.0139])),
+            (b"bXYZ", xyz([0.1431, 0.0606, 0.7142])),
+            (b"rTRC", curve.clone()),
+            (b"gTRC", curve.clone()),
+            (b"bTRC", curve),
+        ];
+        let mut header = vec![0; 128];
+        header[16..20].copy_from_slice(b"RGB ");
+        header[20..24].copy_from_slice(b"XYZ ");
+        header[36..40].copy_from_slice(b"acsp");
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut data = Vec::new();
+        for (signature, tag) in &tags {
+            let offset = 128 + 4 + tags.len() * 12 + data.len();
+            table.extend(*signature);
+            table.extend((offset as u32).to_be_bytes());
+            table.extend((tag.len() as u32).to_be_bytes());
+            data.extend(tag);
+        }
+        let mut profile = [header, table, data].concat();
+        let size = profile.len() as u32;
+        profile[..4].copy_from_slice(&size.to_be_bytes());
+        profile
+    }
+
+    #[test]
+    fn convert_color_profiles() {
+        let tester = Tester::new();
+        let input = tester.path_buf().join("wide.png");
+        RgbImage::from_pixel(4, 4, image::Rgb([255, 0, 0]))
+            .save(&input)
+            .unwrap();
+        let metadata = metadata::Metadata {
+            icc: Some(swapped_primaries_profile()),
+            ..Default::default()
+        };
+        metadata::write(&input, &metadata).unwrap();
+
+        let convert = |convert_to_srgb| Args {
+            paths: vec![input.clone()],
+            yes: true,
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "jpeg".to_owned(),
+                convert_to_srgb,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let output = input.with_extension("jpeg");
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), convert(false)).unwrap();
+        assert_eq!(metadata::read(&output).unwrap().icc, metadata.icc);
+        let pixel = image::open(&output).unwrap().to_rgb8()[(2, 2)];
+        assert!(pixel[0] > 240 && pixel[1] < 15, "{pixel:?}");
+
+        run(Context::new(&mut stdout), convert(true)).unwrap();
+        assert_eq!(metadata::read(&output).unwrap().icc, None);
+        let pixel = image::open(&output).unwrap().to_rgb8()[(2, 2)];
+        assert!(pixel[0] < 15 && pixel[1] > 240, "{pixel:?}");
+    }
+
+    #[test]
+    fn convert_flattens_alpha() {
+        let tester = Tester::new();
+        let input = tester.path_buf().join("logo.png");
+        let img = image::RgbaImage::from_fn(16, 16, |x, _| {
+            image::Rgba([0, 0, 255, if x < 8 { 0 } else { 255 }])
+        });
+        img.save(&input).unwrap();
+
+        let convert = |background: Option<&str>| Args {
+            paths: vec![input.clone()],
+            yes: true,
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "jpeg".to_owned(),
+                background: background.map(|color| color::parse_color(color).unwrap()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let output = input.with_extension("jpeg");
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), convert(None)).unwrap();
+        let flattened = image::open(&output).unwrap().to_rgb8();
+        assert!(flattened[(2, 8)].0.iter().all(|channel| *channel > 240));
+        assert!(flattened[(13, 8)][2] > 240 && flattened[(13, 8)][0] < 15);
+
+        run(Context::new(&mut stdout), convert(Some("#ff0000"))).unwrap();
+        let flattened = image::open(&output).unwrap().to_rgb8();
+        assert!(flattened[(2, 8)][0] > 240 && flattened[(2, 8)][2] < 15);
+    }
+
+    #[test]
+    fn convert_quality() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("noise.png");
+        image::ImageBuffer::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 37 + y * 91) as u8, (x * y) as u8, (x ^ y) as u8 * 4])
+        })
+        .save(&input_path)
+        .unwrap();
+
+        let jpeg_size = |quality| {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                yes: true,
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "jpeg".to_owned(),
+                    quality: Some(quality),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args).unwrap();
+            fs::metadata(input_path.with_extension("jpeg"))
+                .unwrap()
+                .len()
+        };
+        assert!(jpeg_size(10) < jpeg_size(95));
+    }
+
+    #[test]
+    fn convert_out_dir() {
+        let tester = Tester::new();
+        let input_dir = tester.path_buf().join("originals");
+        tester.save_empty_image("originals/a.jpg", 8, ImageFormat::Jpeg);
+        tester.save_empty_image("originals/sub/b.jpg", 8, ImageFormat::Jpeg);
+        let out_dir = input_dir.join("converted");
+
+        for _ in 0..2 {
+            let args = Args {
+                paths: vec![input_dir.clone()],
+                yes: true,
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "png".to_owned(),
+                    out_dir: Some(out_dir.clone()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args).unwrap();
+        }
+
+        assert!(out_dir.join("a.png").exists());
+        assert!(out_dir.join("sub/b.png").exists());
+        assert!(!out_dir.join("converted").exists());
+        assert!(!input_dir.join("a.png").exists());
+    }
+
+    #[test]
+    fn convert_move_original() {
+        let tester = Tester::new();
+        let input_dir = tester.path_buf().join("library");
+        tester.save_empty_image("library/sub/a.png", 8, ImageFormat::Png);
+        let archive_dir = tester.path_buf().join("archive");
+
+        let args = Args {
+            paths: vec![input_dir.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "webp".to_owned(),
+                move_original_to: Some(archive_dir.clone()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        assert!(input_dir.join("sub/a.webp").exists());
+        assert!(!input_dir.join("sub/a.png").exists());
+        assert!(archive_dir.join("sub/a.png").exists());
+
+        let args = || Args {
+            paths: vec![input_dir.join("sub/a.webp")],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "png".to_owned(),
+                delete_original: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut context = Context::new(&mut stdout);
+        context.interactive = true;
+        context.answer = Some(false);
+        let report = run(context, args()).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::aborted");
+        assert!(!input_dir.join("sub/a.png").exists());
+        assert!(input_dir.join("sub/a.webp").exists());
+
+        run(Context::new(&mut stdout), args()).unwrap();
+
+        assert!(input_dir.join("sub/a.png").exists());
+        assert!(!input_dir.join("sub/a.webp").exists());
+    }
+
+    // Listing the trash isn't supported on macOS
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn convert_trashes_original() {
+        let tester = Tester::new();
+        let input = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let args = Args {
+            paths: vec![input.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "webp".to_owned(),
+                delete_original: true,
+                ..Default::default()
+            })),
+            trash: true,
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        assert!(tester.path_buf().join("a.webp").exists());
+        assert!(!input.exists());
+        let parent = input.parent().unwrap().canonicalize().unwrap();
+        let trashed = trash::os_limited::list()
+            .unwrap()
+            .into_iter()
+            .filter(|item| item.name == "a.png" && item.original_parent == parent)
+            .collect::<Vec<_>>();
+        assert_eq!(trashed.len(), 1);
+        trash::os_limited::purge_all(trashed).unwrap();
+    }
+
+    #[test]
+    fn convert_stream_png_to_bmp() {
+        let mut input = io::Cursor::new(Vec::new());
+        RgbImage::new(4, 2)
+            .write_to(&mut input, ImageFormat::Png)
+            .unwrap();
+
+        let args = Args::try_parse_from(["imy", "-", "convert", "-t", "bmp"]).unwrap();
+        let Some(Commands::Convert(convert_args)) = args.command else {
+            panic!("expected convert");
+        };
+        let mut stdout = Vec::new();
+        convert_stream(
+            &mut Context::new(&mut stdout),
+            input.get_ref().as_slice(),
+            &convert_args,
+        )
+        .unwrap();
+
+        let img = image::load_from_memory_with_format(&stdout, ImageFormat::Bmp).unwrap();
+        assert_eq!(img.dimensions(), (4, 2));
+
+        let convert_args = ConvertOptions {
+            target_format: "png".to_owned(),
+            ..Default::default()
+        };
+        let report =
+            convert_stream(&mut Context::new(&mut stdout), &b"raw"[..], &convert_args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::stdin_format");
+    }
+
+    #[test]
+    fn convert_max_size() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("noise.png");
+        image::ImageBuffer::from_fn(128, 128, |x, y| {
+            image::Rgb([(x * 37 + y * 91) as u8, (x * y) as u8, ((x ^ y) * 2) as u8])
+        })
+        .save(&input_path)
+        .unwrap();
+
+        let convert = |max_size| {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                yes: true,
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "jpeg".to_owned(),
+                    max_size: Some(max_size),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        convert(6000).unwrap();
+        let size = fs::metadata(input_path.with_extension("jpeg"))
+            .unwrap()
+            .len();
+        assert!(size <= 6000);
+        assert!(size > 3000);
+
+        let report = convert(10).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::size_budget");
+        assert_eq!(parse_file_size("200KB"), Ok(200_000));
+        assert_eq!(parse_file_size("1.5 MiB"), Ok(1_572_864));
+        assert_eq!(parse_file_size("512"), Ok(512));
+    }
+
+    #[test]
+    fn convert_ico_sizes() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("logo.png");
+        RgbImage::new(64, 32).save(&input_path).unwrap();
+
+        let convert = |target_format: &str| {
+            let args = Args::try_parse_from([
+                "imy".as_ref(),
+                input_path.as_os_str(),
+                "convert".as_ref(),
+                "-t".as_ref(),
+                target_format.as_ref(),
+                "--ico-sizes".as_ref(),
+                "48,16,32".as_ref(),
+                "-y".as_ref(),
+            ])
+            .unwrap();
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        convert("ico").unwrap();
+        let data = fs::read(input_path.with_extension("ico")).unwrap();
+        assert_eq!(u16::from_le_bytes([data[4], data[5]]), 3);
+        // Each directory entry of 16 bytes starts with the width and the height
+        let sizes = (0..3).map(|i| (data[6 + i * 16], data[7 + i * 16]));
+        assert_eq!(sizes.collect::<Vec<_>>(), [(16, 16), (32, 32), (48, 48)]);
+        let img = image::open(input_path.with_extension("ico")).unwrap();
+        assert_eq!(img.dimensions(), (48, 48));
+
+        let report = convert("png").unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::ico_sizes");
+    }
+
+    #[test]
+    fn convert_dither_depth() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("gradient.png");
+        image::ImageBuffer::from_fn(64, 1, |x, _| image::Luma([x as u16 * 4]))
+            .save(&input_path)
+            .unwrap();
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "bmp".to_owned(),
+                dither_depth: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        // Rounding alone maps the whole gradient to 0 and 1, dithering keeps its mean.
+        let img = image::open(input_path.with_extension("bmp"))
+            .unwrap()
+            .to_luma8();
+        let sum = img.pixels().map(|pixel| pixel[0] as u32).sum::<u32>();
+        let expected = (0..64).map(|x| x as f32 * 4.0 / 257.0).sum::<f32>();
+        assert!((sum as f32 - expected).abs() <= 1.0, "{sum} {expected}");
+    }
+
+    #[cfg(feature = "stitch")]
+    #[test]
+    fn stitch_overlapping_shots() {
+        let tester = Tester::new();
+        let scene = RgbImage::from_fn(160, 40, |x, y| {
+            image::Rgb([
+                (x * 37 % 251) as u8,
+                (y * 53 % 241) as u8,
+                ((x ^ y) * 7) as u8,
+            ])
+        });
+        let left = tester.path_buf().join("left.png");
+        let right = tester.path_buf().join("right.png");
+        image::imageops::crop_imm(&scene, 0, 0, 100, 40)
+            .to_image()
+            .save(&left)
+            .unwrap();
+        image::imageops::crop_imm(&scene, 60, 0, 100, 40)
+            .to_image()
+            .save(&right)
+            .unwrap();
+        let output = tester.path_buf().join("pano.png");
+
+        let args = Args {
+            paths: vec![left, right],
+            command: Some(Commands::Stitch(StitchArgs {
+                output: output.clone(),
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let panorama = image::open(&output).unwrap().to_rgb8();
+        assert_eq!(panorama.dimensions(), (160, 40));
+        assert_eq!(panorama, scene);
+    }
+
+    #[test]
+    fn generate_checkerboard() {
+        let tester = Tester::new();
+        let output = tester.path_buf().join("test.png");
+
+        let args = Args {
+            command: Some(Commands::Generate(GenerateArgs {
+                pattern: generate::Pattern::Checkerboard,
+                size: (100, 50),
+                color: image::Rgba([255, 0, 0, 255]),
+                background: image::Rgba([0, 0, 0, 255]),
+                text: None,
+                seed: 1,
+                output: output.clone(),
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let img = image::open(&output).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (100, 50));
+        assert_eq!(img.get_pixel(0, 0), &image::Rgb([255, 0, 0]));
+        assert_eq!(img.get_pixel(32, 0), &image::Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn generate_placeholder_with_text() {
+        let tester = Tester::new();
+        let output = tester.path_buf().join("placeholder.png");
+
+        let args = Args {
+            command: Some(Commands::Generate(GenerateArgs {
+                pattern: generate::Pattern::Solid,
+                size: (800, 600),
+                color: image::Rgba([0, 0, 0, 255]),
+                background: image::Rgba([204, 204, 204, 255]),
+                text: Some("800x600".to_owned()),
+                seed: 1,
+                output: output.clone(),
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        run(context, args).unwrap();
+
+        let img = image::open(&output).unwrap().to_rgb8();
+        assert_eq!(img.get_pixel(0, 0), &image::Rgb([204, 204, 204]));
+        assert!(img.pixels().any(|pixel| pixel == &image::Rgb([0, 0, 0])));
+    }
+
+    #[test]
+    fn stego_round_trip() {
+        let tester = Tester::new();
+        let cover = tester.path_buf().join("cover.png");
+        let secret = tester.path_buf().join("secret.txt");
+        let stego = tester.path_buf().join("stego.png");
+        generate::generate(
+            generate::Pattern::Noise,
+            (64, 64),
+            &generate::Style {
+                color: image::Rgba([255, 255, 255, 255]),
+                background: image::Rgba([0, 0, 0, 255]),
+                seed: 1,
+            },
+        )
+        .save(&cover)
+        .unwrap();
+        fs::write(&secret, "meet me at midnight").unwrap();
+
+        let embed = |passphrase: &str| Args {
+            paths: vec![cover.clone()],
+            command: Some(Commands::Stego(StegoCommands::Embed(StegoEmbedArgs {
+                file: secret.clone(),
+                output: Some(stego.clone()),
+                passphrase: Some(passphrase.to_owned()),
+            }))),
+            yes: true,
+            ..Default::default()
+        };
+        let extract = |passphrase: &str| Args {
+            paths: vec![stego.clone()],
+            command: Some(Commands::Stego(StegoCommands::Extract(StegoExtractArgs {
+                output: None,
+                passphrase: Some(passphrase.to_owned()),
+            }))),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), embed("secret")).unwrap();
+        assert_ne!(fs::read(&cover).unwrap(), fs::read(&stego).unwrap());
+
+        run(Context::new(&mut stdout), extract("secret")).unwrap();
+        assert_eq!(stdout, b"meet me at midnight");
+
+        let mut stdout = Vec::new();
+        let quiet = Args {
+            quiet: true,
+            ..extract("secret")
+        };
+        run(Context::new(&mut stdout), quiet).unwrap();
+        assert_eq!(stdout, b"meet me at midnight");
+
+        let result = run(Context::new(&mut Vec::new()), extract("wrong"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn convert_verify_pixels() {
+        let tester = Tester::new();
+        let path = tester.path_buf().join("image.png");
+        image::RgbImage::from_fn(8, 8, |x, y| image::Rgb([x as u8 * 30, y as u8 * 30, 7]))
+            .save(&path)
+            .unwrap();
+
+        let args = |target_format: &str, bit_depth| Args {
+            paths: vec![path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: target_format.to_owned(),
+                bit_depth,
+                verify_pixels: true,
+                ..Default::default()
+            })),
+            yes: true,
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args("qoi", None)).unwrap();
+        run(Context::new(&mut stdout), args("tiff", Some(16))).unwrap();
+        assert!(run(Context::new(&mut stdout), args("jpeg", None)).is_err());
+    }
+
+    #[cfg(feature = "ffmpeg")]
+    #[test]
+    fn frames_durations() {
+        assert_eq!(ffmpeg::parse_duration("1s"), Ok(1.0));
+        assert_eq!(ffmpeg::parse_duration("500ms"), Ok(0.5));
+        assert_eq!(ffmpeg::parse_duration("2m"), Ok(120.0));
+        assert_eq!(ffmpeg::parse_duration("0.25"), Ok(0.25));
+        assert!(ffmpeg::parse_duration("0s").is_err());
+        assert!(ffmpeg::parse_duration("soon").is_err());
+    }
+
+    #[test]
+    fn convert_newer_than_output() {
+        let tester = Tester::new();
+        let old = tester.save_empty_image("old.png", 8, ImageFormat::Png);
+        let new = tester.save_empty_image("new.png", 8, ImageFormat::Png);
+        fs::write(old.with_extension("bmp"), "up to date").unwrap();
+        let past = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "bmp".to_owned(),
+                newer_than_output: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        assert_eq!(fs::read(old.with_extension("bmp")).unwrap(), b"up to date");
+        assert!(is_image_with_type(&new.with_extension("bmp"), ImageFormat::Bmp).unwrap());
+    }
+
+    #[test]
+    fn convert_incremental() {
+        let tester = Tester::new();
+        let path = tester.save_empty_image("image.png", 8, ImageFormat::Png);
+        let output = path.with_extension("bmp");
+
+        let args = |rewrite_all| Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "bmp".to_owned(),
+                incremental: true,
+                rewrite_all,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        run(Context::new(&mut Vec::new()), args(false)).unwrap();
+        assert!(tester.path_buf().join(cache::FILE_NAME).exists());
+        fs::write(&output, "cached").unwrap();
+        run(Context::new(&mut Vec::new()), args(false)).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"cached");
+
+        // A changed source is converted again without asking to overwrite its output
+        image::RgbImage::new(4, 4).save(&path).unwrap();
+        run(Context::new(&mut Vec::new()), args(false)).unwrap();
+        assert_eq!(image::image_dimensions(&output).unwrap(), (4, 4));
+
+        fs::write(&output, "cached").unwrap();
+        run(Context::new(&mut Vec::new()), args(true)).unwrap();
+        assert!(is_image_with_type(&output, ImageFormat::Bmp).unwrap());
+    }
+
+    #[test]
+    fn convert_resume() {
+        let tester = Tester::new();
+        let good = tester.save_empty_image("good.png", 8, ImageFormat::Png);
+        let broken = tester.path_buf().join("broken.png");
+        fs::write(&broken, "not an image").unwrap();
+
+        let args = |resume, resize| Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "bmp".to_owned(),
+                keep_going: true,
+                resume,
+                resize,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        assert!(run(Context::new(&mut Vec::new()), args(false, None)).is_err());
+        let journal = tester.path_buf().join(journal::FILE_NAME);
+        assert!(journal.exists());
+
+        let result = run(Context::new(&mut Vec::new()), args(true, Some((4, 4))));
+        assert!(matches!(
+            result.unwrap_err().downcast_ref(),
+            Some(Error::JournalOptions { .. })
+        ));
+
+        fs::write(good.with_extension("bmp"), "converted").unwrap();
+        tester.save_empty_image("broken.png", 8, ImageFormat::Png);
+        run(Context::new(&mut Vec::new()), args(true, None)).unwrap();
+        assert_eq!(fs::read(good.with_extension("bmp")).unwrap(), b"converted");
+        assert!(is_image_with_type(&broken.with_extension("bmp"), ImageFormat::Bmp).unwrap());
+        assert!(!journal.exists());
+    }
+
+    #[test]
+    fn convert_files_from() {
+        let tester = Tester::new();
+        let first = tester.save_empty_image("a/first.png", 8, ImageFormat::Png);
+        let second = tester.save_empty_image("b/second with space.png", 8, ImageFormat::Png);
+        let unlisted = tester.save_empty_image("a/unlisted.png", 8, ImageFormat::Png);
+        let list = tester.path_buf().join("list");
+        fs::write(
+            &list,
+            format!("{}\0{}\0", first.display(), second.display()),
+        )
+        .unwrap();
+
+        let args = Args {
+            files_from: Some(list),
+            paths: vec![PathBuf::from("bmp")],
+            ..Default::default()
+        };
+
+        run(Context::new(&mut Vec::new()), args).unwrap();
+
+        assert!(is_image_with_type(&first.with_extension("bmp"), ImageFormat::Bmp).unwrap());
+        assert!(is_image_with_type(&second.with_extension("bmp"), ImageFormat::Bmp).unwrap());
+        assert!(!unlisted.with_extension("bmp").exists());
+    }
+
+    #[test]
+    fn read_file_list_lines() {
+        let paths = read_file_list("a.png\r\n\nb c.png\n".as_bytes()).unwrap();
+        assert_eq!(paths, [PathBuf::from("a.png"), PathBuf::from("b c.png")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_file_list_non_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let paths = read_file_list(&b"./n\xff.png\0./a.png\0"[..]).unwrap();
+        let expected = [
+            PathBuf::from(OsStr::from_bytes(b"./n\xff.png")),
+            PathBuf::from("./a.png"),
+        ];
+        assert_eq!(paths, expected);
+
+        let paths = read_file_list(&b"n\xff.png\n"[..]).unwrap();
+        assert_eq!(paths, [PathBuf::from(OsStr::from_bytes(b"n\xff.png"))]);
+    }
+
+    #[test]
+    fn image_files_sorted() {
+        let tester = Tester::new();
+        for name in ["c.png", "a.png", "b/b.png", "b/a.png", "ab.png"] {
+            tester.save_empty_image(name, 1, ImageFormat::Png);
+        }
+
+        let names = image_files(&WalkOptions::default(), &tester.path_buf())
+            .into_iter()
+            .map(|path| {
+                path.strip_prefix(tester.path_buf())
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.png", "ab.png", "b/a.png", "b/b.png", "c.png"]);
+    }
+
+    #[test]
+    fn image_files_include_exclude() {
+        let tester = Tester::new();
+        tester.save_empty_image("a.jpg", 1, ImageFormat::Jpeg);
+        tester.save_empty_image("b.png", 1, ImageFormat::Png);
+        tester.save_empty_image("sub/c.jpg", 1, ImageFormat::Jpeg);
+        tester.save_empty_image("thumbs/d.jpg", 1, ImageFormat::Jpeg);
+
+        let args = Args::try_parse_from([
+            "imy",
+            "info",
+            "--include",
+            "*.jpg",
+            "--exclude",
+            "thumbs/**",
+        ])
+        .unwrap();
+        let names = image_files(&args.walk, &tester.path_buf())
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.jpg", "c.jpg"]);
+        assert!(Args::try_parse_from(["imy", "info", "--include", "a{"]).is_err());
+    }
+
+    #[test]
+    fn image_files_no_ignore_and_hidden() {
+        let tester = Tester::new();
+        tester.save_empty_image("a.png", 1, ImageFormat::Png);
+        tester.save_empty_image("generated/b.png", 1, ImageFormat::Png);
+        tester.save_empty_image(".c.png", 1, ImageFormat::Png);
+        fs::write(tester.path_buf().join(".ignore"), "generated/\n").unwrap();
+
+        let count = |walk: WalkOptions| image_files(&walk, &tester.path_buf()).len();
+        assert_eq!(count(WalkOptions::default()), 1);
+        let no_ignore = WalkOptions {
+            no_ignore: true,
+            ..Default::default()
+        };
+        assert_eq!(count(no_ignore), 2);
+        let hidden = WalkOptions {
+            hidden: true,
+            ..Default::default()
+        };
+        assert_eq!(count(hidden), 2);
+    }
+
+    #[test]
+    fn image_files_max_depth() {
+        let tester = Tester::new();
+        tester.save_empty_image("a.png", 1, ImageFormat::Png);
+        tester.save_empty_image("b/b.png", 1, ImageFormat::Png);
+        tester.save_empty_image("b/c/c.png", 1, ImageFormat::Png);
+
+        let count = |walk: WalkOptions| image_files(&walk, &tester.path_buf()).len();
+        let max_depth = |max_depth| WalkOptions {
+            max_depth: Some(max_depth),
+            ..Default::default()
+        };
+        assert_eq!(count(max_depth(1)), 1);
+        assert_eq!(count(max_depth(2)), 2);
+        assert_eq!(count(WalkOptions::default()), 3);
+        let no_recursive = WalkOptions {
+            no_recursive: true,
+            ..Default::default()
+        };
+        assert_eq!(count(no_recursive), 1);
+    }
+
+    #[test]
+    fn convert_requires_path() {
+        let args = Args {
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "webp".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+
+        let report = run(context, args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::path_required");
+    }
+
+    #[test]
+    fn info_directory() {
+        let tester = Tester::new();
+        tester.save_empty_image("b.png", 8, ImageFormat::Png);
+        tester.save_empty_image("a.jpg", 8, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Info {
+                format: None,
+                output: OutputFormat::Text,
+                print0: false,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        let context = Context::new(&mut stdout);
+        run(context, args).unwrap();
+
+        let output = String::from_utf8(stdout).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("a.jpg jpeg 8x8 rgb 8-bit"));
+        assert!(lines[1].contains("b.png png 8x8 rgb 8-bit"));
+        assert_eq!(lines[2], "2 image(s): 1 jpeg, 1 png");
+    }
+
+    #[test]
+    fn info_json() {
+        let tester = Tester::new();
+        tester.save_empty_image("b.png", 8, ImageFormat::Png);
+        tester.save_empty_image("a.jpg", 4, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Info {
+                format: None,
+                output: OutputFormat::Json,
+                print0: false,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        let context = Context::new(&mut stdout);
+        run(context, args).unwrap();
+
+        let infos: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        let infos = infos.as_array().unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0]["format"], "jpeg");
+        assert_eq!(infos[0]["width"], 4);
+        assert_eq!(infos[1]["format"], "png");
+        assert_eq!(infos[1]["height"], 8);
+        assert_eq!(infos[1]["color"], "rgb");
+        assert_eq!(infos[1]["bit_depth"], 8);
+        assert!(infos[1]["size"].as_u64().unwrap() > 0);
+        assert!(infos[1].get("layers").is_none());
+    }
+
+    #[test]
+    fn convert_shorthand_and_aliases() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+
+        let args =
+            Args::try_parse_from(["imy".as_ref(), input_path.as_os_str(), "png".as_ref()]).unwrap();
+        assert!(args.command.is_none());
+
+        let mut stdout = io::stdout();
+        let context = Context::new(&mut stdout);
+        run(context, args).unwrap();
+        assert!(is_image_with_type(&input_path.with_extension("png"), ImageFormat::Png).unwrap());
+
+        let args = Args::try_parse_from(["imy", "photo.jpg", "c", "-t", "bmp"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::Convert(_))));
+        let args = Args::try_parse_from(["imy", "photo.jpg", "ls"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::Info { .. })));
+    }
+
+    #[test]
+    fn convert_geotiff() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("map.tif");
+        let tags = geotiff::GeoTags {
+            pixel_scale: Some(vec![10.0, 10.0, 0.0]),
+            tiepoint: Some(vec![0.0, 0.0, 0.0, 500000.0, 4000000.0, 0.0]),
+            key_directory: Some(vec![1, 1, 0, 1, 3072, 0, 1, 32633]),
+            ..Default::default()
+        };
+        let img = DynamicImage::ImageLuma8(image::GrayImage::new(4, 4));
+        geotiff::save_tiff(&img, &input_path, &tags).unwrap();
+
+        let convert = |target_format: &str| Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: target_format.to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), convert("tiff")).unwrap();
+        run(Context::new(&mut stdout), convert("png")).unwrap();
+
+        let output_tags = geotiff::read(&input_path.with_extension("tiff")).unwrap();
+        assert_eq!(output_tags, Some(tags));
+        let world_file = fs::read_to_string(tester.path_buf().join("map.pgw")).unwrap();
+        assert_eq!(world_file, "10\n0\n0\n-10\n500005\n3999995\n");
+    }
+
+    #[test]
+    fn compare_directories() {
+        let tester = Tester::new();
+        tester.save_empty_image("a/same.png", 8, ImageFormat::Png);
+        tester.save_empty_image("b/same.qoi", 8, ImageFormat::Qoi);
+        tester.save_empty_image("a/sub/resized.png", 8, ImageFormat::Png);
+        tester.save_empty_image("b/sub/resized.png", 16, ImageFormat::Png);
+        tester.save_empty_image("a/missing.png", 8, ImageFormat::Png);
+        tester.save_empty_image("b/extra.png", 8, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![tester.path_buf().join("a")],
+            command: Some(Commands::Compare(CompareArgs {
+                other: tester.path_buf().join("b"),
+                recursive: true,
+                ignore_extension: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        let report = run(Context::new(&mut stdout), args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::differences");
+
+        let output = String::from_utf8(stdout).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("extra") && lines[0].ends_with("extra.png"));
+        assert!(lines[1].starts_with("missing") && lines[1].ends_with("missing.png"));
+        assert!(lines[2].ends_with("size 8x8 vs 16x16"));
+        assert_eq!(lines[3], "1 identical, 1 different, 1 missing, 1 extra");
+    }
+
+    #[test]
+    fn compare_writes_diff_image() {
+        let tester = Tester::new();
+        let a = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let b = tester.path_buf().join("b.png");
+        let mut img = RgbImage::new(8, 8);
+        img.put_pixel(2, 3, image::Rgb([255, 255, 255]));
+        img.save(&b).unwrap();
+        let diff = tester.path_buf().join("diff.png");
+
+        let args = Args {
+            paths: vec![a],
+            command: Some(Commands::Compare(CompareArgs {
+                other: b,
+                diff: Some(diff.clone()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        let report = run(Context::new(&mut stdout), args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::differences");
+
+        let diff = image::open(diff).unwrap().to_rgba8();
+        assert_eq!(diff.get_pixel(2, 3).0, [255, 0, 0, 255]);
+        assert_eq!(diff.get_pixel(0, 0).0, [191, 191, 191, 255]);
+    }
+
+    #[test]
+    fn compare_quality_metrics_as_json() {
+        let tester = Tester::new();
+        let a = tester.save_empty_image("a.png", 16, ImageFormat::Png);
+        let b = tester.path_buf().join("b.png");
+        let mut img = RgbImage::new(16, 16);
+        img.put_pixel(5, 5, image::Rgb([64, 64, 64]));
+        img.save(&b).unwrap();
+
+        let args = Args {
+            paths: vec![a],
+            command: Some(Commands::Compare(CompareArgs {
+                other: b,
+                output: OutputFormat::Json,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        let report = run(Context::new(&mut stdout), args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::differences");
+
+        let json = serde_json::from_slice::<serde_json::Value>(&stdout).unwrap();
+        assert_eq!(json["different"], 1);
+        let file = &json["files"][0];
+        assert_eq!(file["status"], "different");
+        assert_eq!(file["differing_pixels"], 1);
+        let psnr = file["psnr"].as_f64().unwrap();
+        assert!(psnr > 30.0 && psnr < 40.0, "{psnr}");
+        let ssim = file["ssim"].as_f64().unwrap();
+        assert!(ssim > 0.5 && ssim < 1.0, "{ssim}");
+    }
+
+    #[test]
+    fn hash_near_duplicates() {
+        let tester = Tester::new();
+        let mut img = RgbImage::new(64, 64);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let value = ((x / 8 * 37 + y / 8 * 91) % 256) as u8;
+            *pixel = image::Rgb([value, value / 2, 255 - value]);
+        }
+        img.save(tester.path_buf().join("a.png")).unwrap();
+        image::imageops::resize(&img, 48, 48, image::imageops::FilterType::Triangle)
+            .save(tester.path_buf().join("b.jpg"))
+            .unwrap();
+        image::imageops::flip_horizontal(&img)
+            .save(tester.path_buf().join("c.png"))
+            .unwrap();
+
+        for algorithm in [
+            HashAlgorithm::Perceptual,
+            HashAlgorithm::Difference,
+            HashAlgorithm::Average,
+        ] {
+            let args = Args {
+                paths: vec![tester.path_buf()],
+                command: Some(Commands::Hash(HashArgs {
+                    algorithm,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = Vec::new();
+            run(Context::new(&mut stdout), args).unwrap();
+
+            let output = String::from_utf8(stdout).unwrap();
+            let hashes = output
+                .lines()
+                .map(|line| line.split_once("  ").unwrap())
+                .map(|(hash, path)| (hash.to_owned(), path.to_owned()))
+                .collect::<Vec<_>>();
+            assert_eq!(hashes.len(), 3);
+            assert!(hashes[0].1.ends_with("a.png") && hashes[0].0.len() == 16);
+            let distance = |a: &str, b: &str| {
+                let (a, b) = (
+                    u64::from_str_radix(a, 16).unwrap(),
+                    u64::from_str_radix(b, 16).unwrap(),
+                );
+                (a ^ b).count_ones()
+            };
+            assert!(distance(&hashes[0].0, &hashes[1].0) <= 4, "{algorithm:?}");
+            assert!(distance(&hashes[0].0, &hashes[2].0) > 16, "{algorithm:?}");
+        }
+    }
+
+    #[test]
+    fn dedupe_deletes_duplicates() {
+        let tester = Tester::new();
+        let img = RgbImage::from_fn(64, 64, |x, y| {
+            let value = ((x / 8 * 37 + y / 8 * 91) % 256) as u8;
+            image::Rgb([value, value / 2, 255 - value])
+        });
+        img.save(tester.path_buf().join("a.png")).unwrap();
+        fs::copy(
+            tester.path_buf().join("a.png"),
+            tester.path_buf().join("b.png"),
+        )
+        .unwrap();
+        image::imageops::resize(&img, 48, 48, image::imageops::FilterType::Triangle)
+            .save(tester.path_buf().join("c.jpg"))
+            .unwrap();
+        image::imageops::flip_horizontal(&img)
+            .save(tester.path_buf().join("d.png"))
+            .unwrap();
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            yes: true,
+            command: Some(Commands::Dedupe(DedupeArgs {
+                delete_duplicates: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let output = String::from_utf8(stdout).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("near ") && lines[0].ends_with("a.png"));
+        assert_eq!(lines[3], "1 group(s) with 2 duplicate(s)");
+        assert!(tester.path_buf().join("a.png").exists());
+        assert!(!tester.path_buf().join("b.png").exists());
+        assert!(!tester.path_buf().join("c.jpg").exists());
+        assert!(tester.path_buf().join("d.png").exists());
+    }
+
+    #[test]
+    fn dedupe_print0() {
+        let tester = Tester::new();
+        let a = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let b = tester.path_buf().join("b\nc.png");
+        fs::copy(&a, &b).unwrap();
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Dedupe(DedupeArgs {
+                exact: true,
+                print0: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        assert_eq!(stdout, format!("{}\0", b.display()).as_bytes());
+    }
+
+    #[test]
+    fn info_print0() {
+        let tester = Tester::new();
+        let a = tester.save_empty_image("a b.png", 8, ImageFormat::Png);
+        let b = tester.save_empty_image("b\nc.png", 4, ImageFormat::Png);
+
+        let info = |format: Option<&str>| Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Info {
+                format: format.map(str::to_owned),
+                output: OutputFormat::Text,
+                print0: true,
+            }),
+            ..Default::default()
+        };
+
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), info(None)).unwrap();
+        assert_eq!(
+            stdout,
+            format!("{}\0{}\0", a.display(), b.display()).as_bytes()
+        );
+
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), info(Some("{width}"))).unwrap();
+        assert_eq!(stdout, b"8\x004\0");
+    }
+
+    #[test]
+    fn show_protocols() {
+        let tester = Tester::new();
+        let path = tester.path_buf().join("image.png");
+        image::RgbaImage::from_fn(16, 8, |x, _| image::Rgba([x as u8 * 16, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+
+        let show = |protocol| {
+            let args = Args {
+                paths: vec![path.clone()],
+                command: Some(Commands::Show(ShowArgs {
+                    width: Some(8),
+                    protocol: Some(protocol),
+                })),
+                ..Default::default()
+            };
+            let mut stdout = Vec::new();
+            run(Context::new(&mut stdout), args).unwrap();
+            String::from_utf8(stdout).unwrap()
+        };
+
+        // Each character shows two pixels of the image scaled to 8x4
+        let blocks = show(preview::Protocol::Blocks);
+        assert_eq!(blocks.lines().count(), 2);
+        assert_eq!(blocks.matches('\u{2580}').count(), 16);
+
+        let kitty = show(preview::Protocol::Kitty);
+        assert!(kitty.starts_with("\x1b_Ga=T,f=100,c=2,m=0;iVBORw0KGgo"));
+
+        let iterm = show(preview::Protocol::Iterm);
+        assert!(iterm.starts_with("\x1b]1337;File=inline=1;"));
+
+        let sixel = show(preview::Protocol::Sixel);
+        assert!(sixel.starts_with("\x1bPq\"1;1;16;8"));
+        assert!(sixel.ends_with("\x1b\\\n"));
+    }
+
+    #[test]
+    fn ascii_art() {
+        let tester = Tester::new();
+        let path = tester.path_buf().join("image.png");
+        image::GrayAlphaImage::from_fn(8, 4, |x, _| match x {
+            0..2 => image::LumaA([0, 0]),
+            2..5 => image::LumaA([0, 255]),
+            _ => image::LumaA([255, 255]),
+        })
+        .save(&path)
+        .unwrap();
+
+        let ascii = |color| {
+            let args = Args {
+                paths: vec![path.clone()],
+                command: Some(Commands::Ascii(AsciiArgs {
+                    width: Some(8),
+                    color,
+                })),
+                ..Default::default()
+            };
+            let mut stdout = Vec::new();
+            run(Context::new(&mut stdout), args).unwrap();
+            String::from_utf8(stdout).unwrap()
+        };
+
+        // Transparent pixels are blank, black is the first and white the last character
+        let art = ascii(false);
+        let lines = art.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines
+            .iter()
+            .all(|line| line.starts_with("  ") && line.ends_with("@@")));
+        assert!(lines.iter().all(|line| line.chars().count() == 8));
+
+        let blocks = ascii(true);
+        assert_eq!(blocks.lines().count(), 2);
+        assert!(blocks.contains("\x1b[38;2;255;255;255m"));
+    }
+
+    #[test]
+    fn append_images() {
+        let tester = Tester::new();
+        let a = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let b = tester.save_empty_image("b.png", 4, ImageFormat::Png);
+        let output = tester.path_buf().join("appended.png");
+
+        let args = Args {
+            paths: vec![a, b],
+            command: Some(Commands::Append(AppendArgs {
+                direction: append::Direction::Horizontal,
+                align: append::Align::Center,
+                gap: 2,
+                background: image::Rgba([255, 255, 255, 255]),
+                output: output.clone(),
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let img = image::open(&output).unwrap();
+        assert_eq!(img.color(), ColorType::Rgb8);
+        assert_eq!(img.dimensions(), (14, 8));
+        let img = img.to_rgb8();
+        assert_eq!(img.get_pixel(9, 4), &image::Rgb([255, 255, 255]));
+        assert_eq!(img.get_pixel(11, 1), &image::Rgb([255, 255, 255]));
+        assert_eq!(img.get_pixel(11, 2), &image::Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn mask_circle() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("avatar.jpg", 32, ImageFormat::Jpeg);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Mask(MaskArgs {
+                circle: true,
+                target_format: "png".to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let img = image::open(input_path.with_extension("png"))
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(img.get_pixel(0, 0)[3], 0);
+        assert_eq!(img.get_pixel(16, 16)[3], 255);
+        assert_eq!(img.get_pixel(16, 1)[3], 255);
+        assert!(img.get_pixel(4, 4)[3] < 255);
+    }
+
+    #[test]
+    fn thumbnail_directory() {
+        let tester = Tester::new();
+        let input = tester.path_buf();
+        RgbImage::new(600, 300)
+            .save(input.join("wide.png"))
+            .unwrap();
+        tester.save_empty_image("album/small.jpg", 100, ImageFormat::Jpeg);
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            input.as_os_str(),
+            "thumbnail".as_ref(),
+            "--size".as_ref(),
+            "256".as_ref(),
+        ])
+        .unwrap();
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let img = image::open(input.join("wide_thumb.png")).unwrap();
+        assert_eq!(img.dimensions(), (256, 128));
+        let img = image::open(input.join("album/small_thumb.jpg")).unwrap();
+        assert_eq!(img.dimensions(), (100, 100));
+
+        // Thumbnails are neither thumbnailed again nor regenerated while up to date
+        let mut stdout = Vec::new();
+        let args = Args {
+            paths: vec![input.clone()],
+            dry_run: true,
+            command: Some(Commands::Thumbnail(ThumbnailArgs::default())),
+            ..Default::default()
+        };
+        run(Context::new(&mut stdout), args).unwrap();
+        let stdout = String::from_utf8(stdout).unwrap();
+        assert_eq!(stdout.lines().count(), 2);
+        assert!(stdout.lines().all(|line| line.starts_with("skip ")));
+
+        let out_dir = input.join("thumbs");
+        let args = Args {
+            paths: vec![input.clone()],
+            command: Some(Commands::Thumbnail(ThumbnailArgs {
+                size: 64,
+                out_dir: Some(out_dir.clone()),
+                target_format: Some("png".to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+        let img = image::open(out_dir.join("album/small.png")).unwrap();
+        assert_eq!(img.dimensions(), (64, 64));
+
+        // Files that are older than their image are only replaced after a confirmation
+        let existing = out_dir.join("wide.png");
+        fs::write(&existing, b"cover").unwrap();
+        File::options()
+            .write(true)
+            .open(&existing)
+            .unwrap()
+            .set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let args = Args {
+            paths: vec![input.join("wide.png")],
+            command: Some(Commands::Thumbnail(ThumbnailArgs {
+                out_dir: Some(out_dir.clone()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut context = Context::new(&mut stdout);
+        context.interactive = true;
+        context.answer = Some(false);
+        let report = run(context, args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::aborted");
+        assert_eq!(fs::read(&existing).unwrap(), b"cover");
+    }
+
+    #[test]
+    fn srcset_widths() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("hero.png");
+        RgbImage::new(800, 400).save(&input_path).unwrap();
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            input_path.as_os_str(),
+            "srcset".as_ref(),
+            "--widths".as_ref(),
+            "320,640,1280".as_ref(),
+            "--format".as_ref(),
+            "jpeg".as_ref(),
+        ])
+        .unwrap();
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "srcset=\"hero-320w.jpeg 320w, hero-640w.jpeg 640w\"\n"
+        );
+        let img = image::open(tester.path_buf().join("hero-320w.jpeg")).unwrap();
+        assert_eq!(img.dimensions(), (320, 160));
+        assert!(!tester.path_buf().join("hero-1280w.jpeg").exists());
+
+        // Existing encodes are only replaced after a confirmation
+        let encode = tester.path_buf().join("hero-320w.jpeg");
+        fs::write(&encode, b"stale").unwrap();
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Srcset(SrcsetArgs {
+                widths: vec![320],
+                target_format: Some("jpeg".to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        let mut context = Context::new(&mut stdout);
+        context.interactive = true;
+        context.answer = Some(false);
+        let report = run(context, args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::aborted");
+        assert_eq!(fs::read(&encode).unwrap(), b"stale");
+
+        // The encodes of the previous run are not encoded again
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Srcset(SrcsetArgs {
+                widths: vec![100],
+                output: OutputFormat::Json,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+        let json = serde_json::from_slice::<serde_json::Value>(&stdout).unwrap();
+        let sets = json.as_array().unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0]["srcset"], "hero-100w.png 100w");
+        assert_eq!(sets[0]["images"][0]["height"], 50);
+    }
+
+    #[test]
+    fn favicon_set() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("logo.png");
+        image::RgbaImage::new(512, 256).save(&input_path).unwrap();
+        let out_dir = tester.path_buf().join("public");
+
+        let args = Args {
+            paths: vec![input_path],
+            command: Some(Commands::Favicon(FaviconArgs {
+                out_dir: out_dir.clone(),
+                manifest: true,
+                name: Some("Demo".to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+        let html = String::from_utf8(stdout).unwrap();
+        assert!(html.contains(r#"<link rel="manifest" href="/site.webmanifest">"#));
+
+        let img = image::open(out_dir.join("apple-touch-icon.png")).unwrap();
+        assert_eq!(img.color(), ColorType::Rgb8);
+        assert_eq!(img.dimensions(), (180, 180));
+        assert_eq!(img.to_rgb8().get_pixel(0, 0), &image::Rgb([255, 255, 255]));
+        let img = image::open(out_dir.join("android-chrome-512x512.png")).unwrap();
+        assert_eq!(img.dimensions(), (512, 512));
+        let img = image::open(out_dir.join("favicon.ico")).unwrap();
+        assert_eq!(img.dimensions(), (48, 48));
+
+        let manifest = fs::read(out_dir.join("site.webmanifest")).unwrap();
+        let manifest = serde_json::from_slice::<serde_json::Value>(&manifest).unwrap();
+        assert_eq!(manifest["name"], "Demo");
+        assert_eq!(manifest["icons"][1]["src"], "/android-chrome-512x512.png");
+    }
+
+    #[test]
+    fn app_icons() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("master.png", 256, ImageFormat::Png);
+        let out_dir = tester.path_buf().join("icons");
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Icons(IconsArgs {
+                out_dir: out_dir.clone(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let ios_dir = out_dir.join("ios/AppIcon.appiconset");
+        let img = image::open(ios_dir.join("Icon-83.5@2x.png")).unwrap();
+        assert_eq!(img.color(), ColorType::Rgb8);
+        assert_eq!(img.dimensions(), (167, 167));
+        let contents = fs::read(ios_dir.join("Contents.json")).unwrap();
+        let contents = serde_json::from_slice::<serde_json::Value>(&contents).unwrap();
+        assert_eq!(contents["images"].as_array().unwrap().len(), 18);
+        let mipmap_dir = out_dir.join("android/res/mipmap-xxxhdpi");
+        let img = image::open(mipmap_dir.join("ic_launcher_round.png")).unwrap();
+        assert_eq!(img.dimensions(), (192, 192));
+        assert_eq!(img.to_rgba8().get_pixel(0, 0)[3], 0);
+
+        let spec_path = tester.path_buf().join("spec.json");
+        let spec = r#"[{"path": "web/icon-64.png", "size": 64, "opaque": true}]"#;
+        fs::write(&spec_path, spec).unwrap();
+        let icons = |spec_path: &Path| {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                yes: true,
+                command: Some(Commands::Icons(IconsArgs {
+                    out_dir: out_dir.clone(),
+                    spec: Some(spec_path.to_owned()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        icons(&spec_path).unwrap();
+        let img = image::open(out_dir.join("web/icon-64.png")).unwrap();
+        assert_eq!(img.dimensions(), (64, 64));
+
+        fs::write(&spec_path, r#"[{"size": 64}]"#).unwrap();
+        let report = icons(&spec_path).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::icon_spec");
+    }
+
+    #[test]
+    fn watermark_gravity_and_opacity() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("photos/photo.png", 40, ImageFormat::Png);
+        let logo_path = tester.path_buf().join("logo.png");
+        RgbImage::from_pixel(10, 10, image::Rgb([255, 255, 255]))
+            .save(&logo_path)
+            .unwrap();
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            input_path.parent().unwrap().as_os_str(),
+            "watermark".as_ref(),
+            "--image".as_ref(),
+            logo_path.as_os_str(),
+            "--gravity".as_ref(),
+            "south-east".as_ref(),
+            "--opacity".as_ref(),
+            "50%".as_ref(),
+            "--margin".as_ref(),
+            "2".as_ref(),
+            "-y".as_ref(),
+        ])
+        .unwrap();
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let img = image::open(&input_path).unwrap();
+        assert_eq!(img.color(), ColorType::Rgb8);
+        let img = img.to_rgb8();
+        assert_eq!(img.get_pixel(28, 28), &image::Rgb([128, 128, 128]));
+        assert_eq!(img.get_pixel(37, 37), &image::Rgb([128, 128, 128]));
+        assert_eq!(img.get_pixel(38, 38), &image::Rgb([0, 0, 0]));
+        assert_eq!(img.get_pixel(27, 27), &image::Rgb([0, 0, 0]));
+        assert_eq!(
+            overlay::position(overlay::Gravity::Center, (40, 30), (10, 10), 5),
+            (15, 10)
+        );
+    }
+
+    #[test]
+    fn caption_placeholders_and_outline() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("IMG_1.png", 64, ImageFormat::Png);
+
+        let caption = |text: &str| {
+            let args = Args::try_parse_from([
+                "imy".as_ref(),
+                input_path.as_os_str(),
+                "caption".as_ref(),
+                text.as_ref(),
+                "--gravity".as_ref(),
+                "north-west".as_ref(),
+                "--margin".as_ref(),
+                "2".as_ref(),
+                "--outline".as_ref(),
+                "#f00".as_ref(),
+                "-y".as_ref(),
+            ])
+            .unwrap();
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        let report = caption("{camera}").unwrap_err();
+        assert_eq!(
+            report.code().unwrap().to_string(),
+            "imy::unknown_placeholder"
+        );
+
+        caption("{stem}").unwrap();
+        let img = image::open(&input_path).unwrap().to_rgb8();
+        let (width, height) = font::text_size("IMG_1", 2);
+        let white = image::Rgb([255, 255, 255]);
+        let red = image::Rgb([255, 0, 0]);
+        // The outline adds a border of one font pixel around the text
+        let inside = |x: u32, y: u32| x >= 2 && y >= 2 && x < width + 6 && y < height + 6;
+        assert!(img.enumerate_pixels().any(|(_, _, p)| p == &white));
+        assert!(img.enumerate_pixels().any(|(_, _, p)| p == &red));
+        assert!(img
+            .enumerate_pixels()
+            .all(|(x, y, p)| inside(x, y) || p == &image::Rgb([0, 0, 0])));
+    }
+
+    #[test]
+    fn composite_blend_modes() {
+        let tester = Tester::new();
+        let base_path = tester.path_buf().join("base.png");
+        let layer_path = tester.path_buf().join("layer.png");
+        image::RgbaImage::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                image::Rgba([200, 100, 50, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        })
+        .save(&base_path)
+        .unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([128, 255, 0, 255]))
+            .save(&layer_path)
+            .unwrap();
+
+        let composite = |blend: &str| {
+            let args = Args::try_parse_from([
+                "imy".as_ref(),
+                base_path.as_os_str(),
+                "composite".as_ref(),
+                layer_path.as_os_str(),
+                "--blend".as_ref(),
+                blend.as_ref(),
+                "--x".as_ref(),
+                "2".as_ref(),
+                "--y".as_ref(),
+                "-1".as_ref(),
+                "-y".as_ref(),
+            ])
+            .unwrap();
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args).unwrap();
+            image::open(&base_path).unwrap().to_rgba8()
+        };
+        let img = composite("multiply");
+        assert_eq!(img.get_pixel(3, 0), &image::Rgba([100, 100, 0, 255]));
+        assert_eq!(img.get_pixel(1, 0), &image::Rgba([200, 100, 50, 255]));
+        assert_eq!(img.get_pixel(3, 3), &image::Rgba([200, 100, 50, 255]));
+        // The overlay keeps its colors where the base is transparent
+        assert_eq!(img.get_pixel(5, 0), &image::Rgba([128, 255, 0, 255]));
+
+        // The base holds the result of the multiplication now
+        let img = composite("screen");
+        assert_eq!(img.get_pixel(3, 0), &image::Rgba([178, 255, 0, 255]));
+    }
+
+    #[test]
+    fn filters() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("color.png");
+        RgbImage::from_pixel(4, 4, image::Rgb([255, 0, 0]))
+            .save(&input_path)
+            .unwrap();
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            input_path.as_os_str(),
+            "convert".as_ref(),
+            "--target-format".as_ref(),
+            "tiff".as_ref(),
+            "--filter".as_ref(),
+            "grayscale".as_ref(),
+        ])
+        .unwrap();
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+        let img = image::open(input_path.with_extension("tiff")).unwrap();
+        assert_eq!(img.color(), ColorType::L8);
+        assert_eq!(img.to_luma8().get_pixel(0, 0)[0], 54);
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            input_path.as_os_str(),
+            "filter".as_ref(),
+            "invert".as_ref(),
+            "sepia".as_ref(),
+            "-y".as_ref(),
+        ])
+        .unwrap();
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+        let img = image::open(&input_path).unwrap().to_rgb8();
+        // Red turns cyan, which sepia turns into a light brown
+        assert_eq!(img.get_pixel(0, 0), &image::Rgb([244, 218, 170]));
+    }
+
+    #[test]
+    fn adjust_brightness_contrast_gamma() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("gray.png");
+        image::GrayImage::from_fn(2, 1, |x, _| image::Luma([[64, 192][x as usize]]))
+            .save(&input_path)
+            .unwrap();
+
+        let adjust = |flags: &[&str]| {
+            let mut args = vec!["imy", input_path.to_str().unwrap(), "adjust", "-y"];
+            args.extend(flags);
+            let args = Args::try_parse_from(args).unwrap();
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args).unwrap();
+            image::open(&input_path).unwrap()
+        };
+        let img = adjust(&["--brightness", "+10", "--contrast", "-50"]);
+        assert_eq!(img.color(), ColorType::L8);
+        // 64 + 25.5 moved halfway to the middle gray of 127.5
+        assert_eq!(img.to_luma8().as_raw(), &[109, 173]);
+        let img = adjust(&["--gamma", "2"]);
+        assert_eq!(img.to_luma8().as_raw(), &[167, 210]);
+
+        let args = Args::try_parse_from(["imy", input_path.to_str().unwrap(), "adjust"]);
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn blur_and_sharpen() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("edge.png");
+        let edge =
+            image::GrayImage::from_fn(16, 4, |x, _| image::Luma([if x < 8 { 64 } else { 192 }]));
+        edge.save(&input_path).unwrap();
+
+        let convert = |sharpen: &str| {
+            let args = Args::try_parse_from([
+                "imy".as_ref(),
+                input_path.as_os_str(),
+                "convert".as_ref(),
+                "-t".as_ref(),
+                "tiff".as_ref(),
+                "--sharpen".as_ref(),
+                sharpen.as_ref(),
+                "-y".as_ref(),
+            ])
+            .unwrap();
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args).unwrap();
+            image::open(input_path.with_extension("tiff")).unwrap()
+        };
+        let img = convert("1.0");
+        assert_eq!(img.color(), ColorType::L8);
+        let img = img.to_luma8();
+        assert!(img.get_pixel(7, 0)[0] < 64);
+        assert!(img.get_pixel(8, 0)[0] > 192);
+        assert_eq!(img.get_pixel(0, 0)[0], 64);
+        let img = convert("1.0,255").to_luma8();
+        assert_eq!(img, edge);
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            input_path.as_os_str(),
+            "blur".as_ref(),
+            "--sigma".as_ref(),
+            "2".as_ref(),
+            "-y".as_ref(),
+        ])
+        .unwrap();
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+        let img = image::open(&input_path).unwrap().to_luma8();
+        assert!((65..128).contains(&img.get_pixel(7, 0)[0]));
+        assert!((128..192).contains(&img.get_pixel(8, 0)[0]));
+    }
+
+    #[test]
+    fn pipeline_stages() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("in.png");
+        RgbImage::from_pixel(160, 80, image::Rgb([255, 0, 0]))
+            .save(&input_path)
+            .unwrap();
+
+        let pipeline = |stages: &[&str]| {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                yes: true,
+                command: Some(Commands::Pipeline(PipelineArgs {
+                    stages: stages.iter().map(|stage| stage.to_string()).collect(),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        pipeline(&[
+            "resize 80x",
+            "filter grayscale",
+            "convert jpeg --quality 80",
+        ])
+        .unwrap();
+        let img = image::open(input_path.with_extension("jpeg")).unwrap();
+        assert_eq!(img.color(), ColorType::L8);
+        assert_eq!(img.dimensions(), (80, 40));
+
+        // Without a conversion, the images are overwritten
+        pipeline(&["rotate --degrees 90", "scale 50%"]).unwrap();
+        let img = image::open(&input_path).unwrap();
+        assert_eq!(img.dimensions(), (40, 80));
+
+        for stages in [
+            &["convert png", "blur 2"][..],
+            &["resize 80x --mode fill"],
+            &["explode"],
+        ] {
+            let report = pipeline(stages).unwrap_err();
+            assert_eq!(report.code().unwrap().to_string(), "imy::pipeline_stage");
+        }
+    }
+
+    #[test]
+    fn run_job_file() {
+        let tester = Tester::new();
+        tester.save_empty_image("assets/a.png", 8, ImageFormat::Png);
+        tester.save_empty_image("assets/b.jpg", 8, ImageFormat::Jpeg);
+        let job_file = tester.path_buf().join("jobs.toml");
+        let run_job_file = |text: &str| {
+            fs::write(&job_file, text).unwrap();
+            let args = Args {
+                command: Some(Commands::Run {
+                    job_file: job_file.clone(),
+                }),
+                ..Default::default()
+            };
+            let mut stdout = Vec::new();
+            run(Context::new(&mut stdout), args).map(|_| String::from_utf8(stdout).unwrap())
+        };
+
+        let output = run_job_file(
+            r#"
+            [[job]]
+            name = "web"
+            source = "assets"
+            include = ["*.png"]
+            stages = ["resize 4x"]
+            format = "webp"
+            out_dir = "build"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(output, "Finished web\n");
+        let img = image::open(tester.path_buf().join("build/a.webp")).unwrap();
+        assert_eq!(img.dimensions(), (4, 4));
+        assert!(!tester.path_buf().join("build/b.webp").exists());
+
+        for (text, code) in [
+            ("[[job]]\nsource = \"assets\"", "imy::invalid_job"),
+            ("[[job]]\nsource = \"assets\"\nstage = []", "imy::job_file"),
+            (
+                "[[job]]\nsource = \"missing\"\nformat = \"png\"",
+                "imy::path_not_found",
+            ),
+        ] {
+            let report = run_job_file(text).unwrap_err();
+            assert_eq!(report.code().unwrap().to_string(), code);
+        }
+    }
+
+    #[test]
+    fn rotate_and_flip() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("wide.png");
+        let mut img = RgbImage::new(4, 2);
+        img.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        img.save(&input_path).unwrap();
+
+        let args = Args::try_parse_from([
+            "imy".as_ref(),
+            input_path.as_os_str(),
+            "rotate".as_ref(),
+            "--degrees".as_ref(),
+            "90".as_ref(),
+            "-y".as_ref(),
+        ])
+        .unwrap();
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let img = image::open(&input_path).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (2, 4));
+        assert_eq!(img.get_pixel(1, 0)[0], 255);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            yes: true,
+            command: Some(Commands::Flip(FlipArgs {
+                vertical: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let img = image::open(&input_path).unwrap().to_rgb8();
+        assert_eq!(img.get_pixel(1, 3)[0], 255);
+    }
+
+    #[test]
+    fn optimize_png_and_jpeg() {
+        use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+
+        let tester = Tester::new();
+        let png = tester.path_buf().join("gradient.png");
+        let img = image::ImageBuffer::from_fn(64, 64, |x, y| image::Rgb([x as u8 * 4, y as u8, 0]));
+        let encoder = PngEncoder::new_with_quality(
+            File::create(&png).unwrap(),
+            CompressionType::Fast,
+            FilterType::NoFilter,
+        );
+        img.write_with_encoder(encoder).unwrap();
+        let jpeg = tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+        let metadata = metadata::Metadata {
+            icc: Some(vec![7; 100]),
+            text: [("comment".to_owned(), "x".repeat(1000))].into(),
+            ..Default::default()
+        };
+        metadata::write(&png, &metadata).unwrap();
+        metadata::write(&jpeg, &metadata).unwrap();
+        let png_size = fs::metadata(&png).unwrap().len();
+        let jpeg_size = fs::metadata(&jpeg).unwrap().len();
+
+        // A dry run reports what would be overwritten without changing the files
+        let png_data = fs::read(&png).unwrap();
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            dry_run: true,
+            command: Some(Commands::Optimize),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+        let output = String::from_utf8(stdout).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], format!("read {}", png.display()));
+        assert!(lines[1].starts_with(&format!("overwrite {} (saves ", png.display())));
+        assert!(lines[4].starts_with("Would save "));
+        assert_eq!(fs::read(&png).unwrap(), png_data);
+        assert_eq!(fs::metadata(&jpeg).unwrap().len(), jpeg_size);
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Optimize),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        assert!(fs::metadata(&png).unwrap().len() < png_size);
+        assert!(fs::metadata(&jpeg).unwrap().len() < jpeg_size);
+        assert_eq!(image::open(&png).unwrap().to_rgb8(), img);
+        let optimized = metadata::read(&png).unwrap();
+        assert_eq!(optimized.icc, metadata.icc);
+        assert!(optimized.text.is_empty());
+        assert!(metadata::read(&jpeg).unwrap().text.is_empty());
+        let output = String::from_utf8(stdout).unwrap();
+        assert!(output.ends_with("in 2 file(s)\n"));
+    }
+
+    #[test]
+    fn meta_sidecar_round_trip() {
+        let tester = Tester::new();
+        // Images that share a stem get a sidecar each
+        let png = tester.save_empty_image("photo.png", 8, ImageFormat::Png);
+        let jpeg = tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+        let png_metadata = metadata::Metadata {
+            exif: Some(b"MM\0\x2a\0\0\0\x08\0\0".to_vec()),
+            icc: Some(vec![7; 100]),
+            text: [("comment".to_owned(), "sunset".to_owned())].into(),
+        };
+        let jpeg_metadata = metadata::Metadata {
+            exif: None,
+            icc: Some(vec![9; 50]),
+            text: [("comment".to_owned(), "sunrise".to_owned())].into(),
+        };
+        metadata::write(&png, &png_metadata).unwrap();
+        metadata::write(&jpeg, &jpeg_metadata).unwrap();
+
+        let meta = |path: &Path, command| Args {
+            paths: vec![path.to_owned()],
+            command: Some(Commands::Meta(command)),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(
+            Context::new(&mut stdout),
+            meta(&tester.path_buf(), MetaCommands::Export { sidecar: true }),
+        )
+        .unwrap();
+        assert!(tester.path_buf().join("photo.png.json").is_file());
+        assert!(tester.path_buf().join("photo.jpg.json").is_file());
+
+        // Existing sidecars are only replaced after a confirmation
+        let mut context = Context::new(&mut stdout);
+        context.interactive = true;
+        context.answer = Some(false);
+        let report = run(
+            context,
+            meta(&tester.path_buf(), MetaCommands::Export { sidecar: true }),
+        )
+        .unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::aborted");
+
+        // Processing that strips the metadata, followed by the import of the sidecars
+        tester.save_empty_image("photo.png", 8, ImageFormat::Png);
+        tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+        assert_eq!(metadata::read(&png).unwrap(), metadata::Metadata::default());
+        let mut output = Vec::new();
+        let mut context = Context::new(&mut output);
+        context.dry_run = true;
+        run(context, meta(&jpeg, MetaCommands::Import)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                format!("read {}.json", jpeg.display()),
+                format!("overwrite {}", jpeg.display()),
+            ]
+        );
+        assert_eq!(
+            metadata::read(&jpeg).unwrap(),
+            metadata::Metadata::default()
+        );
+        run(
+            Context::new(&mut stdout),
+            meta(&tester.path_buf(), MetaCommands::Import),
+        )
+        .unwrap();
+
+        assert_eq!(metadata::read(&png).unwrap(), png_metadata);
+        assert_eq!(metadata::read(&jpeg).unwrap(), jpeg_metadata);
+        image::open(&png).unwrap();
+        image::open(&jpeg).unwrap();
+    }
+
+    /// EXIF data of a photo taken in the southern hemisphere with the orientation 6, which is
+    /// rotated by 90 degrees clockwise.
+    fn exif_data() -> Vec<u8> {
+        let entry = |tag: u16, kind: u16, count: u32, value: u32| {
+            [tag.to_le_bytes(), kind.to_le_bytes()]
+                .concat()
+                .into_iter()
+                .chain(count.to_le_bytes())
+                .chain(value.to_le_bytes())
+                .collect::<Vec<_>>()
+        };
+        let rationals = |values: &[(u32, u32)]| {
+            values
+                .iter()
+                .flat_map(|(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat())
+                .collect::<Vec<_>>()
+        };
+        [
+            b"II\x2a\0\x08\0\0\0".to_vec(),
+            // IFD0 with make, orientation and the offsets of the EXIF and GPS IFDs
+            4u16.to_le_bytes().to_vec(),
+            entry(0x010f, 2, 6, 62),
+            entry(0x0112, 3, 1, 6),
+            entry(0x8769, 4, 1, 68),
+            entry(0x8825, 4, 1, 106),
+            vec![0; 4],
+            b"Canon\0".to_vec(),
+            // EXIF IFD with exposure time and ISO
+            2u16.to_le_bytes().to_vec(),
+            entry(0x829a, 5, 1, 98),
+            entry(0x8827, 3, 1, 200),
+            vec![0; 4],
+            rationals(&[(1, 125)]),
+            // GPS IFD with the latitude
+            2u16.to_le_bytes().to_vec(),
+            entry(0x0001, 2, 2, u32::from_le_bytes(*b"S\0\0\0")),
+            entry(0x0002, 5, 3, 136),
+            vec![0; 4],
+            rationals(&[(33, 1), (30, 1), (0, 1)]),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn exif_fields() {
+        let tester = Tester::new();
+        let jpeg = tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+        let metadata = metadata::Metadata {
+            exif: Some(exif_data()),
+            ..Default::default()
+        };
+        metadata::write(&jpeg, &metadata).unwrap();
+
+        let args = Args {
+            paths: vec![jpeg],
+            command: Some(Commands::Exif {
+                output: OutputFormat::Json,
+            }),
+            ..Default::default()
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        let json = serde_json::from_slice::<serde_json::Value>(&stdout).unwrap();
+        assert_eq!(json["make"], "Canon");
+        assert_eq!(json["orientation"], 6);
+        assert_eq!(json["exposure_time"], "1/125");
+        assert_eq!(json["iso"], 200);
+        assert_eq!(json["latitude"], -33.5);
+        assert!(json.get("longitude").is_none());
+    }
+
+    #[test]
+    fn strip_metadata_keeps_pixels() {
+        let tester = Tester::new();
+        let png = tester.save_empty_image("image.png", 8, ImageFormat::Png);
+        let jpeg = tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+        let metadata = metadata::Metadata {
+            exif: Some(exif_data()),
+            icc: Some(vec![7; 100]),
+            text: [("comment".to_owned(), "sunset".to_owned())].into(),
+        };
+        metadata::write(&png, &metadata).unwrap();
+        metadata::write(&jpeg, &metadata).unwrap();
+        let pixels = image::open(&jpeg).unwrap();
+
+        let strip = |gps_only| Args {
+            paths: vec![tester.path_buf()],
+            yes: true,
+            command: Some(Commands::StripMetadata(StripMetadataArgs { gps_only })),
+            ..Default::default()
+        };
+        let png_data = fs::read(&png).unwrap();
+        let dry_run = Args {
+            dry_run: true,
+            ..strip(false)
+        };
+        let mut stdout = Vec::new();
+        run(Context::new(&mut stdout), dry_run).unwrap();
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            format!(
+                "read {png}\noverwrite {png} (strips metadata)\n\
+                 read {jpeg}\noverwrite {jpeg} (strips metadata)\nWould strip 2 file(s)\n",
+                png = png.display(),
+                jpeg = jpeg.display()
+            )
+        );
+        assert_eq!(fs::read(&png).unwrap(), png_data);
+
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), strip(true)).unwrap();
+        for path in [&png, &jpeg] {
+            let exif = exif::parse(&metadata::read(path).unwrap().exif.unwrap()).unwrap();
+            assert_eq!((exif.make.as_deref(), exif.latitude), (Some("Canon"), None));
+        }
+
+        run(Context::new(&mut stdout), strip(false)).unwrap();
+        for path in [&png, &jpeg] {
+            let stripped = metadata::read(path).unwrap();
+            assert_eq!((stripped.exif, stripped.text), (None, BTreeMap::new()));
+            assert_eq!(stripped.icc, metadata.icc);
+        }
+        assert_eq!(image::open(&jpeg).unwrap(), pixels);
+    }
+
+    #[test]
+    fn is_directory() {
+        let tester = Tester::new();
+        tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let jpeg = tester.save_empty_image("sub/b.jpg", 8, ImageFormat::Jpeg);
+
+        let is = |any| Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Is {
+                format: "png".to_owned(),
+                any,
+            }),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+
+        let report = run(Context::new(&mut stdout), is(false)).unwrap_err();
+        assert!(report.to_string().contains(&jpeg.display().to_string()));
+        run(Context::new(&mut stdout), is(true)).unwrap();
+    }
+
+    #[test]
+    fn convert_output_template() {
+        let tester = Tester::new();
+        let input_path = tester.path_buf().join("photo.png");
+        RgbImage::new(160, 80).save(&input_path).unwrap();
+
+        let convert = |template: &str| {
+            let args = Args {
+                paths: vec![input_path.clone()],
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "jpeg".to_owned(),
+                    resize: Some((40, 40)),
+                    output_template: Some(template.to_owned()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        convert("{stem}_{width}x{height}.{ext}").unwrap();
+        let img = image::open(tester.path_buf().join("photo_40x20.jpeg")).unwrap();
+        assert_eq!(img.dimensions(), (40, 20));
+
+        convert("{hash}-{date}.{format}").unwrap();
+        let hash = crc32fast::hash(&fs::read(&input_path).unwrap());
+        assert!(tester
+            .path_buf()
+            .join(format!("{hash:08x}-unknown.jpeg"))
+            .exists());
+
+        let report = convert("{name}.{ext}").unwrap_err();
+        assert_eq!(
+            report.code().unwrap().to_string(),
+            "imy::unknown_placeholder"
+        );
+    }
+
+    #[test]
+    fn convert_collisions() {
+        let tester = Tester::new();
+        let jpeg = tester.save_empty_image("photo.jpg", 8, ImageFormat::Jpeg);
+        let png = tester.save_empty_image("photo.png", 8, ImageFormat::Png);
+        let bmp = tester.save_empty_image("photo-1.bmp", 8, ImageFormat::Bmp);
+
+        let convert = |on_collision| {
+            let args = Args {
+                paths: vec![tester.path_buf()],
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "qoi".to_owned(),
+                    on_collision,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            run(Context::new(&mut stdout), args)
+        };
+        let report = convert(Collision::Error).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::output_collision");
+        assert!(!jpeg.with_extension("qoi").exists());
+
+        convert(Collision::Suffix).unwrap();
+        for path in ["photo.qoi", "photo-1.qoi", "photo-2.qoi"] {
+            assert!(is_image_with_type(&tester.path_buf().join(path), ImageFormat::Qoi).unwrap());
+        }
+        assert!(png.exists() && bmp.exists());
+    }
+
+    #[test]
+    fn failed_save_keeps_existing_file() {
+        let tester = Tester::new();
+        let path = tester.save_empty_image("photo.jpeg", 8, ImageFormat::Jpeg);
+        let data = fs::read(&path).unwrap();
+
+        // The jpeg encoder doesn't support floating-point images
+        let img = DynamicImage::ImageRgb32F(image::Rgb32FImage::new(8, 8));
+        save_with_quality(&img, &path, ImageFormat::Jpeg, None).unwrap_err();
+
+        assert_eq!(fs::read(&path).unwrap(), data);
+        assert_eq!(fs::read_dir(tester.path_buf()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn convert_keeps_unverified_original() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("photo.png", 8, ImageFormat::Png);
+
+        // Avif is encoded but can't be decoded for the verification
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "avif".to_owned(),
+                delete_original: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        let report = run(Context::new(&mut stdout), args).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::failed_files");
+        assert!(input_path.exists());
+        assert!(input_path.with_extension("avif").exists());
+    }
+
+    #[test]
+    fn convert_verify() {
+        let tester = Tester::new();
+        let input_path = tester.save_empty_image("photo.png", 16, ImageFormat::Png);
+
+        let args = Args {
+            paths: vec![input_path.clone()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "ico".to_owned(),
+                ico_sizes: vec![4, 8],
+                verify: true,
+                delete_original: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+        let output_path = input_path.with_extension("ico");
+        assert!(!input_path.exists());
+        verify_output(&output_path, (8, 8)).unwrap();
+
+        let reason = verify_output(&output_path, (16, 16)).unwrap_err();
+        assert_eq!(reason, "expected 16x16 pixels, found 8x8");
+        let data = fs::read(&output_path).unwrap();
+        fs::write(&output_path, &data[..data.len() / 2]).unwrap();
+        verify_output(&output_path, (8, 8)).unwrap_err();
+    }
+
+    #[test]
+    fn convert_keep_going() {
+        let tester = Tester::new();
+        let valid = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        tester.save_empty_image("b.qoi", 8, ImageFormat::Qoi);
+        let broken = tester.path_buf().join("c.png");
+        fs::write(&broken, &fs::read(&valid).unwrap()[..40]).unwrap();
+
+        let convert = |keep_going| {
+            let args = Args {
+                paths: vec![tester.path_buf()],
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "qoi".to_owned(),
+                    keep_going,
+                    skip_existing: true,
+                    jobs: Some(1),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = Vec::new();
+            let result = run(Context::new(&mut stdout), args);
+            (result, String::from_utf8(stdout).unwrap())
+        };
+        let (result, _) = convert(false);
+        assert_eq!(
+            result.unwrap_err().code().unwrap().to_string(),
+            "imy::decode"
+        );
+
+        fs::remove_file(valid.with_extension("qoi")).unwrap();
+        let (result, output) = convert(true);
+        assert_eq!(
+            result.unwrap_err().code().unwrap().to_string(),
+            "imy::failed_files"
+        );
+        assert!(valid.with_extension("qoi").exists());
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some("converted: 1"));
+        assert_eq!(lines.next(), Some("skipped: 1"));
+        assert_eq!(lines.next(), Some("failed: 1"));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with(&format!("  {}: Failed to decode file", broken.display())));
+    }
+
+    #[test]
+    fn config_defaults() {
+        let tester = Tester::new();
+        let config_path = tester.path_buf().join(config::PROJECT_FILE);
+        fs::write(&config_path, "out_dir = \"build\"\nhidden = true\njobs = 2").unwrap();
+        let nested = tester.path_buf().join("photos/nested");
+        tester.save_empty_image("photos/nested/a.png", 8, ImageFormat::Png);
+        tester.save_empty_image("photos/nested/.b.png", 8, ImageFormat::Png);
+        let config = Config::discover(&nested).unwrap();
+        assert_eq!(config.out_dir, Some(tester.path_buf().join("build")));
+
+        let convert = |out_dir: Option<PathBuf>| {
+            let args = Args {
+                paths: vec![nested.clone()],
+                command: Some(Commands::Convert(ConvertOptions {
+                    target_format: "qoi".to_owned(),
+                    out_dir,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            let mut context = Context::new(&mut stdout);
+            context.config = config.clone();
+            run(context, args).unwrap();
+        };
+        convert(None);
+        assert!(tester.path_buf().join("build/a.qoi").exists());
+        assert!(tester.path_buf().join("build/.b.qoi").exists());
+
+        // Options on the command line take precedence
+        convert(Some(tester.path_buf().join("cli")));
+        assert!(tester.path_buf().join("cli/a.qoi").exists());
+
+        fs::write(&config_path, "quality = 101").unwrap();
+        let report = Config::discover(&nested).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::invalid_config");
+        fs::write(&config_path, "qualty = 80").unwrap();
+        let report = Config::discover(&nested).unwrap_err();
+        assert_eq!(report.code().unwrap().to_string(), "imy::config_file");
+    }
+
+    #[test]
+    fn exit_codes() {
+        let tester = Tester::new();
+        let png = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+
+        let exit_code = |path: PathBuf| {
+            let args = Args {
+                paths: vec![path],
+                command: Some(Commands::Is {
+                    format: "jpeg".to_owned(),
+                    any: false,
+                }),
+                ..Default::default()
+            };
+            let mut stdout = io::stdout();
+            ExitCode::of(&run(Context::new(&mut stdout), args).unwrap_err())
+        };
+        assert_eq!(exit_code(png), ExitCode::Mismatch);
+        assert_eq!(
+            exit_code(tester.path_buf().join("b.png")),
+            ExitCode::PathNotFound
+        );
+        assert_eq!(ExitCode::of(&Error::PathRequired.into()), ExitCode::Failure);
+
+        let usage = |args: &[&str]| ExitCode::of_usage(&Args::try_parse_from(args).unwrap_err());
+        assert_eq!(usage(&["imy", "--no-such-flag"]), ExitCode::Usage);
+        assert_eq!(usage(&["imy", "--help"]), ExitCode::Success);
+        assert_eq!(usage(&["imy", "--version"]), ExitCode::Success);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn convert_url() {
+        use std::{io::Write, net::TcpListener};
+
+        let tester = Tester::new();
+        let source = tester.save_empty_image("served.png", 8, ImageFormat::Png);
+        let data = fs::read(&source).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n",
+                data.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(&data).unwrap();
+        });
+
+        let out_dir = tester.path_buf().join("out");
+        let args = Args {
+            paths: vec![PathBuf::from(format!(
+                "http://{address}/images/logo.png?v=2"
+            ))],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "bmp".to_owned(),
+                out_dir: Some(out_dir.clone()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        run(Context::new(&mut Vec::new()), args).unwrap();
+        server.join().unwrap();
+
+        assert!(is_image_with_type(&out_dir.join("logo.bmp"), ImageFormat::Bmp).unwrap());
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn watch_converts_changed_files() {
+        let tester = Tester::new();
+        let changed = tester.save_empty_image("inbox/a.png", 8, ImageFormat::Png);
+        let unchanged = tester.save_empty_image("inbox/b.png", 8, ImageFormat::Png);
+        let output = tester.save_empty_image("inbox/c.webp", 8, ImageFormat::WebP);
+
+        let args = WatchArgs {
+            debounce: 10,
+            command: WatchCommands::Convert(ConvertOptions {
+                target_format: "webp".to_owned(),
+                ..Default::default()
+            }),
+        };
+        let (sender, receiver) = mpsc::channel();
+        for path in [&changed, &changed, &output] {
+            sender.send(path.clone()).unwrap();
+        }
+        drop(sender);
+        let mut stdout = io::stdout();
+        let inbox = tester.path_buf().join("inbox");
+        watch_events(&mut Context::new(&mut stdout), &inbox, &args, &receiver).unwrap();
+
+        assert!(is_image_with_type(&changed.with_extension("webp"), ImageFormat::WebP).unwrap());
+        assert!(!unchanged.with_extension("webp").exists());
+        assert_eq!(fs::read_dir(&inbox).unwrap().count(), 4);
+
+        // Outputs are replaced without asking, but deleting the originals is still confirmed
+        fs::write(changed.with_extension("webp"), b"stale").unwrap();
+        for (path, delete_original) in [(&changed, false), (&unchanged, true)] {
+            let args = WatchArgs {
+                debounce: 10,
+                command: WatchCommands::Convert(ConvertOptions {
+                    target_format: "webp".to_owned(),
+                    delete_original,
+                    ..Default::default()
+                }),
+            };
+            let (sender, receiver) = mpsc::channel();
+            sender.send(path.clone()).unwrap();
+            drop(sender);
+            let mut context = Context::new(&mut stdout);
+            context.interactive = true;
+            context.answer = Some(false);
+            watch_events(&mut context, &inbox, &args, &receiver).unwrap();
+        }
+        assert!(is_image_with_type(&changed.with_extension("webp"), ImageFormat::WebP).unwrap());
+        assert!(unchanged.exists());
+        assert!(!unchanged.with_extension("webp").exists());
+    }
+
+    #[test]
+    fn convert_folder_with_jobs() {
+        let tester = Tester::new();
+        let paths = (0..8)
+            .map(|index| tester.save_empty_image(format!("{index}.png"), 8, ImageFormat::Png))
+            .collect::<Vec<_>>();
+
+        let args = Args {
+            paths: vec![tester.path_buf()],
+            command: Some(Commands::Convert(ConvertOptions {
+                target_format: "qoi".to_owned(),
+                jobs: Some(2),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut stdout = io::stdout();
+        run(Context::new(&mut stdout), args).unwrap();
+
+        for path in paths {
+            assert!(is_image_with_type(&path.with_extension("qoi"), ImageFormat::Qoi).unwrap());
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_convert_batch() {
+        use asynchronous::{CancellationToken, Progress};
+
+        let tester = Tester::new();
+        let valid = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let broken = tester.path_buf().join("b.png");
+        fs::write(&broken, b"broken").unwrap();
+        let options = || ConvertOptions {
+            target_format: "qoi".to_owned(),
+            jobs: Some(1),
+            ..Default::default()
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let cancel = CancellationToken::new();
+            let paths = vec![valid.clone(), broken.clone()];
+            let mut batch = asynchronous::convert_batch(paths, options(), cancel);
+            let mut events = Vec::new();
+            while let Some(event) = batch.progress.recv().await {
+                events.push(event);
+            }
+            let summary = batch.finish().await;
+            assert_eq!(summary.converted, std::slice::from_ref(&valid));
+            assert_eq!(summary.failed.len(), 1);
+            assert!(events.contains(&Progress::Converted {
+                path: valid.clone()
+            }));
+            assert!(valid.with_extension("qoi").exists());
+
+            let cancel = CancellationToken::new();
+            cancel.cancel();
+            let batch = asynchronous::convert_batch(vec![valid.clone()], options(), cancel);
+            assert_eq!(batch.finish().await.cancelled, std::slice::from_ref(&valid));
+
+            asynchronous::convert(
+                valid.clone(),
+                ConvertOptions {
+                    force: true,
+                    ..options()
+                },
+            )
+            .await
+            .unwrap();
+        });
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn ffi_convert_and_info() {
+        use std::ffi::{CStr, CString};
+
+        let tester = Tester::new();
+        let path = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+
+        let code = unsafe { ffi::imy_convert(c_path.as_ptr(), c"qoi".as_ptr(), std::ptr::null()) };
+        assert_eq!(code, 0);
+        assert!(is_image_with_type(&path.with_extension("qoi"), ImageFormat::Qoi).unwrap());
+
+        let code = unsafe { ffi::imy_convert(c_path.as_ptr(), c"qoi".as_ptr(), std::ptr::null()) };
+        assert_eq!(code, ExitCode::Aborted as i32);
+        let error = unsafe { CStr::from_ptr(ffi::imy_last_error()) };
+        assert!(error.to_str().unwrap().contains("a.qoi"));
+
+        let options = ffi::ImyConvertOptions {
+            quality: -1,
+            out_dir: std::ptr::null(),
+            overwrite: true,
+        };
+        let code = unsafe { ffi::imy_convert(c_path.as_ptr(), c"qoi".as_ptr(), &options) };
+        assert_eq!(code, 0);
+        assert!(ffi::imy_last_error().is_null());
+
+        let json = unsafe { ffi::imy_info_json(c_path.as_ptr()) };
+        let info: serde_json::Value =
+            serde_json::from_slice(unsafe { CStr::from_ptr(json) }.to_bytes()).unwrap();
+        unsafe { ffi::imy_string_free(json) };
+        assert_eq!(info["format"], "png");
+        assert_eq!(info["width"], 8);
+
+        let missing = c"missing.png";
+        assert!(unsafe { ffi::imy_info_json(missing.as_ptr()) }.is_null());
+        assert!(!ffi::imy_last_error().is_null());
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn python_module() {
+        use pyo3::{prelude::*, types::PyDict};
+
+        let tester = Tester::new();
+        let path = tester.save_empty_image("a.png", 8, ImageFormat::Png);
+        let broken = tester.path_buf().join("b.png");
+        fs::write(&broken, b"broken").unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            locals
+                .set_item("imy", pyo3::wrap_pymodule!(python::imy)(py))
+                .unwrap();
+            locals.set_item("path", &path).unwrap();
+            locals.set_item("broken", &broken).unwrap();
+            py.run(
+                cr#"
+imy.convert(path, "qoi")
+try:
+    imy.convert(path, "qoi")
+    raise AssertionError("expected an error")
+except imy.ImyError as error:
+    assert "a.qoi" in str(error)
+imy.convert(path, "qoi", overwrite=True)
+result = imy.convert_batch([path, broken], "bmp", jobs=2)
+assert [str(p) for p in result["converted"]] == [str(path)]
+assert [str(p) for p, _ in result["failed"]] == [str(broken)]
+info = imy.info(path)
+assert info["format"] == "png" and info["width"] == 8
+"#,
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+        });
+        assert!(is_image_with_type(&path.with_extension("qoi"), ImageFormat::Qoi).unwrap());
+        assert!(is_image_with_type(&path.with_extension("bmp"), ImageFormat::Bmp).unwrap());
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+use crate::error::Error;
+
+/// Size from which the log file is rotated before logging to it.
+const MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Number of rotated log files that are kept, e.g. imy.log.1 to imy.log.3.
+const ROTATED: usize = 3;
+
+/// Opens the log file for appending, after rotating it if it has grown too large.
+pub fn open(path: &Path) -> miette::Result<File> {
+    rotate(path, MAX_SIZE)?;
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(|source| Error::CreateDir {
+            path: parent.to_owned(),
+            source,
+        })?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|source| {
+            Error::Open {
+                path: path.to_owned(),
+                source,
+            }
+            .into()
+        })
+}
+
+/// Renames the log file to `<path>.1` if it is at least `max_size` bytes, shifting the rotated
+/// files by one and deleting the oldest.
+pub fn rotate(path: &Path, max_size: u64) -> miette::Result<()> {
+    if !fs::metadata(path).is_ok_and(|metadata| metadata.len() >= max_size) {
+        return Ok(());
+    }
+    for index in (1..=ROTATED).rev() {
+        let from = match index {
+            1 => path.to_owned(),
+            _ => rotated_path(path, index - 1),
+        };
+        let to = rotated_path(path, index);
+        if from.exists() {
+            fs::rename(&from, &to).map_err(|source| Error::MoveFile {
+                path: from.clone(),
+                destination: to.clone(),
+                source,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Path of the rotated log file with the index, e.g. imy.log.1.
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
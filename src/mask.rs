@@ -0,0 +1,37 @@
+use image::{DynamicImage, RgbaImage};
+
+/// Shape that is cut out of an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    /// Rectangle with corners rounded by the radius in pixels
+    Rounded(u32),
+    /// Largest circle that fits into the image, centered
+    Circle,
+}
+
+/// Makes everything outside of the shape transparent. Edges are anti-aliased by the coverage of
+/// the pixels.
+pub fn apply(img: &DynamicImage, shape: Shape) -> RgbaImage {
+    let mut buffer = img.to_rgba8();
+    let (width, height) = (buffer.width() as f32, buffer.height() as f32);
+    let radius = match shape {
+        Shape::Rounded(radius) => (radius as f32).min(width / 2.0).min(height / 2.0),
+        Shape::Circle => width.min(height) / 2.0,
+    };
+    let (center_x, center_y) = (width / 2.0, height / 2.0);
+    for (x, y, pixel) in buffer.enumerate_pixels_mut() {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        let distance = match shape {
+            Shape::Rounded(_) => {
+                // Distance to the center of the nearest corner circle, zero away from the corners
+                let dx = (radius - px).max(px - (width - radius)).max(0.0);
+                let dy = (radius - py).max(py - (height - radius)).max(0.0);
+                (dx * dx + dy * dy).sqrt()
+            }
+            Shape::Circle => ((px - center_x).powi(2) + (py - center_y).powi(2)).sqrt(),
+        };
+        let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+    buffer
+}
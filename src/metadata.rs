@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use image::{ImageDecoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+
+use crate::{atomic, error::Error, exif};
+
+/// Metadata of an image that can be stored in a sidecar and applied to another file.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Raw EXIF data, starting with the TIFF header
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex")]
+    pub exif: Option<Vec<u8>>,
+    /// ICC color profile
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex")]
+    pub icc: Option<Vec<u8>>,
+    /// Text entries by keyword, e.g. PNG text chunks or the comment of a JPEG
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub text: BTreeMap<String, String>,
+}
+
+/// Metadata of a file when it is printed instead of written to a sidecar.
+#[derive(Serialize)]
+pub struct Entry<'a> {
+    pub path: String,
+    #[serde(flatten)]
+    pub metadata: &'a Metadata,
+}
+
+/// Keyword under which JPEG comments are stored.
+const JPEG_COMMENT: &str = "comment";
+
+/// Reads the metadata of an image without decoding the pixels.
+pub fn read(path: &Path) -> miette::Result<Metadata> {
+    let decode_err = |source| Error::Decode {
+        path: path.to_owned(),
+        source,
+    };
+    let reader = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|source| Error::Open {
+            path: path.to_owned(),
+            source,
+        })?;
+    let format = reader.format();
+    let mut decoder = reader.into_decoder().map_err(decode_err)?;
+    let icc = decoder.icc_profile().map_err(decode_err)?;
+    let exif = decoder.exif_metadata().map_err(decode_err)?;
+
+    let (exif, text) = match format {
+        Some(ImageFormat::Png) => {
+            // The PNG decoder of image does not expose the eXIf chunk
+            let data = read_file(path)?;
+            (exif.or_else(|| png_exif(&data)), png_text(&data))
+        }
+        Some(ImageFormat::Jpeg) => (exif, jpeg_text(&read_file(path)?)),
+        _ => (exif, BTreeMap::new()),
+    };
+    Ok(Metadata { exif, icc, text })
+}
+
+/// Writes the metadata into a PNG or JPEG file without re-encoding the pixels. Metadata of the
+/// same kind that is already present is replaced.
+pub fn write(path: &Path, metadata: &Metadata) -> miette::Result<()> {
+    atomic::write_data(path, &embed(path, metadata)?)
+}
+
+/// Returns the contents of a PNG or JPEG file with the metadata written into it, see [`write()`].
+pub fn embed(path: &Path, metadata: &Metadata) -> miette::Result<Vec<u8>> {
+    let data = read_file(path)?;
+    let format = image::guess_format(&data).ok();
+    let data = match format {
+        Some(ImageFormat::Png) => write_png(&data, metadata),
+        Some(ImageFormat::Jpeg) => write_jpeg(&data, metadata),
+        _ => None,
+    }
+    .ok_or_else(|| Error::MetadataImport {
+        path: path.to_owned(),
+    })?;
+    Ok(data)
+}
+
+fn read_file(path: &Path) -> miette::Result<Vec<u8>> {
+    Ok(fs::read(path).map_err(|source| Error::ReadFile {
+        path: path.to_owned(),
+        source,
+    })?)
+}
+
+pub const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Splits a PNG file into its chunks as pairs of type and data, or `None` if it is malformed.
+pub fn png_chunks(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    let mut rest = data.strip_prefix(PNG_SIGNATURE)?;
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let kind = rest.get(4..8)?.try_into().ok()?;
+        chunks.push((kind, rest.get(8..8 + length)?));
+        rest = rest.get(12 + length..)?;
+    }
+    Some(chunks)
+}
+
+fn png_exif(data: &[u8]) -> Option<Vec<u8>> {
+    png_chunks(data)?
+        .into_iter()
+        .find(|(kind, _)| kind == b"eXIf")
+        .map(|(_, data)| data.to_vec())
+}
+
+fn png_text(data: &[u8]) -> BTreeMap<String, String> {
+    let latin1 = |bytes: &[u8]| bytes.iter().map(|byte| *byte as char).collect::<String>();
+    let mut text = BTreeMap::new();
+    for (kind, data) in png_chunks(data).unwrap_or_default() {
+        let Some((keyword, rest)) = split_at_nul(data) else {
+            continue;
+        };
+        let value = match &kind {
+            b"tEXt" => Some(latin1(rest)),
+            b"zTXt" => rest
+                .get(1..)
+                .and_then(|compressed| {
+                    miniz_oxide::inflate::decompress_to_vec_zlib(compressed).ok()
+                })
+                .map(|bytes| latin1(&bytes)),
+            b"iTXt" => (|| {
+                let (flag, rest) = (*rest.first()?, rest.get(2..)?);
+                let (_language, rest) = split_at_nul(rest)?;
+                let (_translated, rest) = split_at_nul(rest)?;
+                let bytes = if flag == 1 {
+                    miniz_oxide::inflate::decompress_to_vec_zlib(rest).ok()?
+                } else {
+                    rest.to_vec()
+                };
+                String::from_utf8(bytes).ok()
+            })(),
+            _ => continue,
+        };
+        if let Some(value) = value {
+            text.insert(latin1(keyword), value);
+        }
+    }
+    text
+}
+
+fn split_at_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let index = data.iter().position(|byte| *byte == 0)?;
+    Some((&data[..index], &data[index + 1..]))
+}
+
+fn write_png(data: &[u8], metadata: &Metadata) -> Option<Vec<u8>> {
+    let chunks = png_chunks(data)?;
+    let mut new_chunks = Vec::new();
+    if let Some(icc) = &metadata.icc {
+        let mut data = b"ICC Profile\0\0".to_vec();
+        data.extend(miniz_oxide::deflate::compress_to_vec_zlib(icc, 6));
+        new_chunks.push((*b"iCCP", data));
+    }
+    if let Some(exif) = &metadata.exif {
+        new_chunks.push((*b"eXIf", exif.clone()));
+    }
+    for (keyword, value) in &metadata.text {
+        let mut data = keyword.as_bytes().to_vec();
+        data.extend(b"\0\0\0\0\0");
+        data.extend(value.as_bytes());
+        new_chunks.push((*b"iTXt", data));
+    }
+
+    let replaced = |kind: &[u8; 4]| match kind {
+        b"iCCP" | b"sRGB" => metadata.icc.is_some(),
+        b"eXIf" => metadata.exif.is_some(),
+        b"tEXt" | b"zTXt" | b"iTXt" => !metadata.text.is_empty(),
+        _ => false,
+    };
+    let mut output = PNG_SIGNATURE.to_vec();
+    for (kind, data) in chunks.iter().filter(|(kind, _)| !replaced(kind)) {
+        write_png_chunk(&mut output, kind, data);
+        // The metadata has to precede the image data, so it is placed right after the header
+        if kind == b"IHDR" {
+            for (kind, data) in &new_chunks {
+                write_png_chunk(&mut output, kind, data);
+            }
+        }
+    }
+    Some(output)
+}
+
+/// Appends a chunk with its length and checksum to a PNG file.
+pub fn write_png_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend((data.len() as u32).to_be_bytes());
+    output.extend(kind);
+    output.extend(data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    output.extend(hasher.finalize().to_be_bytes());
+}
+
+/// Marker segments of a JPEG file as pairs of marker and payload.
+pub type Segments<'a> = Vec<(u8, &'a [u8])>;
+
+/// Splits a JPEG file into the marker segments before the scan data and the remaining bytes
+/// starting with the start of scan marker.
+pub fn jpeg_segments(data: &[u8]) -> Option<(Segments<'_>, &[u8])> {
+    let mut rest = data.strip_prefix(&[0xff, 0xd8])?;
+    let mut segments = Vec::new();
+    loop {
+        let marker = *rest.get(1).filter(|_| rest.first() == Some(&0xff))?;
+        if marker == 0xda {
+            return Some((segments, rest));
+        }
+        let length = u16::from_be_bytes(rest.get(2..4)?.try_into().ok()?) as usize;
+        segments.push((marker, rest.get(4..2 + length)?));
+        rest = rest.get(2 + length..)?;
+    }
+}
+
+pub const EXIF_HEADER: &[u8] = b"Exif\0\0";
+pub const ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+/// Largest payload of a JPEG segment, whose length field includes its own two bytes.
+const MAX_SEGMENT_PAYLOAD: usize = u16::MAX as usize - 2;
+
+fn jpeg_text(data: &[u8]) -> BTreeMap<String, String> {
+    let comments = jpeg_segments(data)
+        .map(|(segments, _)| segments)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(marker, _)| *marker == 0xfe)
+        .map(|(_, payload)| String::from_utf8_lossy(payload).into_owned())
+        .collect::<Vec<_>>();
+    let mut text = BTreeMap::new();
+    if !comments.is_empty() {
+        text.insert(JPEG_COMMENT.to_owned(), comments.join("\n"));
+    }
+    text
+}
+
+fn write_jpeg(data: &[u8], metadata: &Metadata) -> Option<Vec<u8>> {
+    let (segments, scan) = jpeg_segments(data)?;
+    let mut new_segments = Vec::new();
+    if let Some(exif) = &metadata.exif {
+        let payload = [EXIF_HEADER, exif].concat();
+        if payload.len() > MAX_SEGMENT_PAYLOAD {
+            tracing::warn!("EXIF data is too large for a JPEG segment and is skipped");
+        } else {
+            new_segments.push((0xe1, payload));
+        }
+    }
+    if let Some(icc) = &metadata.icc {
+        // Profiles are split into numbered chunks that fit into a segment each
+        let chunk_size = MAX_SEGMENT_PAYLOAD - ICC_HEADER.len() - 2;
+        let count = icc.len().div_ceil(chunk_size);
+        for (index, chunk) in icc.chunks(chunk_size).enumerate() {
+            let mut payload = ICC_HEADER.to_vec();
+            payload.extend([index as u8 + 1, count as u8]);
+            payload.extend(chunk);
+            new_segments.push((0xe2, payload));
+        }
+    }
+    for (keyword, value) in &metadata.text {
+        let comment = if keyword == JPEG_COMMENT {
+            value.clone()
+        } else {
+            format!("{keyword}: {value}")
+        };
+        let mut payload = comment.into_bytes();
+        payload.truncate(MAX_SEGMENT_PAYLOAD);
+        new_segments.push((0xfe, payload));
+    }
+
+    let replaced = |marker: u8, payload: &[u8]| match marker {
+        0xe1 => metadata.exif.is_some() && payload.starts_with(EXIF_HEADER),
+        0xe2 => metadata.icc.is_some() && payload.starts_with(ICC_HEADER),
+        0xfe => !metadata.text.is_empty(),
+        _ => false,
+    };
+    let mut output = vec![0xff, 0xd8];
+    let segments = segments
+        .into_iter()
+        .filter(|(marker, payload)| !replaced(*marker, payload))
+        .collect::<Vec<_>>();
+    // JFIF requires its APP0 segment to come first
+    let split = segments
+        .iter()
+        .take_while(|(marker, _)| *marker == 0xe0)
+        .count();
+    for (marker, payload) in &segments[..split] {
+        write_jpeg_segment(&mut output, *marker, payload);
+    }
+    for (marker, payload) in &new_segments {
+        write_jpeg_segment(&mut output, *marker, payload);
+    }
+    for (marker, payload) in &segments[split..] {
+        write_jpeg_segment(&mut output, *marker, payload);
+    }
+    output.extend(scan);
+    Some(output)
+}
+
+/// Removes the EXIF, XMP, IPTC and text metadata of a PNG or JPEG file without re-encoding the
+/// pixels, or only the GPS location of the EXIF data. Color profiles are kept. Returns `None` for other or
+/// malformed files.
+pub fn strip(data: &[u8], gps_only: bool) -> Option<Vec<u8>> {
+    match image::guess_format(data).ok()? {
+        ImageFormat::Png => {
+            let mut output = PNG_SIGNATURE.to_vec();
+            for (kind, data) in png_chunks(data)? {
+                match &kind {
+                    b"eXIf" if gps_only => {
+                        let mut exif = data.to_vec();
+                        exif::strip_gps(&mut exif);
+                        write_png_chunk(&mut output, &kind, &exif);
+                    }
+                    // XMP and IPTC data are stored in text chunks
+                    b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" if !gps_only => {}
+                    _ => write_png_chunk(&mut output, &kind, data),
+                }
+            }
+            Some(output)
+        }
+        ImageFormat::Jpeg => {
+            let (segments, scan) = jpeg_segments(data)?;
+            let mut output = vec![0xff, 0xd8];
+            for (marker, payload) in segments {
+                match marker {
+                    0xe1 if gps_only && payload.starts_with(EXIF_HEADER) => {
+                        let mut payload = payload.to_vec();
+                        exif::strip_gps(&mut payload[EXIF_HEADER.len()..]);
+                        write_jpeg_segment(&mut output, marker, &payload);
+                    }
+                    // EXIF and XMP are stored in APP1 and IPTC in APP13 segments. Comments are
+                    // removed like the text chunks of PNGs
+                    0xe1 | 0xed | 0xfe if !gps_only => {}
+                    _ => write_jpeg_segment(&mut output, marker, payload),
+                }
+            }
+            output.extend(scan);
+            Some(output)
+        }
+        _ => None,
+    }
+}
+
+/// Appends a marker segment with its length to a JPEG file.
+pub fn write_jpeg_segment(output: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    output.extend([0xff, marker]);
+    output.extend((payload.len() as u16 + 2).to_be_bytes());
+    output.extend(payload);
+}
+
+/// Serializes binary metadata as hex string, which keeps sidecars readable for diffs.
+mod hex {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let hex = bytes.as_ref().map(|bytes| {
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        });
+        hex.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        let Some(hex) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        if !hex.is_ascii() || hex.len() % 2 != 0 {
+            return Err(serde::de::Error::custom("invalid hex string"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
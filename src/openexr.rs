@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use exr::prelude::{read_all_flat_layers_from_file, MetaData};
+use image::{DynamicImage, Rgb32FImage, Rgba32FImage};
+use serde::Serialize;
+
+use crate::error::Error;
+
+/// Layer of an OpenEXR file. Layers are either separate parts of a multi-part file or groups of
+/// channels sharing a prefix, e.g. `diffuse.R`, `diffuse.G` and `diffuse.B`.
+#[derive(Serialize)]
+pub struct Layer {
+    /// Name of the layer, empty for the default layer
+    pub name: String,
+    /// Names of the channels without the layer prefix
+    pub channels: Vec<String>,
+}
+
+/// Splits the name of a channel into its layer and its short name, taking the name of the part
+/// into account.
+fn split_channel_name(part_name: Option<&str>, channel_name: &str) -> (String, String) {
+    let (prefix, short) = match channel_name.rsplit_once('.') {
+        Some((prefix, short)) => (Some(prefix), short),
+        None => (None, channel_name),
+    };
+    let layer = match (part_name, prefix) {
+        (Some(part_name), Some(prefix)) => format!("{part_name}.{prefix}"),
+        (Some(name), None) | (None, Some(name)) => name.to_owned(),
+        (None, None) => String::new(),
+    };
+    (layer, short.to_owned())
+}
+
+/// Lists the layers and channels of an OpenEXR file without decoding the pixels.
+pub fn layers(path: &Path) -> miette::Result<Vec<Layer>> {
+    let meta_data = MetaData::read_from_file(path, false).map_err(|source| Error::OpenExr {
+        path: path.to_owned(),
+        source,
+    })?;
+    let mut layers: Vec<Layer> = Vec::new();
+    for header in &meta_data.headers {
+        let part_name = header
+            .own_attributes
+            .layer_name
+            .as_ref()
+            .map(|name| name.to_string());
+        for channel in &header.channels.list {
+            let (name, short) = split_channel_name(part_name.as_deref(), &channel.name.to_string());
+            match layers.iter_mut().find(|layer| layer.name == name) {
+                Some(layer) => layer.channels.push(short),
+                None => layers.push(Layer {
+                    name,
+                    channels: vec![short],
+                }),
+            }
+        }
+    }
+    Ok(layers)
+}
+
+/// Decodes a single layer of an OpenEXR file, or a single channel of it when `channel` is given.
+/// Without `layer`, the default layer is used, or the first layer if there is no default layer.
+pub fn read_layer(
+    path: &Path,
+    layer: Option<&str>,
+    channel: Option<&str>,
+) -> miette::Result<DynamicImage> {
+    let image = read_all_flat_layers_from_file(path).map_err(|source| Error::OpenExr {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    let mut candidates = Vec::new();
+    for part in &image.layer_data {
+        let part_name = part
+            .attributes
+            .layer_name
+            .as_ref()
+            .map(|name| name.to_string());
+        for data in &part.channel_data.list {
+            let (name, short) = split_channel_name(part_name.as_deref(), &data.name.to_string());
+            candidates.push((name, short, part.size, data.sample_data.values_as_f32()));
+        }
+    }
+
+    let layer_name = match layer {
+        Some(layer) => layer.to_owned(),
+        None => candidates
+            .iter()
+            .find(|(name, ..)| name.is_empty())
+            .or(candidates.first())
+            .map(|(name, ..)| name.clone())
+            .unwrap_or_default(),
+    };
+    let channels = candidates
+        .into_iter()
+        .filter(|(name, ..)| *name == layer_name)
+        .map(|(_, short, size, values)| (short, (size.0, size.1), values.collect::<Vec<f32>>()))
+        .collect::<Vec<_>>();
+    if channels.is_empty() {
+        let names = layers(path)?
+            .into_iter()
+            .map(|layer| display_layer_name(&layer.name).to_owned())
+            .collect::<Vec<_>>();
+        return Err(Error::UnknownExrLayer {
+            layer: layer_name,
+            help: format!("Available layers are: {}", names.join(", ")),
+        }
+        .into());
+    }
+    let available = channels
+        .iter()
+        .map(|(short, ..)| short.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let find = |name: &str| channels.iter().find(|(short, ..)| short == name);
+
+    if let Some(channel) = channel {
+        let (_, size, values) = find(channel).ok_or_else(|| Error::UnknownExrChannel {
+            channel: channel.to_owned(),
+            help: format!("Available channels are: {available}"),
+        })?;
+        return Ok(gray(*size, values));
+    }
+
+    match (find("R"), find("G"), find("B"), find("A"), find("Y")) {
+        (Some((_, size, r)), Some((_, _, g)), Some((_, _, b)), a, _) => {
+            let (width, height) = (size.0 as u32, size.1 as u32);
+            Ok(match a {
+                Some((_, _, a)) => {
+                    let mut buffer = Rgba32FImage::new(width, height);
+                    for (index, pixel) in buffer.pixels_mut().enumerate() {
+                        pixel.0 = [r[index], g[index], b[index], a[index]];
+                    }
+                    DynamicImage::ImageRgba32F(buffer)
+                }
+                None => {
+                    let mut buffer = Rgb32FImage::new(width, height);
+                    for (index, pixel) in buffer.pixels_mut().enumerate() {
+                        pixel.0 = [r[index], g[index], b[index]];
+                    }
+                    DynamicImage::ImageRgb32F(buffer)
+                }
+            })
+        }
+        (.., Some((_, size, y))) => Ok(gray(*size, y)),
+        _ if channels.len() == 1 => Ok(gray(channels[0].1, &channels[0].2)),
+        _ => Err(Error::AmbiguousExrChannels {
+            help: format!("Select one of the channels with --channel: {available}"),
+        }
+        .into()),
+    }
+}
+
+pub fn display_layer_name(name: &str) -> &str {
+    if name.is_empty() {
+        "(default)"
+    } else {
+        name
+    }
+}
+
+fn gray(size: (usize, usize), values: &[f32]) -> DynamicImage {
+    let mut buffer = Rgb32FImage::new(size.0 as u32, size.1 as u32);
+    for (pixel, value) in buffer.pixels_mut().zip(values) {
+        pixel.0 = [*value; 3];
+    }
+    DynamicImage::ImageRgb32F(buffer)
+}
@@ -0,0 +1,66 @@
+use image::{
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+    ImageFormat,
+};
+
+use crate::metadata;
+
+/// Ancillary PNG chunks that affect how the pixels are displayed. All other ancillary chunks, e.g.
+/// text and timestamps, are dropped. `sBIT` is not kept because its layout depends on the color
+/// type, which can change when palettes are expanded.
+const KEPT_PNG_CHUNKS: &[&[u8; 4]] = &[
+    b"iCCP", b"sRGB", b"gAMA", b"cHRM", b"cICP", b"pHYs", b"eXIf",
+];
+
+/// Re-encodes a PNG with the best compression and adaptive filters and keeps only the chunks that
+/// affect the display. Returns `None` for animated or malformed files.
+pub fn optimize_png(data: &[u8]) -> Option<Vec<u8>> {
+    let chunks = metadata::png_chunks(data)?;
+    // Decoding would only keep the first frame of an animation
+    if chunks.iter().any(|(kind, _)| kind == b"acTL") {
+        return None;
+    }
+    let img = image::load_from_memory_with_format(data, ImageFormat::Png).ok()?;
+    let mut encoded = Vec::new();
+    let encoder =
+        PngEncoder::new_with_quality(&mut encoded, CompressionType::Best, FilterType::Adaptive);
+    img.write_with_encoder(encoder).ok()?;
+
+    let mut output = metadata::PNG_SIGNATURE.to_vec();
+    for (kind, data) in metadata::png_chunks(&encoded)? {
+        metadata::write_png_chunk(&mut output, &kind, data);
+        if &kind == b"IHDR" {
+            for (kind, data) in chunks
+                .iter()
+                .filter(|(kind, _)| KEPT_PNG_CHUNKS.contains(&kind))
+            {
+                metadata::write_png_chunk(&mut output, kind, data);
+            }
+        }
+    }
+    Some(output)
+}
+
+/// Drops the marker segments of a JPEG that don't affect the display, e.g. comments, XMP and
+/// thumbnails, without touching the compressed image data. Returns `None` for malformed files.
+pub fn optimize_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let (segments, scan) = metadata::jpeg_segments(data)?;
+    let kept = |marker: u8, payload: &[u8]| match marker {
+        0xe0 => payload.starts_with(b"JFIF\0"),
+        // The orientation is stored in the EXIF data
+        0xe1 => payload.starts_with(metadata::EXIF_HEADER),
+        0xe2 => payload.starts_with(metadata::ICC_HEADER),
+        // Tells the decoder how the color channels are transformed
+        0xee => payload.starts_with(b"Adobe"),
+        0xe3..=0xef | 0xfe => false,
+        _ => true,
+    };
+    let mut output = vec![0xff, 0xd8];
+    for (marker, payload) in segments {
+        if kept(marker, payload) {
+            metadata::write_jpeg_segment(&mut output, marker, payload);
+        }
+    }
+    output.extend(scan);
+    Some(output)
+}
@@ -0,0 +1,148 @@
+use image::{DynamicImage, Rgba32FImage};
+
+use crate::color;
+
+/// Position of an overlay on an image, named after the compass like in ImageMagick.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Gravity {
+    NorthWest,
+    North,
+    NorthEast,
+    West,
+    Center,
+    East,
+    SouthWest,
+    South,
+    #[default]
+    SouthEast,
+}
+
+/// How the colors of an overlay are combined with the colors below it, following the blend modes
+/// of the W3C compositing specification.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The overlay replaces the colors
+    #[default]
+    Normal,
+    /// Multiplies the colors, which darkens like stacked slides
+    Multiply,
+    /// Inverts, multiplies and inverts again, which lightens like overlapping projections
+    Screen,
+    /// Multiplies dark and screens light colors below the overlay, which increases the contrast
+    Overlay,
+    /// Adds the colors, e.g. for light effects
+    Add,
+}
+
+impl BlendMode {
+    /// Blends a channel of the overlay `source` with the channel `backdrop` below it.
+    fn blend(self, backdrop: f32, source: f32) -> f32 {
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => backdrop * source,
+            BlendMode::Screen => backdrop + source - backdrop * source,
+            BlendMode::Overlay if backdrop <= 0.5 => 2.0 * backdrop * source,
+            BlendMode::Overlay => 1.0 - 2.0 * (1.0 - backdrop) * (1.0 - source),
+            BlendMode::Add => backdrop + source,
+        }
+    }
+}
+
+/// Parses an opacity given as fraction or percentage, e.g. "0.5" or "50%".
+pub fn parse_opacity(value: &str) -> Result<f32, String> {
+    let opacity = match value.trim().strip_suffix('%') {
+        Some(percentage) => percentage.trim().parse::<f32>().map(|p| p / 100.0),
+        None => value.trim().parse::<f32>(),
+    }
+    .map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&opacity) {
+        return Err(format!("opacity must be between 0 and 1: {value}"));
+    }
+    Ok(opacity)
+}
+
+/// Top left corner of an overlay of `size` that is placed on an image of `canvas` according to
+/// the gravity, keeping `margin` pixels to the edges it is attached to.
+pub fn position(
+    gravity: Gravity,
+    (canvas_width, canvas_height): (u32, u32),
+    (width, height): (u32, u32),
+    margin: u32,
+) -> (i64, i64) {
+    let place = |canvas: u32, size: u32, alignment: i8| {
+        let (canvas, size, margin) = (canvas as i64, size as i64, margin as i64);
+        match alignment {
+            -1 => margin,
+            0 => (canvas - size) / 2,
+            _ => canvas - size - margin,
+        }
+    };
+    let (horizontal, vertical) = match gravity {
+        Gravity::NorthWest => (-1, -1),
+        Gravity::North => (0, -1),
+        Gravity::NorthEast => (1, -1),
+        Gravity::West => (-1, 0),
+        Gravity::Center => (0, 0),
+        Gravity::East => (1, 0),
+        Gravity::SouthWest => (-1, 1),
+        Gravity::South => (0, 1),
+        Gravity::SouthEast => (1, 1),
+    };
+    (
+        place(canvas_width, width, horizontal),
+        place(canvas_height, height, vertical),
+    )
+}
+
+/// Composites the overlay over the image with its top left corner at `(x, y)` and its alpha
+/// scaled by the opacity. The image keeps its bit depth and gets color channels if the overlay
+/// has color.
+pub fn composite(
+    img: DynamicImage,
+    overlay: &DynamicImage,
+    (x, y): (i64, i64),
+    opacity: f32,
+    mode: BlendMode,
+) -> DynamicImage {
+    let layout = if overlay.color().has_color() {
+        color::with_color(img.color())
+    } else {
+        img.color()
+    };
+    let mut buffer = img.into_rgba32f();
+    blend(&mut buffer, &overlay.to_rgba32f(), (x, y), opacity, mode);
+    color::to_layout(DynamicImage::ImageRgba32F(buffer), layout)
+}
+
+/// Blends the pixels of the overlay with the buffer and composites the result with the "over"
+/// operator. Where the buffer is transparent, the overlay keeps its colors. Pixels outside of the
+/// buffer are skipped.
+fn blend(
+    buffer: &mut Rgba32FImage,
+    overlay: &Rgba32FImage,
+    (x, y): (i64, i64),
+    opacity: f32,
+    mode: BlendMode,
+) {
+    for (ox, oy, pixel) in overlay.enumerate_pixels() {
+        let (px, py) = (x + ox as i64, y + oy as i64);
+        if px < 0 || py < 0 || px >= buffer.width() as i64 || py >= buffer.height() as i64 {
+            continue;
+        }
+        let target = buffer.get_pixel_mut(px as u32, py as u32);
+        let alpha = pixel[3].clamp(0.0, 1.0) * opacity;
+        let target_alpha = target[3].clamp(0.0, 1.0);
+        let out_alpha = alpha + target_alpha * (1.0 - alpha);
+        if out_alpha <= 0.0 {
+            continue;
+        }
+        for channel in 0..3 {
+            let (backdrop, source) = (target[channel], pixel[channel]);
+            let blended =
+                (1.0 - target_alpha) * source + target_alpha * mode.blend(backdrop, source);
+            target[channel] =
+                (blended * alpha + backdrop * target_alpha * (1.0 - alpha)) / out_alpha;
+        }
+        target[3] = out_alpha;
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::BTreeSet, env, fmt::Write, io::Cursor};
+
+use image::{imageops::FilterType, DynamicImage, ImageFormat, Rgba, RgbaImage};
+
+/// Way of drawing images in the terminal.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Graphics protocol of kitty, also supported by Ghostty and WezTerm
+    Kitty,
+    /// Inline images of iTerm2, also supported by WezTerm
+    Iterm,
+    /// Sixel graphics, e.g. in foot, mlterm and Windows Terminal
+    Sixel,
+    /// Colored half blocks, which work in every terminal with true color
+    Blocks,
+}
+
+impl Protocol {
+    /// Guesses the protocol of the terminal from the environment, falling back to half blocks.
+    pub fn detect() -> Self {
+        let var = |name| env::var(name).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        if term == "xterm-kitty"
+            || term == "xterm-ghostty"
+            || env::var_os("KITTY_WINDOW_ID").is_some()
+        {
+            Self::Kitty
+        } else if program == "iTerm.app" || program == "WezTerm" {
+            Self::Iterm
+        } else if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || env::var_os("WT_SESSION").is_some()
+        {
+            Self::Sixel
+        } else {
+            Self::Blocks
+        }
+    }
+}
+
+/// Assumed width of a character cell in pixels, which sizes the images of the graphics protocols.
+const CELL_WIDTH: u32 = 8;
+
+/// Characters of the ASCII art from the darkest to the brightest pixels on a dark background.
+const RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Size of the base64 chunks of the kitty protocol.
+const KITTY_CHUNK: usize = 4096;
+
+/// Renders the image as terminal output that is at most `columns` wide. Images are never scaled up.
+pub fn render(img: &DynamicImage, protocol: Protocol, columns: u32) -> image::ImageResult<String> {
+    let columns = columns.max(1);
+    let output = match protocol {
+        Protocol::Kitty | Protocol::Iterm => {
+            // The terminal scales the image to the columns, so it is only encoded at the size of
+            // the cells on displays with twice the density
+            let img = downscale(img, columns * CELL_WIDTH * 2, u32::MAX);
+            let columns = columns.min(img.width().div_ceil(CELL_WIDTH));
+            let mut png = Vec::new();
+            img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            if protocol == Protocol::Kitty {
+                kitty(&png, columns)
+            } else {
+                format!(
+                    "\x1b]1337;File=inline=1;size={};width={columns};preserveAspectRatio=1:{}\x07\n",
+                    png.len(),
+                    base64(&png)
+                )
+            }
+        }
+        Protocol::Sixel => sixel(&downscale(img, columns * CELL_WIDTH, u32::MAX).to_rgba8()),
+        Protocol::Blocks => half_blocks(&downscale(img, columns, u32::MAX).to_rgba8()),
+    };
+    Ok(output)
+}
+
+/// Renders the image as text that is at most `columns` wide: colored half blocks, or characters of
+/// increasing density for the brightness of the pixels without color. Images are never scaled up.
+pub fn ascii(img: &DynamicImage, columns: u32, color: bool) -> String {
+    let img = downscale(img, columns.max(1), u32::MAX);
+    if color {
+        return half_blocks(&img.to_rgba8());
+    }
+    // Characters are about twice as high as wide
+    let rows = img.height().div_ceil(2);
+    let img = img.resize_exact(img.width(), rows, FilterType::Triangle);
+    let (gray, alpha) = (img.to_luma8(), img.to_rgba8());
+    let mut output = String::new();
+    for y in 0..rows {
+        let line = (0..img.width()).map(|x| {
+            if alpha.get_pixel(x, y)[3] < 128 {
+                return ' ';
+            }
+            let level = gray.get_pixel(x, y)[0] as usize * (RAMP.len() - 1) / 255;
+            char::from(RAMP[level])
+        });
+        output.extend(line);
+        output.push('\n');
+    }
+    output
+}
+
+/// Scales the image down to fit into the size, keeping its aspect ratio.
+fn downscale(img: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if img.width() <= width && img.height() <= height {
+        return img.clone();
+    }
+    img.resize(width, height, FilterType::Triangle)
+}
+
+/// Transmits the PNG in chunks and displays it across the columns.
+fn kitty(png: &[u8], columns: u32) -> String {
+    let data = base64(png);
+    let chunks = data.as_bytes().chunks(KITTY_CHUNK).collect::<Vec<_>>();
+    let mut output = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            write!(output, "\x1b_Ga=T,f=100,c={columns},m={more};{chunk}\x1b\\").unwrap();
+        } else {
+            write!(output, "\x1b_Gm={more};{chunk}\x1b\\").unwrap();
+        }
+    }
+    output.push('\n');
+    output
+}
+
+/// Encodes the image as sixels with a palette of 6 levels per channel. Transparent pixels are
+/// blended onto black.
+fn sixel(img: &RgbaImage) -> String {
+    let level = |value: u32| (value * 5 + 127) / 255;
+    let index = |pixel: &Rgba<u8>| {
+        let [r, g, b, a] = pixel.0.map(u32::from);
+        let [r, g, b] = [r, g, b].map(|value| level(value * a / 255));
+        (r * 36 + g * 6 + b) as usize
+    };
+    let (width, height) = img.dimensions();
+    let mut output = format!("\x1bPq\"1;1;{width};{height}");
+    for i in 0..216 {
+        let [r, g, b] = [i / 36, i / 6 % 6, i % 6].map(|level| level * 20);
+        write!(output, "#{i};2;{r};{g};{b}").unwrap();
+    }
+    // Each band of 6 rows is drawn once per color, returning to its start with $
+    for top in (0..height).step_by(6) {
+        let rows = top..(top + 6).min(height);
+        let colors = rows
+            .clone()
+            .flat_map(|y| (0..width).map(move |x| index(img.get_pixel(x, y))))
+            .collect::<BTreeSet<_>>();
+        for (n, color) in colors.into_iter().enumerate() {
+            if n > 0 {
+                output.push('$');
+            }
+            write!(output, "#{color}").unwrap();
+            let sixels = (0..width).map(|x| {
+                let bits = rows
+                    .clone()
+                    .filter(|&y| index(img.get_pixel(x, y)) == color)
+                    .fold(0, |bits, y| bits | 1 << (y - top));
+                char::from(63 + bits as u8)
+            });
+            push_runs(&mut output, sixels);
+        }
+        output.push('-');
+    }
+    output.push_str("\x1b\\\n");
+    output
+}
+
+/// Appends the sixels with runs of more than 3 equal sixels compressed to "!<count><sixel>".
+fn push_runs(output: &mut String, sixels: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+    let flush = |output: &mut String, (sixel, count): (char, usize)| {
+        if count > 3 {
+            write!(output, "!{count}{sixel}").unwrap();
+        } else {
+            output.extend(std::iter::repeat_n(sixel, count));
+        }
+    };
+    for sixel in sixels {
+        run = match run {
+            Some((current, count)) if current == sixel => Some((current, count + 1)),
+            Some(previous) => {
+                flush(output, previous);
+                Some((sixel, 1))
+            }
+            None => Some((sixel, 1)),
+        };
+    }
+    if let Some(run) = run {
+        flush(output, run);
+    }
+}
+
+/// Draws two pixels per character with the upper half block in 24-bit colors. Pixels that are
+/// mostly transparent show the background of the terminal.
+fn half_blocks(img: &RgbaImage) -> String {
+    let visible = |pixel: &Rgba<u8>| pixel[3] >= 128;
+    let color = |pixel: &Rgba<u8>| format!("{};{};{}", pixel[0], pixel[1], pixel[2]);
+    let mut output = String::new();
+    for y in (0..img.height()).step_by(2) {
+        for x in 0..img.width() {
+            let top = img.get_pixel(x, y);
+            let bottom = (y + 1 < img.height()).then(|| img.get_pixel(x, y + 1));
+            match (visible(top), bottom.filter(|bottom| visible(bottom))) {
+                (true, Some(bottom)) => write!(
+                    output,
+                    "\x1b[38;2;{}m\x1b[48;2;{}m\u{2580}",
+                    color(top),
+                    color(bottom)
+                ),
+                (true, None) => write!(output, "\x1b[49m\x1b[38;2;{}m\u{2580}", color(top)),
+                (false, Some(bottom)) => {
+                    write!(output, "\x1b[49m\x1b[38;2;{}m\u{2584}", color(bottom))
+                }
+                (false, None) => write!(output, "\x1b[0m "),
+            }
+            .unwrap();
+        }
+        output.push_str("\x1b[0m\n");
+    }
+    output
+}
+
+/// Encodes the data as standard base64 with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(char::from(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize]));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
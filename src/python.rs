@@ -0,0 +1,145 @@
+//! Python module `imy`, for scripts that would rather import imy than spawn the binary. It is
+//! built with maturin from `pyproject.toml`.
+//!
+//! ```python
+//! import imy
+//!
+//! imy.convert("photos", "webp", quality=85)
+//! print(imy.info("photos/cat.jpg")["width"])
+//! result = imy.convert_batch(["a.png", "b.png"], "qoi")
+//! for path, message in result["failed"]:
+//!     print(path, message)
+//! ```
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use pyo3::{
+    create_exception,
+    exceptions::PyException,
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{error::Error, error_message, Context, ConvertOptions, OutputFormat};
+
+create_exception!(imy, ImyError, PyException, "Error of an imy command.");
+
+/// The `imy` module.
+#[pymodule]
+pub(crate) fn imy(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("ImyError", module.py().get_type::<ImyError>())?;
+    module.add_function(wrap_pyfunction!(convert, module)?)?;
+    module.add_function(wrap_pyfunction!(convert_batch, module)?)?;
+    module.add_function(wrap_pyfunction!(info, module)?)?;
+    Ok(())
+}
+
+/// Converts an image, or all images in a directory, to the format, e.g. "webp". Existing outputs
+/// are only replaced with `overwrite`.
+#[pyfunction]
+#[pyo3(signature = (path, format, *, quality=None, out_dir=None, overwrite=false))]
+fn convert(
+    py: Python<'_>,
+    path: PathBuf,
+    format: &str,
+    quality: Option<u8>,
+    out_dir: Option<PathBuf>,
+    overwrite: bool,
+) -> PyResult<()> {
+    let options = convert_options(format, quality, out_dir, overwrite, None);
+    py.allow_threads(|| convert_path(&path, &options))
+        .map_err(ImyError::new_err)
+}
+
+/// Converts the files in parallel, at most `jobs` at a time. Files that fail don't stop the
+/// others. Returns a dict with the "converted" paths and the "failed" paths with their messages.
+#[pyfunction]
+#[pyo3(signature = (paths, format, *, quality=None, out_dir=None, overwrite=false, jobs=None))]
+fn convert_batch<'py>(
+    py: Python<'py>,
+    paths: Vec<PathBuf>,
+    format: &str,
+    quality: Option<u8>,
+    out_dir: Option<PathBuf>,
+    overwrite: bool,
+    jobs: Option<usize>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let options = convert_options(format, quality, out_dir, overwrite, Some(1));
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(jobs) = jobs {
+        pool = pool.num_threads(jobs);
+    }
+    let pool = pool
+        .build()
+        .map_err(|source| ImyError::new_err(Error::ThreadPool(source).to_string()))?;
+    let results = py.allow_threads(|| {
+        pool.install(|| {
+            paths
+                .par_iter()
+                .map(|path| (path, convert_path(path, &options)))
+                .collect::<Vec<_>>()
+        })
+    });
+
+    let converted = PyList::empty(py);
+    let failed = PyList::empty(py);
+    for (path, result) in results {
+        match result {
+            Ok(()) => converted.append(path)?,
+            Err(message) => failed.append((path, message))?,
+        }
+    }
+    let summary = PyDict::new(py);
+    summary.set_item("converted", converted)?;
+    summary.set_item("failed", failed)?;
+    Ok(summary)
+}
+
+/// Returns the information of `imy info --output json` about an image, or a list for the images in
+/// a directory.
+#[pyfunction]
+fn info(py: Python<'_>, path: PathBuf) -> PyResult<Bound<'_, PyAny>> {
+    let json = py
+        .allow_threads(|| {
+            let mut json = Vec::new();
+            crate::info(
+                &mut Context::new(&mut json),
+                &path,
+                None,
+                OutputFormat::Json,
+                false,
+            )
+            .map(|()| json)
+        })
+        .map_err(|report| ImyError::new_err(error_message(&report)))?;
+    py.import("json")?
+        .call_method1("loads", (String::from_utf8_lossy(&json),))
+}
+
+fn convert_options(
+    format: &str,
+    quality: Option<u8>,
+    out_dir: Option<PathBuf>,
+    overwrite: bool,
+    jobs: Option<usize>,
+) -> ConvertOptions {
+    ConvertOptions {
+        target_format: format.to_owned(),
+        quality,
+        out_dir,
+        force: overwrite,
+        jobs,
+        ..Default::default()
+    }
+}
+
+/// Converts without printing and returns the message of the error.
+fn convert_path(path: &Path, options: &ConvertOptions) -> Result<(), String> {
+    let mut sink = io::sink();
+    crate::convert(&mut Context::new(&mut sink), path, options)
+        .map_err(|report| error_message(&report))
+}
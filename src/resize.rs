@@ -0,0 +1,142 @@
+use image::{
+    imageops, imageops::FilterType, ColorType, DynamicImage, GenericImageView, ImageBuffer, Pixel,
+};
+
+/// Strategy for resizing an image into a box, following the geometry semantics of ImageMagick.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Preserve the aspect ratio and fit into the box, e.g. "800x600" in ImageMagick
+    #[default]
+    Fit,
+    /// Preserve the aspect ratio and crop the center to exactly fill the box
+    Fill,
+    /// Ignore the aspect ratio and scale to exactly the box, e.g. "800x600!" in ImageMagick
+    Stretch,
+    /// Preserve the aspect ratio and cover the box without cropping, e.g. "800x600^" in
+    /// ImageMagick
+    Cover,
+    /// Preserve the aspect ratio, fit into the box and pad the rest with transparency
+    Contain,
+}
+
+/// Parses a box in which one of the dimensions may be left out, e.g. "800x600", "800x" or "x600".
+pub fn parse_box(value: &str) -> Result<(Option<u32>, Option<u32>), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected <width>x<height>: {value}"))?;
+    let parse = |dimension: &str| match dimension.trim() {
+        "" => Ok(None),
+        dimension => match dimension.parse::<u32>() {
+            Ok(0) => Err(format!("size must not be empty: {value}")),
+            Ok(dimension) => Ok(Some(dimension)),
+            Err(e) => Err(e.to_string()),
+        },
+    };
+    match (parse(width)?, parse(height)?) {
+        (None, None) => Err(format!("expected a width or a height: {value}")),
+        size => Ok(size),
+    }
+}
+
+/// Parses a scale factor given as percentage or fraction, e.g. "50%" or "0.5".
+pub fn parse_scale(value: &str) -> Result<f64, String> {
+    let scale = match value.trim().strip_suffix('%') {
+        Some(percentage) => percentage.trim().parse::<f64>().map(|p| p / 100.0),
+        None => value.trim().parse::<f64>(),
+    }
+    .map_err(|e| e.to_string())?;
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(format!("scale must be positive: {value}"));
+    }
+    Ok(scale)
+}
+
+/// Scales the image by the factor, keeping at least one pixel in each dimension.
+pub fn scale(img: &DynamicImage, scale: f64) -> DynamicImage {
+    let (width, height) = scaled_dimensions(img.dimensions(), scale);
+    img.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// Dimensions of an image of `size` after scaling it by the factor.
+pub fn scaled_dimensions((width, height): (u32, u32), scale: f64) -> (u32, u32) {
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Dimensions of an image of `size` after resizing it into a box of `width` by `height` pixels,
+/// matching the output of [`resize`].
+pub fn dimensions(size: (u32, u32), width: u32, height: u32, mode: Mode) -> (u32, u32) {
+    let ratio = |cover: bool| {
+        let ratios = (width as f64 / size.0 as f64, height as f64 / size.1 as f64);
+        if cover {
+            f64::max(ratios.0, ratios.1)
+        } else {
+            f64::min(ratios.0, ratios.1)
+        }
+    };
+    match mode {
+        Mode::Fit => {
+            let ratio = ratio(false);
+            (
+                ((size.0 as f64 * ratio).round() as u32).max(1),
+                ((size.1 as f64 * ratio).round() as u32).max(1),
+            )
+        }
+        Mode::Fill | Mode::Stretch | Mode::Contain => (width, height),
+        Mode::Cover => {
+            let ratio = ratio(true);
+            (
+                ((size.0 as f64 * ratio).round() as u32).max(width),
+                ((size.1 as f64 * ratio).round() as u32).max(height),
+            )
+        }
+    }
+}
+
+/// Resizes the image into a box of `width` by `height` pixels. The color type is preserved, except
+/// that `Contain` adds an alpha channel for the padding.
+pub fn resize(img: &DynamicImage, width: u32, height: u32, mode: Mode) -> DynamicImage {
+    let filter = FilterType::Lanczos3;
+    match mode {
+        Mode::Fit => img.resize(width, height, filter),
+        Mode::Fill => img.resize_to_fill(width, height, filter),
+        Mode::Stretch => img.resize_exact(width, height, filter),
+        Mode::Cover => {
+            let (cover_width, cover_height) = dimensions(img.dimensions(), width, height, mode);
+            img.resize_exact(cover_width, cover_height, filter)
+        }
+        Mode::Contain => {
+            let fitted = img.resize(width, height, filter);
+            match fitted.color() {
+                ColorType::L8 | ColorType::La8 => {
+                    pad(&fitted.to_luma_alpha8(), width, height).into()
+                }
+                ColorType::L16 | ColorType::La16 => {
+                    pad(&fitted.to_luma_alpha16(), width, height).into()
+                }
+                ColorType::Rgb16 | ColorType::Rgba16 => {
+                    pad(&fitted.to_rgba16(), width, height).into()
+                }
+                ColorType::Rgb32F | ColorType::Rgba32F => {
+                    pad(&fitted.to_rgba32f(), width, height).into()
+                }
+                _ => pad(&fitted.to_rgba8(), width, height).into(),
+            }
+        }
+    }
+}
+
+/// Centers the image on a transparent canvas of `width` by `height` pixels.
+fn pad<P: Pixel>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    width: u32,
+    height: u32,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let mut canvas = ImageBuffer::new(width, height);
+    let x = (width - img.width()) / 2;
+    let y = (height - img.height()) / 2;
+    imageops::replace(&mut canvas, img, x as i64, y as i64);
+    canvas
+}
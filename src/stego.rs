@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use image::DynamicImage;
+
+use crate::error::Error;
+
+/// Marks the start of the hidden data, so that images without hidden data or a wrong passphrase
+/// are detected instead of producing garbage.
+const MAGIC: &[u8; 4] = b"IMYS";
+
+/// Length of the header in bytes: the magic followed by the length of the data as big-endian u32.
+const HEADER_LENGTH: usize = MAGIC.len() + 4;
+
+/// Number of bytes of data that can be hidden in an image of the given size.
+pub fn capacity((width, height): (u32, u32)) -> usize {
+    (width as usize * height as usize * 3 / 8).saturating_sub(HEADER_LENGTH)
+}
+
+/// Hides the data in the least significant bits of the color channels of the image. Alpha is left
+/// untouched, because fully transparent pixels are often normalized by other tools. With a
+/// passphrase, the order of the bits is scrambled and the data is xored with a keystream derived
+/// from it. This obfuscates the data, but isn't encryption.
+pub fn embed(
+    img: &DynamicImage,
+    data: &[u8],
+    passphrase: Option<&str>,
+) -> miette::Result<DynamicImage> {
+    let available = capacity((img.width(), img.height()));
+    if data.len() > available || data.len() > u32::MAX as usize {
+        return Err(Error::StegoCapacity {
+            needed: data.len(),
+            available,
+        }
+        .into());
+    }
+
+    let has_alpha = img.color().has_alpha();
+    let mut buffer = img.to_rgba8();
+    let mut payload = Vec::with_capacity(HEADER_LENGTH + data.len());
+    payload.extend_from_slice(MAGIC);
+    payload.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    payload.extend_from_slice(data);
+
+    let mut scrambler = Scrambler::new(slot_count(&buffer), passphrase);
+    let values: &mut [u8] = &mut buffer;
+    for byte in payload {
+        let byte = byte ^ scrambler.next_key();
+        for bit in (0..8).rev() {
+            let index = channel_index(scrambler.next_slot());
+            values[index] = (values[index] & !1) | ((byte >> bit) & 1);
+        }
+    }
+
+    let img = DynamicImage::ImageRgba8(buffer);
+    Ok(if has_alpha {
+        img
+    } else {
+        DynamicImage::ImageRgb8(img.into_rgb8())
+    })
+}
+
+/// Recovers data that was hidden with `embed` using the same passphrase.
+pub fn extract(img: &DynamicImage, passphrase: Option<&str>) -> miette::Result<Vec<u8>> {
+    let available = capacity((img.width(), img.height()));
+    if available == 0 {
+        return Err(Error::StegoNoData.into());
+    }
+    let buffer = img.to_rgba8();
+    let values: &[u8] = &buffer;
+    let mut scrambler = Scrambler::new(slot_count(&buffer), passphrase);
+    let mut read = |count: usize| {
+        (0..count)
+            .map(|_| {
+                let key = scrambler.next_key();
+                let byte = (0..8).fold(0u8, |byte, _| {
+                    (byte << 1) | (values[channel_index(scrambler.next_slot())] & 1)
+                });
+                byte ^ key
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let header = read(HEADER_LENGTH);
+    let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if &header[..MAGIC.len()] != MAGIC || length > available {
+        return Err(Error::StegoNoData.into());
+    }
+    Ok(read(length))
+}
+
+/// Number of color channels that can hold a hidden bit.
+fn slot_count(buffer: &image::RgbaImage) -> usize {
+    buffer.width() as usize * buffer.height() as usize * 3
+}
+
+/// Maps the index of a color channel to the index in the RGBA buffer, skipping alpha.
+fn channel_index(slot: usize) -> usize {
+    slot / 3 * 4 + slot % 3
+}
+
+/// Yields the channels in which the bits are hidden and the keys with which the bytes are
+/// xored. Without a passphrase, the channels are used in order and the keys are zero.
+struct Scrambler {
+    /// Lazy Fisher-Yates shuffle that only stores the swapped entries, so that the memory depends
+    /// on the size of the data instead of the size of the image
+    swapped: HashMap<usize, usize>,
+    position: usize,
+    count: usize,
+    state: Option<u64>,
+}
+
+impl Scrambler {
+    fn new(count: usize, passphrase: Option<&str>) -> Self {
+        Self {
+            swapped: HashMap::new(),
+            position: 0,
+            count,
+            state: passphrase.map(|passphrase| hash(passphrase.as_bytes())),
+        }
+    }
+
+    fn next_random(&mut self) -> Option<u64> {
+        let state = self.state.as_mut()?;
+        // xorshift64, not cryptographically secure but enough to hide the data from a casual look
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        Some(*state)
+    }
+
+    fn next_key(&mut self) -> u8 {
+        self.next_random().map_or(0, |random| (random >> 56) as u8)
+    }
+
+    fn next_slot(&mut self) -> usize {
+        let position = self.position;
+        self.position += 1;
+        let Some(random) = self.next_random() else {
+            return position;
+        };
+        let other = position + (random % (self.count - position) as u64) as usize;
+        let value = *self.swapped.get(&other).unwrap_or(&other);
+        let current = *self.swapped.get(&position).unwrap_or(&position);
+        self.swapped.insert(other, current);
+        value
+    }
+}
+
+/// FNV-1a hash of the passphrase, never zero because xorshift would get stuck.
+fn hash(bytes: &[u8]) -> u64 {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash.max(1)
+}
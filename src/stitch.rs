@@ -0,0 +1,153 @@
+use image::{imageops::FilterType, DynamicImage, GrayImage, Rgba32FImage};
+
+/// Largest width of the images that are compared to find the coarse alignment.
+const COARSE_WIDTH: u32 = 256;
+
+/// Stitches a sequence of overlapping shots, ordered from left to right, into a panorama.
+///
+/// Neighboring images are aligned by a translation that minimizes the mean absolute difference of
+/// their overlapping regions. The search runs on downscaled images first and is refined at full
+/// resolution. Overlaps are blended with weights that fall off towards the image borders, which
+/// hides seams caused by small differences in exposure.
+pub fn stitch(images: &[DynamicImage]) -> DynamicImage {
+    let mut positions = vec![(0i64, 0i64)];
+    for pair in images.windows(2) {
+        let (dx, dy) = align(&pair[0], &pair[1]);
+        tracing::debug!("Offset between neighboring images: ({dx}, {dy})");
+        let (x, y) = positions[positions.len() - 1];
+        positions.push((x + dx, y + dy));
+    }
+
+    let min_x = positions.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let min_y = positions.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    let max_x = positions
+        .iter()
+        .zip(images)
+        .map(|((x, _), img)| x + img.width() as i64)
+        .max()
+        .unwrap_or(0);
+    let max_y = positions
+        .iter()
+        .zip(images)
+        .map(|((_, y), img)| y + img.height() as i64)
+        .max()
+        .unwrap_or(0);
+    let (width, height) = ((max_x - min_x) as u32, (max_y - min_y) as u32);
+
+    let mut sum = Rgba32FImage::new(width, height);
+    let mut weights = vec![0f32; width as usize * height as usize];
+    for ((x, y), img) in positions.iter().zip(images) {
+        let (offset_x, offset_y) = ((x - min_x) as u32, (y - min_y) as u32);
+        let rgba = img.to_rgba32f();
+        for (px, py, pixel) in rgba.enumerate_pixels() {
+            let weight = feather(px, img.width()) * feather(py, img.height());
+            let (cx, cy) = (offset_x + px, offset_y + py);
+            let target = sum.get_pixel_mut(cx, cy);
+            for channel in 0..4 {
+                target[channel] += pixel[channel] * weight;
+            }
+            weights[(cy * width + cx) as usize] += weight;
+        }
+    }
+    for (index, pixel) in sum.pixels_mut().enumerate() {
+        let weight = weights[index];
+        if weight > 0.0 {
+            for channel in &mut pixel.0 {
+                *channel /= weight;
+            }
+        }
+    }
+
+    let panorama = DynamicImage::ImageRgba32F(sum);
+    if images.iter().any(|img| img.color().has_alpha()) {
+        DynamicImage::ImageRgba8(panorama.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(panorama.to_rgb8())
+    }
+}
+
+/// Weight of a pixel that increases linearly from the border towards the center of the image.
+fn feather(position: u32, size: u32) -> f32 {
+    let distance = position.min(size - 1 - position) as f32 + 1.0;
+    distance / (size as f32 / 2.0)
+}
+
+/// Finds the position of `right` relative to `left`.
+fn align(left: &DynamicImage, right: &DynamicImage) -> (i64, i64) {
+    let scale = (COARSE_WIDTH as f32 / left.width() as f32).min(1.0);
+    let downscale = |img: &DynamicImage| {
+        let width = ((img.width() as f32 * scale).round() as u32).max(1);
+        let height = ((img.height() as f32 * scale).round() as u32).max(1);
+        img.resize_exact(width, height, FilterType::Triangle)
+            .to_luma8()
+    };
+    let (small_left, small_right) = (downscale(left), downscale(right));
+    let max_dy = small_left.height() as i64 / 10;
+    let (coarse_dx, coarse_dy) = search(
+        &small_left,
+        &small_right,
+        small_left.width() as i64 / 10..=small_left.width() as i64 * 9 / 10,
+        -max_dy..=max_dy,
+        1,
+    );
+
+    if scale == 1.0 {
+        return (coarse_dx, coarse_dy);
+    }
+    let (left, right) = (left.to_luma8(), right.to_luma8());
+    let radius = (1.0 / scale).ceil() as i64;
+    let (dx, dy) = (
+        (coarse_dx as f32 / scale).round() as i64,
+        (coarse_dy as f32 / scale).round() as i64,
+    );
+    search(
+        &left,
+        &right,
+        dx - radius..=dx + radius,
+        dy - radius..=dy + radius,
+        (1.0 / scale) as usize,
+    )
+}
+
+fn search(
+    left: &GrayImage,
+    right: &GrayImage,
+    dx_range: std::ops::RangeInclusive<i64>,
+    dy_range: std::ops::RangeInclusive<i64>,
+    step: usize,
+) -> (i64, i64) {
+    let mut best = (f32::MAX, (*dx_range.start(), 0));
+    for dy in dy_range {
+        for dx in dx_range.clone() {
+            if let Some(cost) = cost(left, right, dx, dy, step) {
+                if cost < best.0 {
+                    best = (cost, (dx, dy));
+                }
+            }
+        }
+    }
+    best.1
+}
+
+/// Mean absolute difference of the overlap when `right` is placed at `(dx, dy)` relative to
+/// `left`, or `None` if the overlap is too small to be meaningful.
+fn cost(left: &GrayImage, right: &GrayImage, dx: i64, dy: i64, step: usize) -> Option<f32> {
+    let x0 = dx.max(0);
+    let y0 = dy.max(0);
+    let x1 = (left.width() as i64).min(dx + right.width() as i64);
+    let y1 = (left.height() as i64).min(dy + right.height() as i64);
+    if x1 - x0 < 4 || (y1 - y0) < left.height() as i64 / 2 {
+        return None;
+    }
+    let mut sum = 0u64;
+    let mut count = 0u64;
+    for y in (y0..y1).step_by(step) {
+        for x in (x0..x1).step_by(step) {
+            let a = left.get_pixel(x as u32, y as u32)[0] as i64;
+            let b = right.get_pixel((x - dx) as u32, (y - dy) as u32)[0] as i64;
+            sum += (a - b).unsigned_abs();
+            count += 1;
+        }
+    }
+    (count > 0).then(|| sum as f32 / count as f32)
+}
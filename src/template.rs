@@ -0,0 +1,28 @@
+use crate::error::Error;
+
+/// Replaces every `{name}` placeholder in the template with the value returned by `lookup`.
+/// Placeholders for which `lookup` returns `None` are reported as an error listing `valid`.
+pub fn render(
+    template: &str,
+    valid: &[&str],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> miette::Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            output.push_str(&rest[start..]);
+            return Ok(output);
+        };
+        let name = &rest[start + 1..start + end];
+        let value = lookup(name).ok_or_else(|| Error::UnknownPlaceholder {
+            placeholder: name.to_owned(),
+            help: format!("Valid placeholders are: {}", valid.join(", ")),
+        })?;
+        output.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
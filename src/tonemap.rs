@@ -0,0 +1,56 @@
+use image::{DynamicImage, Rgba32FImage};
+
+/// Operator that maps unbounded HDR values into the displayable range.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Operator {
+    /// Clamp values to the displayable range without compressing highlights
+    Clamp,
+    /// Reinhard's global operator `x / (1 + x)`
+    #[default]
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+    /// Hable's "Uncharted 2" filmic curve
+    Filmic,
+}
+
+/// Tone maps a linear HDR image into the range `[0, 1]`, so that it can be stored in 8-bit or
+/// 16-bit formats. The result is still linear.
+pub fn tonemap(img: &DynamicImage, operator: Operator, exposure: f32) -> DynamicImage {
+    let scale = 2f32.powf(exposure);
+    let mut buffer: Rgba32FImage = img.to_rgba32f();
+    for pixel in buffer.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            let mapped = map(operator, *channel * scale);
+            *channel = mapped.clamp(0.0, 1.0);
+        }
+    }
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba32F(buffer)
+    } else {
+        DynamicImage::ImageRgb32F(DynamicImage::ImageRgba32F(buffer).into_rgb32f())
+    }
+}
+
+fn map(operator: Operator, x: f32) -> f32 {
+    match operator {
+        Operator::Clamp => x,
+        Operator::Reinhard => x / (1.0 + x),
+        Operator::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        Operator::Filmic => {
+            const WHITE_POINT: f32 = 11.2;
+            const EXPOSURE_BIAS: f32 = 2.0;
+            hable(x * EXPOSURE_BIAS) / hable(WHITE_POINT)
+        }
+    }
+}
+
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}